@@ -0,0 +1,108 @@
+//! ANSI SGR colour sequences for terminal output
+
+use std::fmt;
+use unicode_width::UnicodeWidthStr;
+
+/// Sequence that restores the default foreground colour
+pub const RESET: &str = "\x1b[39m";
+
+/// Highlight colour for numbers
+pub const NUMBER: Color = Color::Ansi(36);
+/// Highlight colour for identifiers
+pub const IDENT: Color = Color::Ansi(34);
+/// Highlight colour for operators
+pub const OPERATOR: Color = Color::Ansi(35);
+
+/// A terminal foreground colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// One of the basic SGR colour codes
+    Ansi(u8),
+    /// A 24 bit colour
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Parse a named colour or a `#rgb` / `#rrggbb` hex colour
+    pub fn parse(name: &str) -> Option<Self> {
+        if let Some(hex) = name.strip_prefix('#') {
+            let digit = |ind: usize, len: usize| {
+                hex.get(ind * len..(ind + 1) * len)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+            };
+            match hex.len() {
+                3 => Some(Color::Rgb(
+                    digit(0, 1)? * 17,
+                    digit(1, 1)? * 17,
+                    digit(2, 1)? * 17,
+                )),
+                6 => Some(Color::Rgb(digit(0, 2)?, digit(1, 2)?, digit(2, 2)?)),
+                _ => None,
+            }
+        } else {
+            let code = match name.to_ascii_lowercase().as_str() {
+                "black" => 30,
+                "red" => 31,
+                "green" => 32,
+                "yellow" => 33,
+                "blue" => 34,
+                "magenta" | "purple" => 35,
+                "cyan" => 36,
+                "white" => 37,
+                "gray" | "grey" => 90,
+                "orange" => return Some(Color::Rgb(255, 165, 0)),
+                _ => return None,
+            };
+            Some(Color::Ansi(code))
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::Ansi(code) => write!(f, "\x1b[{code}m"),
+            Color::Rgb(r, g, b) => write!(f, "\x1b[38;2;{r};{g};{b}m"),
+        }
+    }
+}
+
+/// The display width of `text` ignoring any SGR sequences
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1b[") {
+        width += UnicodeWidthStr::width(&rest[..start]);
+        rest = &rest[start + 2..];
+        rest = rest.find('m').map_or("", |end| &rest[end + 1..]);
+    }
+    width + UnicodeWidthStr::width(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, display_width};
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(Color::parse("red"), Some(Color::Ansi(31)));
+        assert_eq!(Color::parse("Blue"), Some(Color::Ansi(34)));
+        assert_eq!(Color::parse("#f80"), Some(Color::Rgb(255, 136, 0)));
+        assert_eq!(Color::parse("#102030"), Some(Color::Rgb(16, 32, 48)));
+        assert_eq!(Color::parse("#12345"), None);
+        assert_eq!(Color::parse("#gg0000"), None);
+        assert_eq!(Color::parse("rouge"), None);
+    }
+
+    #[test]
+    fn sequences() {
+        assert_eq!(Color::Ansi(31).to_string(), "\x1b[31m");
+        assert_eq!(Color::Rgb(1, 2, 3).to_string(), "\x1b[38;2;1;2;3m");
+    }
+
+    #[test]
+    fn width_ignores_sequences() {
+        assert_eq!(display_width("\x1b[31mx\x1b[39m+\x1b[38;2;1;2;3m全"), 4);
+        assert_eq!(display_width("plain"), 5);
+    }
+}
@@ -0,0 +1,141 @@
+//! Pure ascii fallbacks for rendered unicode
+
+use unicode_normalization::char::decompose_canonical;
+
+use super::from_unicode::Output;
+
+/// Convert rendered unicode into readable ascii
+///
+/// Symbols become their asciimath names or digraphs, and anything asciimath can't name falls back
+/// to its unaccented base letter or `?`.
+pub fn to_ascii(rendered: &str) -> String {
+    let mut digraphs = String::with_capacity(rendered.len());
+    for chr in rendered.chars() {
+        match digraph(chr) {
+            Some(replacement) => digraphs.push_str(replacement),
+            None => digraphs.push(chr),
+        }
+    }
+    let converted = converted(&digraphs);
+    if converted.is_ascii() {
+        converted
+    } else {
+        converted
+            .chars()
+            .filter_map(|chr| {
+                if chr.is_ascii() {
+                    Some(chr)
+                } else {
+                    let mut base = None;
+                    decompose_canonical(chr, |part| {
+                        if base.is_none() {
+                            base = Some(part);
+                        }
+                    });
+                    match base {
+                        Some(base) if base.is_ascii() => Some(base),
+                        // a lone combining mark has nothing to fall back to
+                        _ if is_combining(chr) => None,
+                        _ => Some('?'),
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Convert to asciimath, copying through the colour sequences of highlighted output
+fn converted(rendered: &str) -> String {
+    let mut out = Output::default();
+    let mut rest = rendered;
+    while let Some(start) = rest.find("\x1b[") {
+        out.convert(&rest[..start]);
+        let len = escape_len(&rest[start..]).unwrap_or(rest.len() - start);
+        out.raw(&rest[start..start + len]);
+        rest = &rest[start + len..];
+    }
+    out.convert(rest);
+    out.into_text()
+}
+
+/// The length of the ANSI escape sequence at the start of `text`
+fn escape_len(text: &str) -> Option<usize> {
+    let params = text.strip_prefix("\x1b[")?;
+    let end = params.find(|c| ('@'..='~').contains(&c))?;
+    Some(end + 3)
+}
+
+/// Digraphs that read better than the asciimath name of a symbol
+fn digraph(chr: char) -> Option<&'static str> {
+    match chr {
+        '→' => Some("->"),
+        '←' => Some("<-"),
+        '↔' => Some("<->"),
+        '⇒' => Some("=>"),
+        '⇐' => Some("<=="),
+        '⇔' => Some("<=>"),
+        '↦' => Some("|->"),
+        _ => None,
+    }
+}
+
+fn is_combining(chr: char) -> bool {
+    matches!(chr, '\u{0300}'..='\u{036f}' | '\u{20d0}'..='\u{20ff}')
+}
+
+/// The ascii stand in for a block drawing character, keeping its single column width
+pub fn glyph(chr: char) -> char {
+    match chr {
+        '─' | '━' | '┄' => '-',
+        '│' | '┃' | '⎜' | '⎟' | '⎢' | '⎥' | '⎮' => '|',
+        '╱' | '⎛' | '⎠' | '⎧' | '⎭' | '⎰' | '⌠' | '⌡' | '╯' | '╭' | '⎳' => {
+            '/'
+        }
+        '╲' | '⎝' | '⎞' | '⎩' | '⎫' | '⎱' | '╰' | '╮' | '⎲' => '\\',
+        '⎡' | '⎣' | '⌈' | '⌊' => '[',
+        '⎤' | '⎦' | '⌉' | '⌋' => ']',
+        '⎨' | '⟨' => '<',
+        '⎬' | '⟩' => '>',
+        '┬' | '⏟' => 'v',
+        '┴' | '⏞' => '^',
+        '┼' | '╋' => '+',
+        chr if chr.is_ascii() => chr,
+        _ => '?',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glyph, to_ascii};
+
+    #[test]
+    fn symbols_become_names() {
+        assert_eq!(to_ascii("α≤β≠γ"), "alpha<=beta!=gamma");
+        assert_eq!(to_ascii("√(x+1)→∞"), "sqrt(x+1)->oo");
+        assert_eq!(to_ascii("∑₍ᵢ₌₁₎ⁿi²"), "sum_(i=1)^n i^2");
+    }
+
+    #[test]
+    fn fallbacks() {
+        assert_eq!(to_ascii("héllo"), "hello");
+        assert_eq!(to_ascii("✋"), "?");
+    }
+
+    #[test]
+    fn escapes_pass_through() {
+        assert_eq!(
+            to_ascii("\x1b[34mα\x1b[39m\x1b[34mβ\x1b[39m"),
+            "\x1b[34malpha\x1b[39m\x1b[34m beta\x1b[39m"
+        );
+        assert_eq!(
+            to_ascii("\x1b[34mx\x1b[39m\x1b[36m²\x1b[39m"),
+            "\x1b[34mx\x1b[39m\x1b[36m^2\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn glyphs() {
+        let drawn: String = "⎛─╱╲⎞".chars().map(glyph).collect();
+        assert_eq!(drawn, "/-/\\\\");
+    }
+}
@@ -1,35 +1,51 @@
 #![allow(missing_docs, clippy::must_use_candidate)]
 
-use asciimath_parser::tree::{Expression, Intermediate, Script, ScriptFunc, Simple, SimpleScript};
+use asciimath_parser::tree::{
+    Expression, Intermediate, Matrix, Script, ScriptFunc, Simple, SimpleScript, SimpleUnary,
+};
+
+use super::tokens::{left_bracket_str, right_bracket_str};
+
+const VULGAR_FRACS: [(&str, &str, char); 24] = [
+    ("0", "3", '↉'),
+    ("1", "10", '⅒'),
+    ("1", "9", '⅑'),
+    ("1", "8", '⅛'),
+    ("1", "7", '⅐'),
+    ("1", "6", '⅙'),
+    ("1", "5", '⅕'),
+    ("1", "4", '¼'),
+    ("1", "3", '⅓'),
+    ("1", "2", '½'),
+    ("2", "5", '⅖'),
+    ("2", "3", '⅔'),
+    ("3", "8", '⅜'),
+    ("3", "5", '⅗'),
+    ("3", "4", '¾'),
+    ("4", "5", '⅘'),
+    ("5", "8", '⅝'),
+    ("5", "6", '⅚'),
+    ("7", "8", '⅞'),
+    ("a", "c", '℀'),
+    ("a", "s", '℁'),
+    ("A", "S", '⅍'),
+    ("c", "o", '℅'),
+    ("c", "u", '℆'),
+];
 
 fn vulgar_frac_char(num: &str, den: &str) -> Option<char> {
-    match (num, den) {
-        ("0", "3") => Some('↉'),
-        ("1", "10") => Some('⅒'),
-        ("1", "9") => Some('⅑'),
-        ("1", "8") => Some('⅛'),
-        ("1", "7") => Some('⅐'),
-        ("1", "6") => Some('⅙'),
-        ("1", "5") => Some('⅕'),
-        ("1", "4") => Some('¼'),
-        ("1", "3") => Some('⅓'),
-        ("1", "2") => Some('½'),
-        ("2", "5") => Some('⅖'),
-        ("2", "3") => Some('⅔'),
-        ("3", "8") => Some('⅜'),
-        ("3", "5") => Some('⅗'),
-        ("3", "4") => Some('¾'),
-        ("4", "5") => Some('⅘'),
-        ("5", "8") => Some('⅝'),
-        ("5", "6") => Some('⅚'),
-        ("7", "8") => Some('⅞'),
-        ("a", "c") => Some('℀'),
-        ("a", "s") => Some('℁'),
-        ("A", "S") => Some('⅍'),
-        ("c", "o") => Some('℅'),
-        ("c", "u") => Some('℆'),
-        _ => None,
-    }
+    VULGAR_FRACS
+        .iter()
+        .find(|&&(n, d, _)| n == num && d == den)
+        .map(|&(_, _, chr)| chr)
+}
+
+/// The numerator and denominator of a vulgar fraction character
+pub fn vulgar_frac_parts(chr: char) -> Option<(&'static str, &'static str)> {
+    VULGAR_FRACS
+        .iter()
+        .find(|&&(_, _, c)| c == chr)
+        .map(|&(num, den, _)| (num, den))
 }
 
 pub fn extract_single_char(expr: &Expression<'_>) -> Option<char> {
@@ -79,3 +95,289 @@ pub fn extract_vulgar_frac(numer: &Simple<'_>, denom: &Simple<'_>, strip: bool)
     let den = extract_simple_str(denom, strip)?;
     vulgar_frac_char(num, den)
 }
+
+/// The cells of an expression made of bracketed rows with the same number of cells
+///
+/// The parser only builds a matrix when the outer and row brackets match, so cases like
+/// `{(x, if x > 0), (-x, otherwise):}` are a group of comma separated `(...)` groups instead.
+pub fn extract_rows<'e, 'a>(
+    expr: &'e [Intermediate<'a>],
+) -> Option<Vec<Vec<&'e [Intermediate<'a>]>>> {
+    let mut rows = Vec::new();
+    for (ind, part) in expr.iter().enumerate() {
+        match part {
+            Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript {
+                simple: Simple::Group(group),
+                script: Script::None,
+            })) if ind % 2 == 0
+                && left_bracket_str(group.left_bracket) == "("
+                && right_bracket_str(group.right_bracket) == ")" =>
+            {
+                rows.push(group.expr.split(is_comma).collect::<Vec<_>>());
+            }
+            part if ind % 2 == 1 && is_comma(part) => {}
+            _ => return None,
+        }
+    }
+    let cols = rows.first()?.len();
+    (expr.len() % 2 == 1 && rows.iter().all(|row| row.len() == cols)).then_some(rows)
+}
+
+/// A bare single letter identifier, as each letter of a word like `otherwise` is tokenized
+fn letter<'a>(inter: &Intermediate<'a>) -> Option<&'a str> {
+    match inter {
+        Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript {
+            simple: Simple::Ident(ident),
+            script: Script::None,
+        })) if ident.chars().count() == 1 && ident.starts_with(char::is_alphabetic) => Some(ident),
+        _ => None,
+    }
+}
+
+/// The number of letters written together in the source at the start of `expr`
+fn word_len(expr: &[Intermediate<'_>]) -> usize {
+    if expr.first().and_then(letter).is_none() {
+        return 0;
+    }
+    1 + expr
+        .windows(2)
+        .take_while(|pair| match (letter(&pair[0]), letter(&pair[1])) {
+            (Some(prev), Some(next)) => prev.as_ptr().addr() + prev.len() == next.as_ptr().addr(),
+            _ => false,
+        })
+        .count()
+}
+
+/// A word like the `otherwise` of a case condition at the start of `expr`, and what follows it
+///
+/// The parser tokenizes each letter of an unknown word separately, so a word is a run of two or
+/// more single letters written together in the source.
+pub fn split_word<'e, 'a>(
+    expr: &'e [Intermediate<'a>],
+) -> Option<(String, &'e [Intermediate<'a>])> {
+    let len = word_len(expr);
+    (len > 1).then(|| {
+        (
+            expr[..len].iter().filter_map(letter).collect(),
+            &expr[len..],
+        )
+    })
+}
+
+/// A unary operator written directly after a single letter at the start of `expr`, with the
+/// letter and the script on the operator
+///
+/// Names like `hbar` or `lceil` aren't asciimath tokens, so they're tokenized as a letter directly
+/// followed by an operator like `bar` or `ceil`.
+pub fn fused_unary<'e, 'a>(
+    expr: &'e [Intermediate<'a>],
+) -> Option<(&'a str, &'e SimpleUnary<'a>, &'e Script<'a>)> {
+    if let [first, second, ..] = expr
+        && let Some(letter) = letter(first)
+        && let Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript {
+            simple: Simple::Unary(unary),
+            script,
+        })) = second
+        && letter.as_ptr().addr() + letter.len() == unary.op.as_ptr().addr()
+    {
+        Some((letter, unary, script))
+    } else {
+        None
+    }
+}
+
+fn is_comma(inter: &Intermediate<'_>) -> bool {
+    matches!(
+        inter,
+        Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript {
+            simple: Simple::Symbol(","),
+            script: Script::None,
+        }))
+    )
+}
+
+/// Which columns of a matrix hold nothing but a bare `|`, like the third in `[[1,2,|,3],[4,5,|,6]]`
+pub fn rule_columns(matrix: &Matrix<'_>) -> Vec<bool> {
+    let mut rules = vec![true; matrix.num_cols()];
+    for row in matrix.rows() {
+        for (rule, expr) in rules.iter_mut().zip(row) {
+            *rule &= matches!(
+                &**expr,
+                [Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript {
+                    simple: Simple::Symbol("|" | ":|:"),
+                    script: Script::None,
+                }))]
+            );
+        }
+    }
+    rules
+}
+
+/// Rebuilds source text from the tokens of a tree, which all borrow from the source
+#[derive(Debug, Default)]
+struct SourceText {
+    out: String,
+    /// The address just past the last source token
+    end: Option<usize>,
+}
+
+impl SourceText {
+    /// A token sliced from the source, after a space if whitespace separated it from the last one
+    fn token(&mut self, tok: &str) {
+        if tok.is_empty() {
+            return;
+        }
+        let start = tok.as_ptr().addr();
+        if self.end.is_some_and(|end| start > end) {
+            self.out.push(' ');
+        }
+        self.out.push_str(tok);
+        self.end = Some(start + tok.len());
+    }
+
+    /// Syntax the tree doesn't keep, like the `_` of a subscript, assumed to be written unspaced
+    fn implied(&mut self, syntax: &str) {
+        self.out.push_str(syntax);
+        if let Some(end) = &mut self.end {
+            *end += syntax.len();
+        }
+    }
+
+    fn script(&mut self, script: &Script<'_>) {
+        if let Some(sub) = script.sub() {
+            self.implied("_");
+            self.simple(sub);
+        }
+        if let Some(sup) = script.sup() {
+            self.implied("^");
+            self.simple(sup);
+        }
+    }
+
+    fn scriptfunc(&mut self, func: &ScriptFunc<'_>) {
+        match func {
+            ScriptFunc::Simple(SimpleScript { simple, script }) => {
+                self.simple(simple);
+                self.script(script);
+            }
+            ScriptFunc::Func(func) => {
+                self.token(func.func);
+                self.script(&func.script);
+                self.scriptfunc(func.arg());
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &[Intermediate<'_>]) {
+        for inter in expr {
+            match inter {
+                Intermediate::ScriptFunc(func) => self.scriptfunc(func),
+                Intermediate::Frac(frac) => {
+                    self.scriptfunc(&frac.numer);
+                    self.implied("/");
+                    self.scriptfunc(&frac.denom);
+                }
+            }
+        }
+    }
+
+    fn simple(&mut self, simple: &Simple<'_>) {
+        match simple {
+            Simple::Missing => {}
+            &(Simple::Number(s) | Simple::Text(s) | Simple::Ident(s) | Simple::Symbol(s)) => {
+                self.token(s);
+            }
+            Simple::Unary(unary) => {
+                self.token(unary.op);
+                self.simple(unary.arg());
+            }
+            Simple::Func(func) => {
+                self.token(func.func);
+                self.simple(func.arg());
+            }
+            Simple::Binary(binary) => {
+                self.token(binary.op);
+                self.simple(binary.first());
+                self.simple(binary.second());
+            }
+            Simple::Group(group) => {
+                self.token(group.left_bracket);
+                self.expression(&group.expr);
+                self.token(group.right_bracket);
+            }
+            Simple::Matrix(matrix) => {
+                // rows are only matrices when their brackets match the outer ones
+                self.token(matrix.left_bracket);
+                for (ind, row) in matrix.rows().enumerate() {
+                    if ind > 0 {
+                        self.implied(",");
+                    }
+                    self.implied(matrix.left_bracket);
+                    for (col, cell) in row.iter().enumerate() {
+                        if col > 0 {
+                            self.implied(",");
+                        }
+                        self.expression(cell);
+                    }
+                    self.implied(matrix.right_bracket);
+                }
+                self.token(matrix.right_bracket);
+            }
+        }
+    }
+}
+
+/// The source text of an argument like the `if x` in `text(if x)`, with its outer brackets
+/// removed
+///
+/// Tokens that whitespace separated in the source are separated by a single space.
+pub fn extract_text(simple: &Simple<'_>) -> String {
+    let mut text = SourceText::default();
+    match simple {
+        Simple::Group(group) => text.expression(&group.expr),
+        simple => text.simple(simple),
+    }
+    text.out
+}
+
+/// The raw source text of an argument like the `red` in `color(red)(x)`, with any brackets removed
+pub fn extract_raw_text(simple: &Simple<'_>) -> String {
+    fn push_simple(simple: &Simple<'_>, out: &mut String) {
+        match simple {
+            &(Simple::Number(s) | Simple::Text(s) | Simple::Ident(s) | Simple::Symbol(s)) => {
+                out.push_str(s);
+            }
+            // letters like the `f` in a hex colour are function tokens
+            Simple::Func(func) => {
+                out.push_str(func.func);
+                push_simple(func.arg(), out);
+            }
+            Simple::Group(group) => {
+                for inter in group.expr.iter() {
+                    if let Intermediate::ScriptFunc(func) = inter {
+                        push_scriptfunc(func, out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push_scriptfunc(func: &ScriptFunc<'_>, out: &mut String) {
+        match func {
+            ScriptFunc::Simple(SimpleScript {
+                simple,
+                script: Script::None,
+            }) => push_simple(simple, out),
+            ScriptFunc::Func(func) if matches!(func.script, Script::None) => {
+                out.push_str(func.func);
+                push_scriptfunc(func.arg(), out);
+            }
+            ScriptFunc::Simple(_) | ScriptFunc::Func(_) => {}
+        }
+    }
+
+    let mut out = String::new();
+    push_simple(simple, &mut out);
+    out
+}
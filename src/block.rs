@@ -1,10 +1,13 @@
 #![allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
 
-use std::{fmt, iter};
-use unicode_width::UnicodeWidthStr;
+use std::{fmt, iter, mem};
 
 use super::Conf;
-use super::inline::{Mapper, MapperConf};
+use super::ansi::{Color, RESET, display_width};
+use super::ascii::{self, to_ascii};
+use super::ast::{extract_raw_text, extract_rows, extract_simple_str, rule_columns};
+use super::explain::{self, Layout};
+use super::inline::{Mapper, MapperConf, Sink};
 use super::tokens::{left_bracket_str, right_bracket_str, subscript_char, superscript_char};
 
 use asciimath_parser::tree::{
@@ -24,7 +27,7 @@ pub struct Block {
 impl Block {
     fn text(text: impl Into<String>) -> Self {
         let owned = text.into();
-        let width = UnicodeWidthStr::width(&*owned);
+        let width = display_width(&owned);
         Block {
             lines: vec![owned],
             baseline: 0,
@@ -48,6 +51,16 @@ impl Block {
         }
     }
 
+    /// The rendered lines, each padded to `width` display columns
+    pub(crate) fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// The width in display columns
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
     fn height(&self) -> usize {
         self.lines.len()
     }
@@ -95,9 +108,21 @@ impl Block {
         }
     }
 
-    fn stack_frac(numer: Self, denom: Self) -> Self {
+    /// Whether `other` placed beside this would touch it in some line
+    fn touches(&self, other: &Self) -> bool {
+        self.lines.iter().enumerate().any(|(row, line)| {
+            (row + other.baseline)
+                .checked_sub(self.baseline)
+                .and_then(|other_row| other.lines.get(other_row))
+                .is_some_and(|next| {
+                    line.ends_with(|chr| chr != ' ') && next.starts_with(|chr| chr != ' ')
+                })
+        })
+    }
+
+    fn stack_frac(numer: Self, denom: Self, style: BlockStyle) -> Self {
         let bar_width = numer.width.max(denom.width);
-        let bar = "─".repeat(bar_width);
+        let bar = style.glyph('─').to_string().repeat(bar_width);
         let baseline = numer.lines.len();
 
         let lines = numer
@@ -120,12 +145,46 @@ impl Block {
         }
     }
 
-    fn with_brackets(self, left: &str, right: &str) -> Self {
+    /// Stack blocks vertically with the given alignment, with the baseline on the first line
+    fn stack(parts: Vec<Self>, align: Align) -> Self {
+        let width = parts
+            .iter()
+            .map(|part| part.width)
+            .max()
+            .unwrap_or_default();
+        let lines = parts
+            .into_iter()
+            .flat_map(|part| {
+                let part_width = part.width;
+                part.lines
+                    .into_iter()
+                    .map(move |line| align_pad(&line, part_width, width, align))
+            })
+            .collect();
+        Block {
+            lines,
+            baseline: 0,
+            width,
+        }
+    }
+
+    /// Center optional limits above and below a base, keeping the base's baseline
+    fn under_over(base: Self, over: Option<Self>, under: Option<Self>) -> Self {
+        let baseline = over.as_ref().map_or(0, Block::height) + base.baseline;
+        let parts = over.into_iter().chain([base]).chain(under).collect();
+        Block::stack(parts, Align::Center).with_baseline(baseline)
+    }
+
+    fn with_brackets(self, left: &str, right: &str, style: BlockStyle) -> Self {
         if left.is_empty() && right.is_empty() {
             self
         } else {
-            let left_col = tall_bracket_left(left, self.height()).with_baseline(self.baseline);
-            let right_col = tall_bracket_right(right, self.height()).with_baseline(self.baseline);
+            let left_col = tall_bracket_left(left, self.height())
+                .map_glyphs(|chr| style.glyph(chr))
+                .with_baseline(self.baseline);
+            let right_col = tall_bracket_right(right, self.height())
+                .map_glyphs(|chr| style.glyph(chr))
+                .with_baseline(self.baseline);
             let new_baseline = self.height() / 2;
             left_col
                 .beside(self)
@@ -134,6 +193,27 @@ impl Block {
         }
     }
 
+    /// Replace every character with a glyph of the same width
+    fn map_glyphs(mut self, map: impl Fn(char) -> char) -> Self {
+        for line in &mut self.lines {
+            *line = line.chars().map(&map).collect();
+        }
+        self
+    }
+
+    /// Color every line, restoring the color after any nested colors end
+    fn paint(mut self, color: Color) -> Self {
+        let start = color.to_string();
+        for line in &mut self.lines {
+            let content = line.trim_end();
+            if !content.is_empty() {
+                let pad = &line[content.len()..];
+                *line = format!("{start}{}{RESET}{pad}", content.replace(RESET, &start));
+            }
+        }
+        self
+    }
+
     fn with_baseline(mut self, new_baseline: usize) -> Self {
         self.baseline = new_baseline;
         self
@@ -156,11 +236,27 @@ impl Block {
         self
     }
 
-    /// Center horizontally to `width` display columns.
-    fn pad_center(mut self, width: usize) -> Self {
+    /// Remove blank columns from the left, like the space that starts the ` if ` symbol
+    fn trim_start(mut self) -> Self {
+        let blank = self
+            .lines
+            .iter()
+            .map(|line| line.chars().take_while(|&chr| chr == ' ').count())
+            .min()
+            .unwrap_or_default()
+            .min(self.width);
+        for line in &mut self.lines {
+            line.drain(..blank);
+        }
+        self.width -= blank;
+        self
+    }
+
+    /// Pad horizontally to `width` display columns with the given alignment.
+    fn pad_align(mut self, width: usize, align: Align) -> Self {
         if width > self.width {
             for line in &mut self.lines {
-                *line = center_pad(line, self.width, width);
+                *line = align_pad(line, self.width, width, align);
             }
             self.width = width;
         }
@@ -180,6 +276,165 @@ impl fmt::Display for Block {
     }
 }
 
+/// The glyph set used to draw brackets, fraction bars, radicals, and braces in block mode
+///
+/// Some terminal fonts misalign the unicode bracket pieces, so the box drawing sets offer
+/// fallbacks that tend to render more consistently.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum BlockStyle {
+    /// Unicode bracket pieces like `⎛⎜⎝`
+    #[default]
+    Unicode,
+    /// Light box drawing like `┌│└`
+    Light,
+    /// Heavy box drawing like `┏┃┗`
+    Heavy,
+    /// Light box drawing with rounded parentheses and braces like `╭│╰`
+    Rounded,
+    /// Plain ascii like `/|\`
+    Ascii,
+}
+
+impl BlockStyle {
+    /// The glyph drawn in place of a unicode drawing character
+    fn glyph(self, chr: char) -> char {
+        match self {
+            BlockStyle::Unicode => chr,
+            BlockStyle::Light => light_glyph(chr),
+            BlockStyle::Heavy => heavy_glyph(light_glyph(chr)),
+            BlockStyle::Rounded => rounded_glyph(chr),
+            BlockStyle::Ascii => ascii::glyph(chr),
+        }
+    }
+}
+
+/// How matrix cells line up within their column in block mode
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum MatrixAlign {
+    /// Center each cell
+    #[default]
+    Center,
+    /// Align cells on their left edge
+    Left,
+    /// Align cells on their right edge
+    Right,
+    /// Align cells on their decimal point, or right after the last digit of whole numbers
+    Decimal,
+}
+
+fn light_glyph(chr: char) -> char {
+    match chr {
+        '⎜' | '⎟' | '⎢' | '⎥' | '⎮' => '│',
+        '⎛' | '⎠' | '⎰' => '╱',
+        '⎝' | '⎞' | '⎱' => '╲',
+        '⎡' | '⌈' | '⎧' | '⌠' | '╭' => '┌',
+        '⎤' | '⌉' | '⎫' | '╮' => '┐',
+        '⎣' | '⌊' | '⎩' | '╰' => '└',
+        '⎦' | '⌋' | '⎭' | '⌡' | '╯' => '┘',
+        '⎨' => '┤',
+        '⎬' => '├',
+        chr => chr,
+    }
+}
+
+fn heavy_glyph(chr: char) -> char {
+    match chr {
+        '─' => '━',
+        '│' => '┃',
+        '┌' => '┏',
+        '┐' => '┓',
+        '└' => '┗',
+        '┘' => '┛',
+        '┤' => '┫',
+        '├' => '┣',
+        '┬' => '┳',
+        '┴' => '┻',
+        '┼' => '╋',
+        chr => chr,
+    }
+}
+
+fn rounded_glyph(chr: char) -> char {
+    match chr {
+        '⎛' | '⎧' | '╭' => '╭',
+        '⎞' | '⎫' | '╮' => '╮',
+        '⎝' | '⎩' | '╰' => '╰',
+        '⎠' | '⎭' | '╯' => '╯',
+        chr => light_glyph(chr),
+    }
+}
+
+/// Horizontal placement of a narrower line within a wider one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+fn align_pad(s: &str, current_width: usize, target_width: usize, align: Align) -> String {
+    let extra = target_width.saturating_sub(current_width);
+    match align {
+        Align::Left => format!("{s}{}", " ".repeat(extra)),
+        Align::Center => center_pad(s, current_width, target_width),
+        Align::Right => format!("{}{s}", " ".repeat(extra)),
+    }
+}
+
+/// A radical drawn around `arg`, with a vinculum above it and an optional index at the top left
+fn radical(arg: Block, index: Option<Block>, style: BlockStyle) -> Block {
+    let (stem, hook) = (style.glyph('╱'), style.glyph('╲'));
+    let height = arg.height();
+    let arg_below = height - 1 - arg.baseline;
+    let vinculum = style.glyph('─').to_string().repeat(arg.width);
+    // the stem climbs one column per line, up to the line of the vinculum it runs into
+    let stem = iter::once(format!("{}{stem}{vinculum}", " ".repeat(height)))
+        .chain(arg.lines.into_iter().enumerate().map(|(idx, line)| {
+            format!(
+                "{}{stem}{}{line}",
+                " ".repeat(height - 1 - idx),
+                " ".repeat(idx + 1)
+            )
+        }))
+        .collect();
+    let stem = Block {
+        lines: stem,
+        baseline: height,
+        width: height + 1 + arg.width,
+    };
+    // the index sits directly above the hook at the foot of the stem
+    let hook = index
+        .into_iter()
+        .chain([Block::text(hook)])
+        .collect::<Vec<_>>();
+    let hook = Block::stack(hook, Align::Right);
+    let hook_height = hook.height();
+    let combined = hook.with_baseline(hook_height - 1).beside(stem);
+    let baseline = combined.height() - 1 - arg_below;
+    combined.with_baseline(baseline)
+}
+
+/// The column of the decimal point on a cell's baseline, or the end of a whole number
+fn decimal_point(cell: &Block) -> usize {
+    let line = &cell.lines[cell.baseline];
+    match line.find('.') {
+        Some(ind) => display_width(&line[..ind]),
+        None => display_width(line.trim_end()),
+    }
+}
+
 fn center_pad(s: &str, current_width: usize, target_width: usize) -> String {
     if current_width >= target_width {
         s.to_string()
@@ -280,6 +535,90 @@ fn tall_bracket_right(bracket: &str, height: usize) -> Block {
     }
 }
 
+/// A multi-line glyph for a large operator built from its piece characters
+pub(crate) fn tall_operator(op: &str) -> Option<Block> {
+    let pieces: &[&str] = match op {
+        "sum" => &["⎲", "⎳"],
+        "int" => &["⌠", "⎮", "⌡"],
+        _ => return None,
+    };
+    Some(Block {
+        lines: pieces.iter().map(ToString::to_string).collect(),
+        baseline: pieces.len() / 2,
+        width: 1,
+    })
+}
+
+/// A horizontal brace spanning `width` columns, opening up for underbraces
+fn brace_line(width: usize, under: bool) -> Block {
+    let (single, left, tip, right) = if under {
+        ('⏟', '╰', '┬', '╯')
+    } else {
+        ('⏞', '╭', '┴', '╮')
+    };
+    if width <= 1 {
+        Block::text(single)
+    } else {
+        let line: String = (0..width)
+            .map(|idx| {
+                // a two column brace has no room for both ends, but always has a tip
+                if idx == 0 {
+                    left
+                } else if idx == width / 2 {
+                    tip
+                } else if idx == width - 1 {
+                    right
+                } else {
+                    '─'
+                }
+            })
+            .collect();
+        Block::text(line)
+    }
+}
+
+/// Operators whose scripts are placed as limits above and below
+pub(crate) fn is_underover(simple: &Simple<'_>) -> bool {
+    match simple {
+        Simple::Unary(unary) => {
+            matches!(unary.op, "ubrace" | "underbrace" | "obrace" | "overbrace")
+        }
+        Simple::Symbol(sym) => matches!(
+            *sym,
+            "sum"
+                | "prod"
+                | "^^^"
+                | "bigwedge"
+                | "vvv"
+                | "bigvee"
+                | "nnn"
+                | "bigcap"
+                | "uuu"
+                | "bigcup"
+        ),
+        Simple::Ident(id) => matches!(*id, "lim" | "Lim" | "max" | "min" | "lub" | "glb"),
+        _ => false,
+    }
+}
+
+/// Whether an intermediate is an operator with limits stacked above or below it
+fn has_limits(inter: &Intermediate<'_>) -> bool {
+    matches!(
+        inter,
+        Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript { simple, script }))
+            if !matches!(simple, Simple::Unary(_))
+                && !matches!(script, Script::None)
+                && is_underover(simple)
+    )
+}
+
+pub(crate) fn is_integral(simple: &Simple<'_>) -> bool {
+    matches!(
+        simple,
+        Simple::Symbol("int" | "oint" | "iint" | "iiint" | "oiint" | "oiiint")
+    )
+}
+
 #[allow(clippy::too_many_lines)]
 fn is_spaced_operator(sym: &str) -> bool {
     matches!(
@@ -406,45 +745,289 @@ fn is_spaced_ident(id: &str) -> bool {
     matches!(id, "+" | "-" | "=" | ">" | "<" | "≤" | "≥" | "≠")
 }
 
-fn inter_is_spaced_op(inter: &Intermediate<'_>) -> bool {
-    match inter {
+/// Columns that continuation rows are indented by when an expression is broken
+pub(crate) const CONTINUATION_INDENT: usize = 2;
+
+/// Split an expression before each spaced operator where a line may break
+///
+/// Operators that directly follow another, like the `-` in `a = -b`, stay attached.
+pub(crate) fn break_segments<'e, 'a>(expr: &'e [Intermediate<'a>]) -> Vec<&'e [Intermediate<'a>]> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for (idx, pair) in expr.windows(2).enumerate() {
+        if inter_is_spaced_op(&pair[1]) && !inter_is_spaced_op(&pair[0]) {
+            segments.push(&expr[start..=idx]);
+            start = idx + 1;
+        }
+    }
+    segments.push(&expr[start..]);
+    segments
+}
+
+/// The operator of an intermediate that's spaced from its operands
+fn spaced_op<'a>(inter: &Intermediate<'a>) -> Option<&'a str> {
+    match *inter {
         Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript {
             simple: Simple::Symbol(sym),
             script: Script::None,
-        })) => is_spaced_operator(sym),
+        })) if is_spaced_operator(sym) => Some(sym),
         Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript {
             simple: Simple::Ident(id),
             script: Script::None,
-        })) => is_spaced_ident(id),
-        _ => false,
+        })) if is_spaced_ident(id) => Some(id),
+        _ => None,
     }
 }
 
+fn inter_is_spaced_op(inter: &Intermediate<'_>) -> bool {
+    spaced_op(inter).is_some()
+}
+
+/// Spaced operators that combine terms, rather than relating the two sides of an equation
+fn is_combining_operator(op: &str) -> bool {
+    matches!(
+        op,
+        "+" | "-"
+            | "+-"
+            | "pm"
+            | "-+"
+            | "mp"
+            | "xx"
+            | "times"
+            | "-:"
+            | "div"
+            | "divide"
+            | "*"
+            | "cdot"
+            | "**"
+            | "ast"
+            | "o+"
+            | "oplus"
+            | "ox"
+            | "otimes"
+            | "o."
+            | "odot"
+            | "^^"
+            | "wedge"
+            | "land"
+            | "vv"
+            | "vee"
+            | "lor"
+            | "nn"
+            | "cap"
+            | "uu"
+            | "cup"
+            | "AA"
+            | "forall"
+            | "EE"
+            | "exists"
+            | "and"
+            | "or"
+            | "if"
+    )
+}
+
+/// The index of the first relation at the top level of an expression, like the `=` in `y = x`
+fn first_relation(expr: &[Intermediate<'_>]) -> Option<usize> {
+    expr.iter()
+        .position(|inter| spaced_op(inter).is_some_and(|op| !is_combining_operator(op)))
+}
+
 impl Conf {
+    /// A block of rendered text, converted to ascii if configured
+    fn block_text(self, text: impl Into<String>) -> Block {
+        let text = text.into();
+        if self.ascii {
+            Block::text(to_ascii(&text))
+        } else {
+            Block::text(text)
+        }
+    }
+
     fn block_inline_simple(self, simple: &Simple<'_>) -> Block {
         let mut s = String::new();
         self.inline_simple(simple, &mut Mapper::new(&mut s))
             .unwrap_or_else(|_| unreachable!("write to String is infallible"));
-        Block::text(s)
+        self.block_text(s)
+    }
+
+    pub(crate) fn block_expression(self, expr: &[Intermediate<'_>]) -> Block {
+        self.block_row(None, expr)
     }
 
-    pub(crate) fn block_expression(self, expr: &Expression<'_>) -> Block {
-        let mut items = expr.iter();
-        let Some(first) = items.next() else {
-            return Block::empty();
+    /// Render a top level expression, applying line breaking and ascii conversion
+    pub(crate) fn block_render(self, expr: &Expression<'_>) -> Block {
+        let conf = self.block_conf();
+        match conf.max_width {
+            Some(width) => conf.block_wrapped(expr, width),
+            None => conf.block_expression(expr),
+        }
+    }
+
+    /// The configuration to render top level blocks with
+    pub(crate) fn block_conf(self) -> Conf {
+        if self.ascii {
+            // stack fractions with `-` rather than using unicode fraction characters
+            Conf {
+                vulgar_fracs: false,
+                script_fracs: false,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    /// Render several expressions as rows aligned on their first relation
+    pub(crate) fn block_render_aligned(self, rows: &[&[Intermediate<'_>]]) -> Block {
+        self.block_conf().block_aligned(rows)
+    }
+
+    /// Rows aligned on their first relation, like the steps of a derivation
+    ///
+    /// Left sides are right aligned before the relation and right sides are left aligned after
+    /// it. A row without a relation is treated as a left side, and a row starting with one
+    /// continues the previous row. With `max_width`, right sides break to fit beside the column
+    /// of relations, while left sides are never broken.
+    fn block_aligned(self, rows: &[&[Intermediate<'_>]]) -> Block {
+        let parts: Vec<_> = rows
+            .iter()
+            .map(|row| match first_relation(row) {
+                Some(ind) => (
+                    self.block_expression(&row[..ind]),
+                    Some((self.block_intermediate(&row[ind]), &row[ind + 1..])),
+                ),
+                None => (self.block_expression(row), None),
+            })
+            .collect();
+        let left_width = parts
+            .iter()
+            .map(|(left, _)| left.width)
+            .max()
+            .unwrap_or_default();
+        let rel_width = parts
+            .iter()
+            .filter_map(|(_, rest)| rest.as_ref().map(|(rel, _)| rel.width))
+            .max()
+            .unwrap_or_default();
+        let right_width = self
+            .max_width
+            .map(|width| width.saturating_sub(left_width + rel_width + 2));
+        let lines = parts
+            .into_iter()
+            .map(|(left, rest)| {
+                let left = left.pad_align(left_width, Align::Right);
+                match rest {
+                    Some((rel, right)) => left
+                        .beside(Block::space(1))
+                        .beside(rel.pad_align(rel_width, Align::Left))
+                        .beside(Block::space(1))
+                        .beside(match right_width {
+                            Some(width) => self.block_wrapped(right, width),
+                            None => self.block_expression(right),
+                        }),
+                    None => left,
+                }
+            })
+            .collect();
+        let grid = Block::stack(lines, Align::Left);
+        let baseline = grid.height() / 2;
+        grid.with_baseline(baseline)
+    }
+
+    /// The glyphs to draw with, which are always ascii when rendering ascii
+    fn glyphs(self) -> BlockStyle {
+        if self.ascii {
+            BlockStyle::Ascii
+        } else {
+            self.block_style
+        }
+    }
+
+    /// Render an expression, breaking it into rows no wider than `max_width` where possible
+    ///
+    /// Rows break before top level relations and additive operators, and continuation rows are
+    /// indented. A segment that's wider than `max_width` on its own is left as is.
+    pub(crate) fn block_wrapped(self, expr: &[Intermediate<'_>], max_width: usize) -> Block {
+        let mut segments = break_segments(expr).into_iter();
+        let mut row = segments
+            .next()
+            .map_or_else(Block::empty, |first| self.block_row(None, first));
+        let mut rows = Vec::new();
+        for segment in segments {
+            let (op, rest) = segment
+                .split_first()
+                .unwrap_or_else(|| unreachable!("later segments start with an operator"));
+            let block = self
+                .block_intermediate(op)
+                .beside(Block::space(1))
+                .beside(self.block_row(Some(op), rest));
+            if row.width + 1 + block.width > max_width {
+                rows.push(row);
+                row = Block::space(CONTINUATION_INDENT).beside(block);
+            } else {
+                row = row.beside(Block::space(1)).beside(block);
+            }
+        }
+        if rows.is_empty() {
+            row
+        } else {
+            rows.push(row);
+            let baseline = rows[0].baseline;
+            Block::stack(rows, Align::Left).with_baseline(baseline)
+        }
+    }
+
+    /// Render a row of items that follow `prev`, spacing the operators that follow another item
+    ///
+    /// In ascii, runs of single line items are converted together rather than leaf by leaf, so
+    /// adjacent names like `alpha beta` stay apart just as in inline output.
+    fn block_row<'e>(
+        self,
+        mut prev: Option<&'e Intermediate<'e>>,
+        items: &'e [Intermediate<'e>],
+    ) -> Block {
+        let plain = Conf {
+            ascii: false,
+            ..self
         };
-        let mut result = self.block_intermediate(first);
+        let mut result = Block::empty();
+        let mut run = String::new();
         for inter in items {
+            let spaced = prev.is_some() && inter_is_spaced_op(inter);
+            let limited = prev.is_some_and(has_limits);
+            if self.ascii && !limited {
+                let (block, layouts) = explain::tentative(|| plain.block_intermediate(inter));
+                if !block.is_multiline() {
+                    layouts.keep();
+                    let sep = if spaced { " " } else { "" };
+                    run.extend([sep, &block.lines[0], sep]);
+                    prev = Some(inter);
+                    continue;
+                }
+            }
+            if !run.is_empty() {
+                result = result.beside(self.block_text(mem::take(&mut run)));
+            }
             let block = self.block_intermediate(inter);
-            if inter_is_spaced_op(inter) {
+            if spaced {
                 result = result.beside(Block::space(1));
                 result = result.beside(block);
                 result = result.beside(Block::space(1));
             } else {
+                // limits as wide as their operator would touch the operand
+                if limited && result.touches(&block) {
+                    result = result.beside(Block::space(1));
+                }
                 result = result.beside(block);
             }
+            prev = Some(inter);
+        }
+        if run.is_empty() {
+            result
+        } else {
+            result.beside(self.block_text(run))
         }
-        result
     }
 
     fn block_intermediate(self, inter: &Intermediate<'_>) -> Block {
@@ -462,23 +1045,82 @@ impl Conf {
     }
 
     fn block_simplescript(self, ss: &SimpleScript<'_>) -> Block {
-        let base_block = self.block_simple(&ss.simple);
-        self.block_apply_script(base_block, &ss.script)
+        let tall = match ss.simple {
+            Simple::Symbol(sym) if self.tall_operators => {
+                tall_operator(sym).map(|glyph| glyph.map_glyphs(|chr| self.glyphs().glyph(chr)))
+            }
+            _ => None,
+        };
+        let scripted = !matches!(ss.script, Script::None);
+        if is_underover(&ss.simple) && scripted {
+            explain::choose(&ss.script, Layout::LimitsAboveBelow);
+            let base = tall.unwrap_or_else(|| self.block_simple(&ss.simple));
+            let over = ss
+                .script
+                .sup()
+                .map(|sup| self.block_simple_or_expr_stripped(sup));
+            let under = ss
+                .script
+                .sub()
+                .map(|sub| self.block_simple_or_expr_stripped(sub));
+            Block::under_over(base, over, under)
+        } else if let Some(glyph) = tall {
+            if is_integral(&ss.simple) && scripted {
+                // limits sit beside the top and bottom pieces of the glyph
+                explain::choose(&ss.script, Layout::LimitsBeside);
+                let over = ss
+                    .script
+                    .sup()
+                    .map_or_else(Block::empty, |sup| self.block_simple_or_expr_stripped(sup));
+                let under = ss
+                    .script
+                    .sub()
+                    .map_or_else(Block::empty, |sub| self.block_simple_or_expr_stripped(sub));
+                let baseline = over.height() - 1 + glyph.baseline;
+                let gap = Block {
+                    lines: vec![String::new(); glyph.height() - 2],
+                    baseline: 0,
+                    width: 0,
+                };
+                let limits =
+                    Block::stack(vec![over, gap, under], Align::Left).with_baseline(baseline);
+                glyph.beside(limits)
+            } else {
+                self.block_apply_script(glyph, &ss.script)
+            }
+        } else {
+            let base_block = self.block_simple(&ss.simple);
+            self.block_apply_script(base_block, &ss.script)
+        }
+    }
+
+    /// Render `simple` inline with `conf`, if all of it has a form there
+    fn block_inline_script(self, simple: &Simple<'_>, conf: MapperConf) -> Option<String> {
+        self.inline_simple(simple, &mut conf.wrap(&mut Sink)).ok()?;
+        let mut out = String::new();
+        self.inline_simple(simple, &mut Mapper::new(&mut out).with_conf(conf))
+            .ok()?;
+        Some(out)
     }
 
     fn block_apply_script(self, base: Block, script: &Script<'_>) -> Block {
+        let lower_conf = MapperConf {
+            sub_sup: Some(subscript_char),
+            ..MapperConf::default()
+        };
+        let upper_conf = MapperConf {
+            sub_sup: Some(superscript_char),
+            ..MapperConf::default()
+        };
         match script {
             Script::None => base,
             Script::Sub(sub) => {
-                let conf = MapperConf {
-                    sub_sup: Some(subscript_char),
-                    ..MapperConf::default()
-                };
-                let mut out = String::new();
-                if self.inline_simple(sub, &mut conf.wrap(&mut out)).is_ok() {
-                    base.beside(Block::text(out))
+                if let Some(out) = self.block_inline_script(sub, lower_conf) {
+                    explain::choose(script, Layout::UnicodeScripts);
+                    base.beside(self.block_text(out))
                 } else {
                     // Vertical: sub below-right
+                    explain::choose(script, Layout::StackedScripts);
                     let sub_blk = self.block_simple(sub);
                     let original = base.baseline;
                     let base_h = base.height();
@@ -488,15 +1130,12 @@ impl Conf {
                 }
             }
             Script::Super(sup) => {
-                let conf = MapperConf {
-                    sub_sup: Some(superscript_char),
-                    ..MapperConf::default()
-                };
-                let mut out = String::new();
-                if self.inline_simple(sup, &mut conf.wrap(&mut out)).is_ok() {
-                    base.beside(Block::text(out))
+                if let Some(out) = self.block_inline_script(sup, upper_conf) {
+                    explain::choose(script, Layout::UnicodeScripts);
+                    base.beside(self.block_text(out))
                 } else {
                     // Vertical: sup above-right
+                    explain::choose(script, Layout::StackedScripts);
                     let sup_blk = self.block_simple(sup);
                     let new_baseline = sup_blk.height() + base.baseline;
                     let sup_h = sup_blk.height();
@@ -506,26 +1145,18 @@ impl Conf {
                 }
             }
             Script::Subsuper(sub, sup) => {
-                let lower_conf = MapperConf {
-                    sub_sup: Some(subscript_char),
-                    ..MapperConf::default()
-                };
-                let upper_conf = MapperConf {
-                    sub_sup: Some(superscript_char),
-                    ..MapperConf::default()
-                };
-                let mut subscript = String::new();
-                let mut superscript = String::new();
+                // probe the superscript first, so the subscript only renders if both fit
                 if self
-                    .inline_simple(sub, &mut lower_conf.wrap(&mut subscript))
+                    .inline_simple(sup, &mut upper_conf.wrap(&mut Sink))
                     .is_ok()
-                    && self
-                        .inline_simple(sup, &mut upper_conf.wrap(&mut superscript))
-                        .is_ok()
+                    && let Some(subscript) = self.block_inline_script(sub, lower_conf)
+                    && let Some(superscript) = self.block_inline_script(sup, upper_conf)
                 {
-                    base.beside(Block::text(format!("{subscript}{superscript}")))
+                    explain::choose(script, Layout::UnicodeScripts);
+                    base.beside(self.block_text(format!("{subscript}{superscript}")))
                 } else {
                     // Vertical: sup above-right, then sub below-right
+                    explain::choose(script, Layout::StackedScripts);
                     let upper = self.block_simple(sup);
                     let new_baseline = upper.height() + base.baseline;
                     let upper_h = upper.height();
@@ -559,44 +1190,92 @@ impl Conf {
     }
 
     fn block_simplefunc(self, func: &SimpleFunc<'_>) -> Block {
-        let name = Block::text(func.func);
+        let name = self.block_text(func.func);
         let arg = self.block_simple(func.arg());
         name.beside(Block::space(1)).beside(arg)
     }
 
     fn block_unary(self, unary: &SimpleUnary<'_>) -> Block {
-        if unary.op == "sqrt" {
-            let arg = self.block_simple(unary.arg());
+        if let under @ ("ubrace" | "underbrace" | "obrace" | "overbrace") = unary.op {
+            let under = matches!(under, "ubrace" | "underbrace");
+            let arg = self.block_simple_or_expr_stripped(unary.arg());
+            let brace = brace_line(arg.width, under).map_glyphs(|chr| self.glyphs().glyph(chr));
+            if under {
+                Block::under_over(arg, None, Some(brace))
+            } else {
+                Block::under_over(arg, Some(brace), None)
+            }
+        } else if unary.op == "sqrt" {
+            let arg = self.block_simple_or_expr_stripped(unary.arg());
             if arg.is_multiline() {
-                return Block::text("√").beside(arg);
+                return radical(arg, None, self.glyphs());
             }
             let mut s = String::from("√");
             self.inline_simple(unary.arg(), &mut Mapper::new(&mut s))
                 .unwrap_or_else(|_| unreachable!("write to String is infallible"));
-            Block::text(s)
+            self.block_text(s)
         } else {
             let mut s = String::new();
             let mut mapper = Mapper::new(&mut s);
             self.inline_simpleunary(unary, &mut mapper)
                 .unwrap_or_else(|_| unreachable!("write to String is infallible"));
-            Block::text(s)
+            self.block_text(s)
         }
     }
 
     fn block_binary(self, binary: &SimpleBinary<'_>) -> Block {
         if binary.op == "frac" {
             self.block_simplefrac(binary.first(), binary.second())
+        } else if binary.op == "root" {
+            let arg = self.block_simple_or_expr_stripped(binary.second());
+            let glyph = matches!(
+                extract_simple_str(binary.first(), true),
+                Some("2" | "3" | "4")
+            );
+            if glyph && !arg.is_multiline() {
+                self.block_inline_binary(binary)
+            } else {
+                let index = self.block_simple_or_expr_stripped(binary.first());
+                radical(arg, Some(index), self.glyphs())
+            }
+        } else if let op @ ("overset" | "stackrel" | "underset") = binary.op {
+            let annotation = self.block_simple_or_expr_stripped(binary.first());
+            let base = self.block_simple_or_expr_stripped(binary.second());
+            if op == "underset" {
+                Block::under_over(base, None, Some(annotation))
+            } else {
+                Block::under_over(base, Some(annotation), None)
+            }
+        } else if binary.op == "color"
+            && self.ansi_color
+            && let Some(color) = Color::parse(&extract_raw_text(binary.first()))
+        {
+            // explicit colors take precedence over highlighting
+            let plain = Conf {
+                highlight: false,
+                ..self
+            };
+            plain
+                .block_simple_or_expr_stripped(binary.second())
+                .paint(color)
         } else {
-            let mut s = String::new();
-            let mut mapper = Mapper::new(&mut s);
-            self.inline_simplebinary(binary, &mut mapper)
-                .unwrap_or_else(|_| unreachable!("write to String is infallible"));
-            Block::text(s)
+            self.block_inline_binary(binary)
         }
     }
 
+    fn block_inline_binary(self, binary: &SimpleBinary<'_>) -> Block {
+        let mut s = String::new();
+        let mut mapper = Mapper::new(&mut s);
+        self.inline_simplebinary(binary, &mut mapper)
+            .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+        self.block_text(s)
+    }
+
     fn try_script_simplefrac(self, numer: &Simple<'_>, denom: &Simple<'_>) -> Option<String> {
         if self.script_fracs {
+            // probe first, so a fraction that doesn't fit records nothing
+            self.inline_simplefrac(numer, denom, &mut MapperConf::default().wrap(&mut Sink))
+                .ok()?;
             let mut text = String::new();
             self.inline_simplefrac(numer, denom, &mut Mapper::new(&mut text))
                 .ok()?;
@@ -608,6 +1287,9 @@ impl Conf {
 
     fn try_script_frac(self, frac: &Frac<'_>) -> Option<String> {
         if self.script_fracs {
+            // probe first, so a fraction that doesn't fit records nothing
+            self.inline_frac(frac, &mut MapperConf::default().wrap(&mut Sink))
+                .ok()?;
             let mut text = String::new();
             self.inline_frac(frac, &mut Mapper::new(&mut text)).ok()?;
             Some(text)
@@ -620,13 +1302,16 @@ impl Conf {
         if self.vulgar_fracs
             && let Some(frac) = super::ast::extract_vulgar_frac(numer, denom, self.strip_brackets)
         {
-            Block::text(frac)
+            explain::choose(numer, Layout::VulgarFraction);
+            self.block_text(frac)
         } else if let Some(text) = self.try_script_simplefrac(numer, denom) {
-            Block::text(text)
+            self.block_text(text)
         } else {
+            explain::choose(numer, Layout::StackedFraction);
             Block::stack_frac(
                 self.block_simple_or_expr_stripped(numer),
                 self.block_simple_or_expr_stripped(denom),
+                self.glyphs(),
             )
         }
     }
@@ -656,11 +1341,13 @@ impl Conf {
         {
             self.block_simplefrac(num, den)
         } else if let Some(text) = self.try_script_frac(frac) {
-            Block::text(text)
+            self.block_text(text)
         } else {
+            explain::choose(&frac.numer, Layout::StackedFraction);
             Block::stack_frac(
                 self.block_scriptfunc_for_frac(&frac.numer),
                 self.block_scriptfunc_for_frac(&frac.denom),
+                self.glyphs(),
             )
         }
     }
@@ -676,50 +1363,197 @@ impl Conf {
     }
 
     fn block_group(self, group: &Group<'_>) -> Block {
-        let inner = self.block_expression(&group.expr);
         let left = left_bracket_str(group.left_bracket);
         let right = right_bracket_str(group.right_bracket);
-        inner.with_brackets(left, right)
+        if let Some(rows) = extract_rows(&group.expr) {
+            match (left, right, rows[0].len()) {
+                ("{", "", 2..) => return self.block_cases(&rows),
+                ("", "", 1)
+                    if rows.len() > 1
+                        && rows.iter().any(|row| first_relation(row[0]).is_some()) =>
+                {
+                    let rows: Vec<_> = rows.into_iter().map(|row| row[0]).collect();
+                    return self.block_aligned(&rows);
+                }
+                _ => {}
+            }
+        }
+        let inner = self.block_expression(&group.expr);
+        inner.with_brackets(left, right, self.glyphs())
     }
 
-    fn block_matrix(self, matrix: &Matrix<'_>) -> Block {
-        let num_rows = matrix.num_rows();
-        let num_cols = matrix.num_cols();
-        let sep_width = 2;
+    /// Piecewise cases like `{(x, if x > 0), (-x, otherwise):}`
+    ///
+    /// Columns are left aligned with rows stacked directly under each other, and the last column
+    /// of conditions is set further apart.
+    fn block_cases(self, rows: &[Vec<&[Intermediate<'_>]>]) -> Block {
+        let rows: Vec<Vec<Block>> = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| self.block_expression(cell).trim_start())
+                    .collect()
+            })
+            .collect();
+        let num_cols = rows[0].len();
+        let widths: Vec<usize> = (0..num_cols)
+            .map(|col| {
+                rows.iter()
+                    .map(|row| row[col].width)
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let lines = rows
+            .into_iter()
+            .map(|row| {
+                let above = row.iter().map(|c| c.baseline).max().unwrap_or_default();
+                let below = row
+                    .iter()
+                    .map(|c| c.height() - 1 - c.baseline)
+                    .max()
+                    .unwrap_or_default();
+                let mut row_block = Block::empty();
+                for (col, cell) in row.into_iter().enumerate() {
+                    if col + 1 == num_cols {
+                        row_block = row_block.beside(Block::space(4));
+                    } else if col > 0 {
+                        row_block = row_block.beside(Block::space(2));
+                    }
+                    row_block = row_block.beside(
+                        cell.pad_vertical(above, below)
+                            .pad_align(widths[col], Align::Left),
+                    );
+                }
+                row_block
+            })
+            .collect();
+        let grid = Block::stack(lines, Align::Left);
+        let baseline = grid.height() / 2;
+        grid.with_baseline(baseline)
+            .with_brackets("{", "", self.glyphs())
+    }
+
+    /// Which columns of `matrix` to draw as rules
+    pub(crate) fn matrix_rules(self, matrix: &Matrix<'_>) -> Vec<bool> {
+        if self.column_rules {
+            rule_columns(matrix)
+        } else {
+            vec![false; matrix.num_cols()]
+        }
+    }
 
-        let mut cells = Vec::with_capacity(num_rows * num_cols);
-        for row in matrix.rows() {
-            for expr in row {
-                cells.push(self.block_expression(expr));
+    /// The line between matrix rows, which crosses any column rules
+    fn row_divider(self, widths: &[usize], gaps: &[usize], rules: &[bool]) -> String {
+        let style = self.glyphs();
+        let fill = if self.row_rules {
+            style.glyph('─')
+        } else {
+            ' '
+        };
+        let mut divider = String::new();
+        for (col, &width) in widths.iter().enumerate() {
+            if col > 0 {
+                divider.extend(iter::repeat_n(fill, gaps[col - 1]));
+            }
+            if rules[col] {
+                divider.push(style.glyph(if self.row_rules { '┼' } else { '│' }));
+            } else {
+                divider.extend(iter::repeat_n(fill, width));
             }
         }
+        divider
+    }
 
-        // one width, one above, one below for all cells
-        let col_width = cells.iter().map(|c| c.width).max().unwrap_or_default();
-        let above = cells.iter().map(|c| c.baseline).max().unwrap_or_default();
-        let below = cells
-            .iter()
+    fn block_matrix(self, matrix: &Matrix<'_>) -> Block {
+        let left = left_bracket_str(matrix.left_bracket);
+        let right = right_bracket_str(matrix.right_bracket);
+        if let ("{", "") = (left, right) {
+            let rows: Vec<Vec<_>> = matrix
+                .rows()
+                .map(|row| row.iter().map(|expr| &**expr).collect())
+                .collect();
+            return self.block_cases(&rows);
+        }
+        let num_cols = matrix.num_cols();
+        let rules = self.matrix_rules(matrix);
+        let style = self.glyphs();
+
+        let rows: Vec<Vec<Block>> = matrix
+            .rows()
+            .map(|row| row.iter().map(|expr| self.block_expression(expr)).collect())
+            .collect();
+
+        // one above and one below for all cells, but a width for each column
+        let cells = || rows.iter().flatten();
+        let above = cells().map(|c| c.baseline).max().unwrap_or_default();
+        let below = cells()
             .map(|c| c.height() - 1 - c.baseline)
             .max()
             .unwrap_or_default();
+        let column = |col: usize| rows.iter().map(move |row| &row[col]);
+        // for decimal alignment, the widths left and right of the point
+        let points: Vec<(usize, usize)> = (0..num_cols)
+            .map(|col| {
+                if self.matrix_align == MatrixAlign::Decimal {
+                    let left = column(col).map(decimal_point).max().unwrap_or_default();
+                    let right = column(col)
+                        .map(|cell| cell.width - decimal_point(cell))
+                        .max()
+                        .unwrap_or_default();
+                    (left, right)
+                } else {
+                    let width = column(col).map(|cell| cell.width).max();
+                    (0, width.unwrap_or_default())
+                }
+            })
+            .collect();
+        let widths: Vec<usize> = points
+            .iter()
+            .zip(&rules)
+            .map(|(&(left, right), &rule)| if rule { 1 } else { left + right })
+            .collect();
+        // rules get a single space on either side
+        let gaps: Vec<usize> = (1..num_cols)
+            .map(|col| if rules[col - 1] || rules[col] { 1 } else { 2 })
+            .collect();
+        let total_width = widths.iter().sum::<usize>() + gaps.iter().sum::<usize>();
+
+        let between = self.row_divider(&widths, &gaps, &rules);
 
-        // build rows
-        let total_width = col_width * num_cols + (num_cols - 1) * sep_width;
+        let align = match self.matrix_align {
+            MatrixAlign::Left | MatrixAlign::Decimal => Align::Left,
+            MatrixAlign::Center => Align::Center,
+            MatrixAlign::Right => Align::Right,
+        };
         let mut grid_lines: Vec<String> = Vec::new();
-        let mut cells = cells.into_iter();
-        for _ in 0..num_rows {
+        for row in rows {
             if !grid_lines.is_empty() {
-                grid_lines.push(" ".repeat(total_width));
+                grid_lines.push(between.clone());
             }
-            let mut cell_row = cells
-                .by_ref()
-                .take(num_cols)
-                .map(|cell| cell.pad_vertical(above, below).pad_center(col_width));
-            let mut row_block: Block = cell_row
-                .next()
-                .unwrap_or_else(|| unreachable!("must have at least one col"));
-            for cell in cell_row {
-                row_block = row_block.beside(Block::space(sep_width)).beside(cell);
+            let mut row_block = Block::empty();
+            for (col, cell) in row.into_iter().enumerate() {
+                if col > 0 {
+                    row_block = row_block.beside(Block::space(gaps[col - 1]));
+                }
+                let cell = if rules[col] {
+                    let height = above + below + 1;
+                    Block {
+                        lines: vec![style.glyph('│').to_string(); height],
+                        baseline: above,
+                        width: 1,
+                    }
+                } else if self.matrix_align == MatrixAlign::Decimal {
+                    let offset = points[col].0 - decimal_point(&cell);
+                    Block::space(offset)
+                        .beside(cell)
+                        .pad_vertical(above, below)
+                        .pad_align(widths[col], align)
+                } else {
+                    cell.pad_vertical(above, below)
+                        .pad_align(widths[col], align)
+                };
+                row_block = row_block.beside(cell);
             }
             grid_lines.extend(row_block.lines);
         }
@@ -729,13 +1563,11 @@ impl Conf {
             width: total_width,
             lines: grid_lines,
         };
-        let left = left_bracket_str(matrix.left_bracket);
-        let right = right_bracket_str(matrix.right_bracket);
-        grid.with_brackets(left, right)
+        grid.with_brackets(left, right, style)
     }
 
     fn block_func(self, func: &Func<'_>) -> Block {
-        let name = Block::text(func.func);
+        let name = self.block_text(func.func);
         let name_with_script = self.block_apply_script(name, &func.script);
         // a bare function name (e.g. `f`, `g`) has a missing argument and takes no separator
         if let ScriptFunc::Simple(SimpleScript {
@@ -753,7 +1585,7 @@ impl Conf {
 
 #[cfg(test)]
 mod tests {
-    use super::{Block, Conf};
+    use super::{Block, BlockStyle, Conf, MatrixAlign};
     use crate::tokens;
     use std::fmt::Write;
 
@@ -777,6 +1609,118 @@ mod tests {
         assert_eq!(format!("{block}"), "hello");
     }
 
+    #[test]
+    fn block_text_ignores_escapes() {
+        let block = Block::text("\x1b[31mab\x1b[39m");
+        assert_eq!(block.width, 2);
+    }
+
+    #[test]
+    fn block_color() {
+        let conf = Conf {
+            block: true,
+            ansi_color: true,
+            ..Conf::default()
+        };
+        assert_eq!(
+            render_block_conf("color(red)(x/y) + 1/z", conf),
+            "\x1b[31mx\x1b[39m   1\n\x1b[31m─\x1b[39m + ─\n\x1b[31my\x1b[39m   z"
+        );
+        // the outer color resumes after a nested one
+        assert_eq!(
+            render_block_conf("color(red)(a color(blue)(b)/c)", conf),
+            "\x1b[31m \x1b[34mb\x1b[31m\x1b[39m\n\x1b[31ma─\x1b[39m\n\x1b[31m c\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn block_max_width() {
+        let conf = Conf {
+            max_width: Some(10),
+            ..Conf::default()
+        };
+        let wrapped = |inp: &str| conf.block_wrapped(&tokens::parse(inp), 10).to_string();
+        assert_eq!(
+            wrapped("f(x) = a + b - c + d"),
+            "f (x) = a\n  + b - c\n  + d"
+        );
+        // rows keep their own height and baseline
+        assert_eq!(
+            wrapped("x/y + 1/(2z) + w + v"),
+            "x    1\n─ + ── + w\ny   2z\n  + v"
+        );
+        // operators that follow another stay attached
+        assert_eq!(wrapped("abc = -b"), "abc =  - b");
+        // segments that don't fit are left as is
+        assert_eq!(wrapped("\"longer text\" + l"), "longer text\n  + l");
+        assert_eq!(wrapped(""), "");
+    }
+
+    #[test]
+    fn block_ascii() {
+        let conf = Conf {
+            ascii: true,
+            ..Conf::default()
+        };
+        let render = |inp: &str| conf.block_render(&tokens::parse(inp)).to_string();
+        assert_eq!(render("(x+1)/(2alpha)"), " x + 1\n-------\n2 alpha");
+        assert_eq!(render("((1,2),(3,4))"), "/1  2\\\n|    |\n\\3  4/");
+        assert_eq!(render("sqrt(x/y)"), "    /-\n   / x\n  /  -\n\\/   y");
+        assert_eq!(render("sum_(i=1)^n i^2"), "  n\n sum i^2\ni = 1");
+        assert!(render("ubrace(a+b)_x <= oo").is_ascii());
+        // names converted together stay apart
+        assert_eq!(render("alpha beta"), "alpha beta");
+        assert_eq!(render("2 pi r"), "2 pi r");
+    }
+
+    #[test]
+    fn block_styles() {
+        let styled = |inp: &str, block_style: BlockStyle| {
+            let conf = Conf {
+                block_style,
+                ..Conf::default()
+            };
+            render_block_conf(inp, conf)
+        };
+        let matrix = "((1,2),(3,4))";
+        assert_eq!(styled(matrix, BlockStyle::Light), "╱1  2╲\n│    │\n╲3  4╱");
+        assert_eq!(styled(matrix, BlockStyle::Heavy), "╱1  2╲\n┃    ┃\n╲3  4╱");
+        assert_eq!(
+            styled(matrix, BlockStyle::Rounded),
+            "╭1  2╮\n│    │\n╰3  4╯"
+        );
+        assert_eq!(
+            styled(matrix, BlockStyle::Ascii),
+            "/1  2\\\n|    |\n\\3  4/"
+        );
+        let square = "[[a,b],[c,d]]";
+        assert_eq!(styled(square, BlockStyle::Light), "┌a  b┐\n│    │\n└c  d┘");
+        assert_eq!(styled(square, BlockStyle::Heavy), "┏a  b┓\n┃    ┃\n┗c  d┛");
+        assert_eq!(styled("x/y", BlockStyle::Heavy), "x\n━\ny");
+        assert_eq!(styled("ubrace(a+b)", BlockStyle::Light), "a + b\n└─┬─┘");
+        assert_eq!(
+            styled("sqrt(x/y)", BlockStyle::Ascii),
+            "    /-\n   / x\n  /  -\n\\/   y"
+        );
+    }
+
+    #[test]
+    fn block_highlight_widths() {
+        let conf = Conf {
+            block: true,
+            highlight: true,
+            ..Conf::default()
+        };
+        let plain = render_block_conf("(x+1)/2", Conf::default());
+        let highlighted = render_block_conf("(x+1)/2", conf);
+        let strip = |s: &str| {
+            ["\x1b[34m", "\x1b[35m", "\x1b[36m", "\x1b[39m"]
+                .iter()
+                .fold(s.to_owned(), |acc, seq| acc.replace(seq, ""))
+        };
+        assert_eq!(strip(&highlighted), plain);
+    }
+
     #[test]
     fn block_empty() {
         let block = Block::empty();
@@ -812,7 +1756,7 @@ mod tests {
     fn block_stack_frac() {
         let numer = Block::text("x");
         let denom = Block::text("y");
-        let frac = Block::stack_frac(numer, denom);
+        let frac = Block::stack_frac(numer, denom, BlockStyle::Unicode);
         assert_eq!(frac.height(), 3);
         assert_eq!(frac.baseline, 1);
         assert_eq!(format!("{frac}"), "x\n─\ny");
@@ -822,7 +1766,7 @@ mod tests {
     fn block_stack_frac_different_widths() {
         let numer = Block::text("abc");
         let denom = Block::text("d");
-        let frac = Block::stack_frac(numer, denom);
+        let frac = Block::stack_frac(numer, denom, BlockStyle::Unicode);
         assert_eq!(frac.width, 3);
         assert_eq!(format!("{frac}"), "abc\n───\n d");
     }
@@ -1037,6 +1981,132 @@ mod tests {
         }
     }
 
+    #[test]
+    fn matrix_column_widths() {
+        assert_eq!(
+            render_block("((1,22),(333,4))"),
+            "⎛ 1   22⎞\n⎜       ⎟\n⎝333   4⎠"
+        );
+    }
+
+    #[test]
+    fn matrix_alignment() {
+        let aligned = |inp: &str, matrix_align: MatrixAlign| {
+            let conf = Conf {
+                matrix_align,
+                ..Conf::default()
+            };
+            render_block_conf(inp, conf)
+        };
+        let matrix = "((1,22),(333,4))";
+        assert_eq!(
+            aligned(matrix, MatrixAlign::Left),
+            "⎛1    22⎞\n⎜       ⎟\n⎝333  4 ⎠"
+        );
+        assert_eq!(
+            aligned(matrix, MatrixAlign::Right),
+            "⎛  1  22⎞\n⎜       ⎟\n⎝333   4⎠"
+        );
+        assert_eq!(
+            aligned("((1.5),(10.25),(3))", MatrixAlign::Decimal),
+            "⎛ 1.5 ⎞\n⎜     ⎟\n⎜10.25⎟\n│     │\n⎝ 3   ⎠"
+        );
+    }
+
+    #[test]
+    fn matrix_rules() {
+        let matrix = "[[1,2,|,3],[4,5,|,6]]";
+        let columns = Conf {
+            column_rules: true,
+            ..Conf::default()
+        };
+        assert_eq!(
+            render_block_conf(matrix, columns),
+            "⎡1  2 │ 3⎤\n⎢     │  ⎥\n⎣4  5 │ 6⎦"
+        );
+        let rows = Conf {
+            row_rules: true,
+            ..columns
+        };
+        assert_eq!(
+            render_block_conf(matrix, rows),
+            "⎡1  2 │ 3⎤\n⎢─────┼──⎥\n⎣4  5 │ 6⎦"
+        );
+        let ascii = Conf {
+            block_style: BlockStyle::Ascii,
+            ..rows
+        };
+        assert_eq!(
+            render_block_conf(matrix, ascii),
+            "[1  2 | 3]\n|-----+--|\n[4  5 | 6]"
+        );
+        // without the option a column of bars is ordinary content
+        assert_eq!(
+            render_block(matrix),
+            "⎡1  2  |  3⎤\n⎢          ⎥\n⎣4  5  |  6⎦"
+        );
+    }
+
+    #[test]
+    fn cases() {
+        assert_eq!(
+            render_block("{(x, if x > 0), (-x, otherwise):}"),
+            "⎰x     if x > 0\n⎱-x    otherwise"
+        );
+        assert_eq!(
+            render_block("{(1, if x > 0), (0, if x = 0), (-1, if x < 0):}"),
+            "⎧1     if x > 0\n⎨0     if x = 0\n⎩-1    if x < 0"
+        );
+        // rows with different numbers of cells aren't cases
+        assert_eq!(render_block("{(a,b),(c):}"), "{(a,b),(c)");
+    }
+
+    #[test]
+    fn aligned_equations() {
+        assert_eq!(
+            render_block("{:(2x + 3 = 7), (2x = 4), (x = 2):}"),
+            "2x + 3 = 7\n    2x = 4\n     x = 2"
+        );
+        // rows can continue the derivation, and relations of different widths line up
+        assert_eq!(render_block("{:(a <= b), (= c), (a):}"), "a ≤ b\n  = c\na");
+        // groups without a relation aren't aligned
+        assert_eq!(render_block("{:(a), (b):}"), "(a),(b)");
+    }
+
+    #[test]
+    fn aligned_max_width() {
+        let conf = Conf {
+            max_width: Some(20),
+            ..Conf::default()
+        };
+        // right sides break to fit beside the relations
+        assert_eq!(
+            conf.render_aligned(["f(x) = a + b + c + d", "= e"]),
+            "f (x) = a + b + c\n          + d\n      = e"
+        );
+        // left sides are never broken
+        assert_eq!(
+            conf.render_aligned(["a + b + c + d + e + h = 1"]),
+            "a + b + c + d + e + h = 1"
+        );
+    }
+
+    #[test]
+    fn aligned_multiline_rows() {
+        let conf = Conf::default();
+        let rows = [tokens::parse("x/y = 1"), tokens::parse("x = y")];
+        let rows: Vec<&[_]> = rows.iter().map(|expr| &**expr).collect();
+        assert_eq!(
+            conf.block_render_aligned(&rows).to_string(),
+            "x\n─ = 1\ny\nx = y"
+        );
+        // relations are found after the operators that combine terms
+        assert_eq!(
+            conf.render_aligned(["a + b = c", "a = c - b"]),
+            "a + b = c\n    a = c - b"
+        );
+    }
+
     #[test]
     fn floor_stays_inline_in_block() {
         // floor/ceil/abs fall back to inline rendering even in block mode.
@@ -1067,9 +2137,9 @@ mod tests {
 
     #[test]
     fn block_sqrt_multiline() {
-        // sqrt of a stacked fraction renders the radical beside the block
+        // sqrt of a stacked fraction draws a radical around the block
         let result = render_block_conf("sqrt(x/y)", stacked());
-        assert_eq!(result, " ⎛x⎞\n√⎜─⎟\n ⎝y⎠");
+        assert_eq!(result, "    ╱─\n   ╱ x\n  ╱  ─\n╲╱   y");
     }
 
     #[test]
@@ -1150,4 +2220,138 @@ mod tests {
         };
         assert_eq!(conf.parse("x/n").to_string(), "ˣ⁄ₙ");
     }
+
+    fn tall() -> Conf {
+        Conf {
+            tall_operators: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sum_limits_above_and_below() {
+        assert_eq!(render_block("sum_(i=1)^n i"), "  n\n  ∑  i\ni = 1");
+    }
+
+    #[test]
+    fn limit_ident_under() {
+        assert_eq!(render_block("lim_(x->0) x"), " lim x\nx → 0");
+        assert_eq!(render_block("max_x f"), "max f\n x");
+        // wide limits only touch a tall operand
+        assert_eq!(
+            render_block("sum_(i=1)^n (a_i)/(b_i)"),
+            "  n   aᵢ\n  ∑   ──\ni = 1 bᵢ"
+        );
+    }
+
+    #[test]
+    fn underover_single_limit() {
+        assert_eq!(render_block("prod_k a_k"), "∏ aₖ\nk");
+        assert_eq!(render_block("uuu^n A"), "n\n⋃ A");
+    }
+
+    #[test]
+    fn underover_unscripted() {
+        assert_eq!(render_block("sum i"), "∑i");
+    }
+
+    #[test]
+    fn tall_sum() {
+        assert_eq!(render_block_conf("sum i", tall()), "⎲\n⎳i");
+        assert_eq!(
+            render_block_conf("sum_(i=1)^n i", tall()),
+            "  n\n  ⎲\n  ⎳  i\ni = 1"
+        );
+    }
+
+    #[test]
+    fn tall_integral_side_limits() {
+        assert_eq!(render_block_conf("int_0^1 x", tall()), "⌠1\n⎮ x\n⌡0");
+        assert_eq!(render_block_conf("int x", tall()), "⌠\n⎮x\n⌡");
+    }
+
+    #[test]
+    fn tall_integral_multiline_limit() {
+        let result = render_block_conf("int_0^(x/y) t", tall());
+        assert_eq!(result, " x\n ─\n⌠y\n⎮ t\n⌡0");
+    }
+
+    #[test]
+    fn integral_without_tall_operators() {
+        assert_eq!(render_block("int_0^1 x"), "∫₀¹x");
+    }
+
+    #[test]
+    fn overset_stacks_annotation() {
+        assert_eq!(render_block("overset(def)(=)"), "def\n =");
+        assert_eq!(render_block("a stackrel(?)(=) b"), " ?\na=b");
+    }
+
+    #[test]
+    fn underset_stacks_annotation() {
+        assert_eq!(render_block("underset(x->0)(lim)"), " lim\nx → 0");
+    }
+
+    #[test]
+    fn underbrace_with_label() {
+        assert_eq!(render_block("underbrace(a+b)_n"), "a + b\n╰─┬─╯\n  n");
+        assert_eq!(render_block("ubrace(x)"), "x\n⏟");
+        assert_eq!(render_block("ubrace(xy)"), "xy\n╰┬");
+    }
+
+    #[test]
+    fn overbrace_with_label() {
+        assert_eq!(render_block("obrace(ab)^k"), " k\n╭┴\nab");
+        assert_eq!(render_block("overbrace(a+b+c)"), "╭───┴───╮\na + b + c");
+    }
+
+    #[test]
+    fn brace_under_multiline() {
+        assert_eq!(render_block("ubrace(x/y)_k"), "x\n─\ny\n⏟\nk");
+    }
+
+    #[test]
+    fn block_root_index() {
+        assert_eq!(render_block("root(5)(x+1)"), "5 ╱─────\n╲╱ x + 1");
+    }
+
+    #[test]
+    fn block_root_multiline() {
+        let result = render_block_conf("root(3)(x/y)", stacked());
+        assert_eq!(result, "    ╱─\n   ╱ x\n3 ╱  ─\n╲╱   y");
+    }
+
+    #[test]
+    fn block_root_wide_index() {
+        let result = render_block_conf("root(n+1)(a/b)", stacked());
+        assert_eq!(result, "        ╱─\n       ╱ a\nn + 1 ╱  ─\n    ╲╱   b");
+    }
+
+    #[test]
+    fn block_root_tall_index() {
+        let result = render_block_conf("root(a/b)(x)", stacked());
+        assert_eq!(result, "a\n─\nb ╱─\n╲╱ x");
+    }
+
+    #[test]
+    fn block_radical_styles() {
+        let styled = |block_style: BlockStyle| {
+            let conf = Conf {
+                block_style,
+                ..Conf::default()
+            };
+            render_block_conf("root(5)(x+1)", conf)
+        };
+        assert_eq!(styled(BlockStyle::Unicode), "5 ╱─────\n╲╱ x + 1");
+        assert_eq!(styled(BlockStyle::Light), "5 ╱─────\n╲╱ x + 1");
+        assert_eq!(styled(BlockStyle::Heavy), "5 ╱━━━━━\n╲╱ x + 1");
+        assert_eq!(styled(BlockStyle::Rounded), "5 ╱─────\n╲╱ x + 1");
+        assert_eq!(styled(BlockStyle::Ascii), "5 /-----\n\\/ x + 1");
+    }
+
+    #[test]
+    fn block_radical_baseline() {
+        let result = render_block_conf("1 + sqrt(a/b) = c", stacked());
+        assert_eq!(result, "        ╱─\n       ╱ a\n1 +   ╱  ─ = c\n    ╲╱   b");
+    }
 }
@@ -0,0 +1,477 @@
+//! Reporting of constructs that could not be rendered faithfully
+
+use asciimath_parser::tree::{Expression, Intermediate, Script, ScriptFunc, Simple};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+use std::thread::LocalKey;
+
+/// The way in which a construct was degraded during rendering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// A unary operator that couldn't be applied to its argument and was written out by name
+    UnsupportedUnary,
+    /// A binary operator that couldn't be applied to its arguments and was written out by name
+    UnsupportedBinary,
+    /// A sub- or superscript without a unicode script form that was written with `_` or `^`
+    ///
+    /// Ascii output writes every script this way.
+    UnscriptableScript,
+    /// A fraction without a vulgar or script form that was written with `/`
+    ///
+    /// Ascii output writes every fraction this way.
+    FlattenedFraction,
+}
+
+impl WarningKind {
+    /// A short name for the kind that stays the same across releases, like `"flattened_fraction"`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WarningKind::UnsupportedUnary => "unsupported_unary",
+            WarningKind::UnsupportedBinary => "unsupported_binary",
+            WarningKind::UnscriptableScript => "unscriptable_script",
+            WarningKind::FlattenedFraction => "flattened_fraction",
+        }
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WarningKind::UnsupportedUnary => "unsupported unary operator",
+            WarningKind::UnsupportedBinary => "unsupported binary operator",
+            WarningKind::UnscriptableScript => "script without a unicode script form",
+            WarningKind::FlattenedFraction => "fraction without a unicode fraction form",
+        })
+    }
+}
+
+/// A construct that was rendered in a degraded form
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Warning {
+    /// How the construct was degraded
+    pub kind: WarningKind,
+    /// The byte range of the construct in the source, if it could be located
+    pub span: Option<Range<usize>>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => write!(f, "{} at {}..{}", self.kind, span.start, span.end),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// Rendered output together with the warnings produced while rendering it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The rendered unicode
+    pub output: String,
+    /// Every degraded construct, in source order
+    pub warnings: Vec<Warning>,
+}
+
+/// Computes the byte range covered by a node from the source slices it holds
+pub(crate) struct Span {
+    source: Range<usize>,
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    /// The covered range, or `None` if nothing covered points into the source
+    fn range(&self) -> Option<Range<usize>> {
+        (self.start <= self.end).then_some(self.start..self.end)
+    }
+
+    pub(crate) fn slice(&mut self, s: &str) {
+        // missing brackets are static empty strings that don't point into the source
+        let addr = s.as_ptr().addr();
+        if !s.is_empty() && addr >= self.source.start && addr + s.len() <= self.source.end {
+            self.start = self.start.min(addr - self.source.start);
+            self.end = self.end.max(addr - self.source.start + s.len());
+        }
+    }
+
+    pub(crate) fn simple(&mut self, simple: &Simple<'_>) {
+        match simple {
+            Simple::Missing => {}
+            &(Simple::Number(s) | Simple::Text(s) | Simple::Ident(s) | Simple::Symbol(s)) => {
+                self.slice(s);
+            }
+            Simple::Unary(unary) => {
+                self.slice(unary.op);
+                self.simple(unary.arg());
+            }
+            Simple::Func(func) => {
+                self.slice(func.func);
+                self.simple(func.arg());
+            }
+            Simple::Binary(binary) => {
+                self.slice(binary.op);
+                self.simple(binary.first());
+                self.simple(binary.second());
+            }
+            Simple::Group(group) => {
+                self.slice(group.left_bracket);
+                self.expression(&group.expr);
+                self.slice(group.right_bracket);
+            }
+            Simple::Matrix(matrix) => {
+                self.slice(matrix.left_bracket);
+                for expr in matrix.rows().flatten() {
+                    self.expression(expr);
+                }
+                self.slice(matrix.right_bracket);
+            }
+        }
+    }
+
+    pub(crate) fn script(&mut self, script: &Script<'_>) {
+        if let Some(sub) = script.sub() {
+            self.simple(sub);
+        }
+        if let Some(sup) = script.sup() {
+            self.simple(sup);
+        }
+    }
+
+    pub(crate) fn scriptfunc(&mut self, sf: &ScriptFunc<'_>) {
+        match sf {
+            ScriptFunc::Simple(simple) => {
+                self.simple(&simple.simple);
+                self.script(&simple.script);
+            }
+            ScriptFunc::Func(func) => {
+                self.slice(func.func);
+                self.script(&func.script);
+                self.scriptfunc(func.arg());
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression<'_>) {
+        for inter in expr.iter() {
+            match inter {
+                Intermediate::ScriptFunc(sf) => self.scriptfunc(sf),
+                Intermediate::Frac(frac) => {
+                    self.scriptfunc(&frac.numer);
+                    self.scriptfunc(&frac.denom);
+                }
+            }
+        }
+    }
+}
+
+/// Accumulates warnings for the source being rendered
+struct Diagnostics {
+    /// The addresses of the source, which the rendered tree borrows from
+    source: Range<usize>,
+    warnings: Vec<Warning>,
+}
+
+thread_local! {
+    /// The diagnostics of the report being rendered on this thread, if any
+    static DIAGNOSTICS: RefCell<Option<Diagnostics>> = const { RefCell::new(None) };
+}
+
+/// Report a construct covered by `cover` as degraded, if a report is being rendered
+pub(crate) fn warn(kind: WarningKind, cover: impl FnOnce(&mut Span)) {
+    DIAGNOSTICS.with_borrow_mut(|diag| {
+        if let Some(diag) = diag {
+            let mut covered = Span {
+                source: diag.source.clone(),
+                start: usize::MAX,
+                end: 0,
+            };
+            cover(&mut covered);
+            diag.warnings.push(Warning {
+                kind,
+                span: covered.range(),
+            });
+        }
+    });
+}
+
+/// Sets a thread local for the duration of a render, restoring its outer value when dropped
+///
+/// Restoring on drop means a render that panics, e.g. under `catch_unwind`, doesn't leave the
+/// thread collecting into state that points at a freed tree.
+pub(crate) struct Scoped<T: 'static> {
+    key: &'static LocalKey<RefCell<Option<T>>>,
+    outer: Option<T>,
+}
+
+impl<T> Scoped<T> {
+    /// Set `key` to `value` until the guard is dropped or finished
+    pub(crate) fn new(key: &'static LocalKey<RefCell<Option<T>>>, value: T) -> Self {
+        Scoped {
+            key,
+            outer: key.replace(Some(value)),
+        }
+    }
+
+    /// Take the value collected under the guard, and restore the outer one
+    pub(crate) fn finish(self) -> Option<T> {
+        self.key.take()
+    }
+}
+
+impl<T> Drop for Scoped<T> {
+    fn drop(&mut self) {
+        self.key.set(self.outer.take());
+    }
+}
+
+/// Run `render` on a tree parsed from `inp`, collecting the warnings it reports
+pub(crate) fn record(inp: &str, render: impl FnOnce() -> String) -> Report {
+    let start = inp.as_ptr().addr();
+    let scoped = Scoped::new(
+        &DIAGNOSTICS,
+        Diagnostics {
+            source: start..start + inp.len(),
+            warnings: Vec::new(),
+        },
+    );
+    let output = render();
+    let mut warnings = scoped
+        .finish()
+        .map(|diag| diag.warnings)
+        .unwrap_or_default();
+    // a construct can be rendered more than once, e.g. when measuring
+    let mut seen = HashSet::new();
+    warnings.retain(|warn| seen.insert(warn.clone()));
+    // warnings that can't be located come last
+    warnings.sort_by_key(|warn| {
+        warn.span
+            .as_ref()
+            .map_or((true, 0, 0), |span| (false, span.start, span.end))
+    });
+    Report { output, warnings }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DIAGNOSTICS, Warning, WarningKind, record, warn};
+    use crate::Conf;
+    use std::panic;
+
+    fn warnings(inp: &str) -> Vec<Warning> {
+        Conf::default().render_with_report(inp).warnings
+    }
+
+    #[test]
+    fn faithful_render_has_no_warnings() {
+        let report = Conf::default().render_with_report("sum_(i=1)^n i^3=((n(n+1))/2)^2");
+        assert_eq!(report.output, "∑₍ᵢ₌₁₎ⁿi³=(ⁿ⁽ⁿ⁺¹⁾⁄₂)²");
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn unsupported_unary() {
+        let report = Conf::default().render_with_report("a + ubrace x");
+        assert_eq!(report.output, "a+ubrace x");
+        assert_eq!(
+            report.warnings,
+            [Warning {
+                kind: WarningKind::UnsupportedUnary,
+                span: Some(4..12),
+            }]
+        );
+    }
+
+    #[test]
+    fn unapplicable_modifier() {
+        // `hat` is known, but can't combine with a multi-character argument
+        assert_eq!(
+            warnings("hat(x y)"),
+            [Warning {
+                kind: WarningKind::UnsupportedUnary,
+                span: Some(0..8),
+            }]
+        );
+    }
+
+    #[test]
+    fn unsupported_binary() {
+        assert_eq!(
+            warnings("color(red)(x)"),
+            [Warning {
+                kind: WarningKind::UnsupportedBinary,
+                span: Some(0..13),
+            }]
+        );
+        assert_eq!(
+            warnings("root(5)(x)")[0].kind,
+            WarningKind::UnsupportedBinary
+        );
+        assert!(warnings("root(3)(x)").is_empty());
+    }
+
+    #[test]
+    fn ansi_colors_are_supported() {
+        let conf = Conf {
+            ansi_color: true,
+            ..Conf::default()
+        };
+        assert!(conf.render_with_report("color(red)(x)").warnings.is_empty());
+        assert_eq!(
+            conf.render_with_report("color(xyz)(x)").warnings[0].kind,
+            WarningKind::UnsupportedBinary
+        );
+    }
+
+    #[test]
+    fn unscriptable_script() {
+        assert_eq!(
+            warnings("x_y"),
+            [Warning {
+                kind: WarningKind::UnscriptableScript,
+                span: Some(2..3),
+            }]
+        );
+        assert_eq!(warnings("x_y^sin x")[0].span, Some(2..9));
+        assert!(warnings("x_x^y").is_empty());
+    }
+
+    #[test]
+    fn nested_script_only_reports_outer() {
+        // the inner subscript renders fine once the outer superscript falls back
+        assert_eq!(
+            warnings("x^(y_2)"),
+            [Warning {
+                kind: WarningKind::UnscriptableScript,
+                span: Some(2..7),
+            }]
+        );
+    }
+
+    #[test]
+    fn flattened_fraction() {
+        assert_eq!(
+            warnings("(A+B)/(C+D)"),
+            [Warning {
+                kind: WarningKind::FlattenedFraction,
+                span: Some(0..11),
+            }]
+        );
+        assert_eq!(
+            warnings("frac(x)(y)")[0].kind,
+            WarningKind::FlattenedFraction
+        );
+        assert!(warnings("1/2").is_empty());
+    }
+
+    #[test]
+    fn disabled_forms_are_not_warnings() {
+        let conf = Conf {
+            vulgar_fracs: false,
+            script_fracs: false,
+            ..Default::default()
+        };
+        assert!(conf.render_with_report("x/y").warnings.is_empty());
+    }
+
+    #[test]
+    fn ascii_reports_converted_forms() {
+        let conf = Conf {
+            ascii: true,
+            ..Conf::default()
+        };
+        let report = conf.render_with_report("x_(\"hi\") + 1/2");
+        assert_eq!(report.output, "x_(hi)+1/2");
+        assert_eq!(
+            report.warnings,
+            [
+                Warning {
+                    kind: WarningKind::UnscriptableScript,
+                    span: Some(2..8),
+                },
+                Warning {
+                    kind: WarningKind::FlattenedFraction,
+                    span: Some(11..14),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn block_mode_only_reports_operators() {
+        let conf = Conf {
+            block: true,
+            ..Default::default()
+        };
+        let report = conf.render_with_report("x_y + A/B + obrace x + class(a)(x)");
+        assert_eq!(
+            report.warnings,
+            [Warning {
+                kind: WarningKind::UnsupportedBinary,
+                span: Some(23..34),
+            }]
+        );
+    }
+
+    #[test]
+    fn kind_names() {
+        let names = [
+            WarningKind::UnsupportedUnary,
+            WarningKind::UnsupportedBinary,
+            WarningKind::UnscriptableScript,
+            WarningKind::FlattenedFraction,
+        ]
+        .map(WarningKind::as_str);
+        assert_eq!(
+            names,
+            [
+                "unsupported_unary",
+                "unsupported_binary",
+                "unscriptable_script",
+                "flattened_fraction",
+            ]
+        );
+    }
+
+    #[test]
+    fn display() {
+        let warning = Warning {
+            kind: WarningKind::FlattenedFraction,
+            span: Some(3..7),
+        };
+        assert_eq!(
+            warning.to_string(),
+            "fraction without a unicode fraction form at 3..7"
+        );
+    }
+
+    #[test]
+    fn unlocated_warning() {
+        // a construct covering nothing in the source has no span rather than an empty one
+        let report = record("x", || {
+            warn(WarningKind::FlattenedFraction, |_| {});
+            String::new()
+        });
+        assert_eq!(
+            report.warnings,
+            [Warning {
+                kind: WarningKind::FlattenedFraction,
+                span: None,
+            }]
+        );
+        assert_eq!(
+            report.warnings[0].to_string(),
+            "fraction without a unicode fraction form"
+        );
+    }
+
+    #[test]
+    fn panicking_render_stops_recording() {
+        let res = panic::catch_unwind(|| record("x", || panic!("render failed")));
+        assert!(res.is_err());
+        assert!(DIAGNOSTICS.with_borrow(Option::is_none));
+    }
+}
@@ -0,0 +1,373 @@
+//! Rendering asciimath embedded in prose
+
+use std::fmt;
+use std::io;
+
+use super::Conf;
+
+/// Renders the asciimath between delimiters in a document, passing all other text through
+///
+/// By default math is delimited by backticks, `$...$`, or `\(...\)`. Spans never cross lines,
+/// and spans without a closing delimiter on their line and empty spans are left unchanged. Math
+/// between `$` can't start or end with whitespace, so prices like `$5 and $10` stay text, and a
+/// delimiter after a backslash like `\$` is text too. Markdown fenced and indented code blocks
+/// pass through unchanged. With [`Conf::block`] or [`Conf::max_width`] set, only spans alone on
+/// their line are rendered as blocks or wrapped, and spans within prose stay on one line.
+///
+/// ```
+/// use asciimath_unicode::{Conf, Document};
+///
+/// let doc = Document::new(Conf::default());
+/// assert_eq!(doc.render("the area is `pi r^2`."), "the area is πr².");
+///
+/// let mut doc = Document::new(Conf::default());
+/// doc.clear_delimiters().add_delimiter("{{", "}}");
+/// assert_eq!(doc.render("`x` is {{x^2}}"), "`x` is x²");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Document {
+    /// The configuration used for rendering math
+    pub conf: Conf,
+    delimiters: Vec<(String, String)>,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Document::new(Conf::default())
+    }
+}
+
+impl Document {
+    /// Create a document renderer with the default delimiters
+    #[must_use]
+    pub fn new(conf: Conf) -> Self {
+        Document {
+            conf,
+            delimiters: [("`", "`"), ("$", "$"), ("\\(", "\\)")]
+                .into_iter()
+                .map(|(open, close)| (open.to_owned(), close.to_owned()))
+                .collect(),
+        }
+    }
+
+    /// Add a pair of delimiters that surround math
+    ///
+    /// When several delimiters start at the same place, the longest opening delimiter wins.
+    /// Empty opening delimiters are ignored.
+    pub fn add_delimiter(
+        &mut self,
+        open: impl Into<String>,
+        close: impl Into<String>,
+    ) -> &mut Self {
+        self.delimiters.push((open.into(), close.into()));
+        self
+    }
+
+    /// Remove all delimiters, including the defaults
+    pub fn clear_delimiters(&mut self) -> &mut Self {
+        self.delimiters.clear();
+        self
+    }
+
+    /// The earliest unescaped opening delimiter in `line` from `from` with its position
+    fn next_open(&self, line: &str, from: usize) -> Option<(usize, &str, &str)> {
+        self.delimiters
+            .iter()
+            .filter(|(open, _)| !open.is_empty())
+            .filter_map(|(open, close)| {
+                unescaped(line, from, open)
+                    .next()
+                    .map(|pos| (pos, open, close))
+            })
+            .min_by_key(|&(pos, open, _)| (pos, usize::MAX - open.len()))
+            .map(|(pos, open, close)| (pos, open.as_str(), close.as_str()))
+    }
+
+    /// Render every complete span in one line of text
+    ///
+    /// Opening delimiters without a close on the same line are written unchanged.
+    fn write_line(&self, line: &str, out: &mut impl fmt::Write) -> fmt::Result {
+        let mut pos = 0;
+        let mut search = 0;
+        while let Some((start, open, close)) = self.next_open(line, search) {
+            let math_start = start + open.len();
+            let tight = open == "$";
+            let len = if tight && line[math_start..].starts_with(char::is_whitespace) {
+                None
+            } else {
+                find_close(line, math_start, close, tight)
+            };
+            let Some(len) = len else {
+                search = math_start;
+                continue;
+            };
+            let end = math_start + len + close.len();
+            if len > 0 {
+                out.write_str(&line[pos..start])?;
+                // blocks or wrapped formulas spliced into prose would break the lines around them
+                let display = line[..start].trim().is_empty() && line[end..].trim().is_empty();
+                let conf = Conf {
+                    block: self.conf.block && display,
+                    max_width: self.conf.max_width.filter(|_| display),
+                    ..self.conf
+                };
+                write!(out, "{}", conf.parse(&line[math_start..math_start + len]))?;
+                pos = end;
+            }
+            search = end;
+        }
+        out.write_str(&line[pos..])
+    }
+
+    /// Render a document to a writer
+    ///
+    /// # Errors
+    ///
+    /// If writing to `out` fails.
+    pub fn write_to(&self, inp: &str, out: &mut impl fmt::Write) -> fmt::Result {
+        let mut code = CodeBlocks::default();
+        for line in inp.split_inclusive('\n') {
+            if code.contains(line) {
+                out.write_str(line)?;
+            } else {
+                self.write_line(line, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Render a document to a string
+    #[must_use]
+    pub fn render(&self, inp: &str) -> String {
+        let mut out = String::with_capacity(inp.len());
+        self.write_to(inp, &mut out)
+            .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+        out
+    }
+
+    /// Render a document line by line, writing each line as soon as it's read
+    ///
+    /// # Errors
+    ///
+    /// If reading, writing, or decoding the input as utf-8 fails.
+    pub fn stream(
+        &self,
+        mut reader: impl io::BufRead,
+        mut writer: impl io::Write,
+    ) -> io::Result<()> {
+        let mut code = CodeBlocks::default();
+        let mut line = String::new();
+        let mut out = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            if code.contains(&line) {
+                writer.write_all(line.as_bytes())?;
+            } else {
+                self.write_line(&line, &mut out)
+                    .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+                writer.write_all(out.as_bytes())?;
+                out.clear();
+            }
+            line.clear();
+        }
+        writer.flush()
+    }
+}
+
+/// The length of math from `start` before `close` in `line`
+///
+/// Tight spans can't end with whitespace, so a close after whitespace is skipped.
+fn find_close(line: &str, start: usize, close: &str, tight: bool) -> Option<usize> {
+    unescaped(line, start, close)
+        .map(|pos| pos - start)
+        .find(|&len| !tight || !line[start..start + len].ends_with(char::is_whitespace))
+}
+
+/// The positions of `delim` in `line` from `from` that don't follow a backslash
+fn unescaped<'a>(line: &'a str, from: usize, delim: &'a str) -> impl Iterator<Item = usize> + 'a {
+    line[from..]
+        .match_indices(delim)
+        .map(move |(pos, _)| from + pos)
+        .filter(|&pos| !line[..pos].ends_with('\\'))
+}
+
+/// Tracks the markdown code blocks of a document, whose lines pass through unchanged
+#[derive(Debug)]
+struct CodeBlocks {
+    /// The character and length of the fence of an open fenced block
+    fence: Option<(char, usize)>,
+    /// Whether an indented block can start, which it can't in the middle of a paragraph
+    indented_allowed: bool,
+}
+
+impl Default for CodeBlocks {
+    fn default() -> Self {
+        CodeBlocks {
+            fence: None,
+            indented_allowed: true,
+        }
+    }
+}
+
+impl CodeBlocks {
+    /// Whether `line` is part of a code block
+    fn contains(&mut self, line: &str) -> bool {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let unindented = content.trim_start_matches(' ');
+        let indent = content.len() - unindented.len();
+        let fence = (indent < 4)
+            .then(|| {
+                ['`', '~'].into_iter().find_map(|chr| {
+                    let len = unindented.len() - unindented.trim_start_matches(chr).len();
+                    (len >= 3).then_some((chr, len))
+                })
+            })
+            .flatten();
+        if let Some((chr, len)) = self.fence {
+            // a closing fence is at least as long as the opening one and has no info string
+            if fence.is_some_and(|(close, close_len)| {
+                close == chr && close_len >= len && unindented[close_len..].trim().is_empty()
+            }) {
+                self.fence = None;
+            }
+            true
+        } else if fence.is_some() {
+            self.fence = fence;
+            self.indented_allowed = true;
+            true
+        } else {
+            let blank = unindented.trim().is_empty();
+            let code =
+                !blank && self.indented_allowed && (indent >= 4 || unindented.starts_with('\t'));
+            self.indented_allowed = blank || code;
+            code
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Document;
+    use crate::Conf;
+
+    #[test]
+    fn default_delimiters() {
+        let doc = Document::default();
+        assert_eq!(
+            doc.render("with `x^2`, $1/2$, and \\(alpha\\) done"),
+            "with x², ½, and α done"
+        );
+    }
+
+    #[test]
+    fn passes_through() {
+        let doc = Document::default();
+        assert_eq!(doc.render("no math here\n"), "no math here\n");
+        assert_eq!(doc.render("empty `` span"), "empty `` span");
+        assert_eq!(doc.render("costs $5"), "costs $5");
+        assert_eq!(doc.render("`a` and `b"), "a and `b");
+    }
+
+    #[test]
+    fn custom_delimiters() {
+        let mut doc = Document::default();
+        doc.clear_delimiters()
+            .add_delimiter("[", "]")
+            .add_delimiter("[[", "]]");
+        assert_eq!(doc.render("$x$ [[x_1]] [y]"), "$x$ x₁ y");
+    }
+
+    #[test]
+    fn conf() {
+        let doc = Document::new(Conf {
+            block: true,
+            ..Conf::default()
+        });
+        assert_eq!(doc.render("`a/b`\n"), "a\n─\nb\n");
+        // spans within prose stay inline
+        assert_eq!(doc.render("is `a/b`"), "is a/b");
+        let doc = Document::new(Conf {
+            max_width: Some(6),
+            ..Conf::default()
+        });
+        assert_eq!(
+            doc.render(
+                "`a + b + c + d`
+"
+            ),
+            "a+b+c
+  +d
+"
+        );
+        // spans within prose stay on their line
+        assert_eq!(
+            doc.render("the sum `a + b + c + d` is big"),
+            "the sum a+b+c+d is big"
+        );
+    }
+
+    #[test]
+    fn escaped_delimiters() {
+        let doc = Document::default();
+        assert_eq!(
+            doc.render("costs \\$5, `x`, and \\$x$"),
+            "costs \\$5, x, and \\$x$"
+        );
+        // an escaped close doesn't end a span
+        assert_eq!(doc.render("$a\\$ + b$ $c$"), "a\\$+b c");
+    }
+
+    #[test]
+    fn spans_stay_on_one_line() {
+        let doc = Document::default();
+        assert_eq!(doc.render("one `x\ny two `z`"), "one `x\ny two z");
+        assert_eq!(doc.render("a $x\n\ny$ b"), "a $x\n\ny$ b");
+    }
+
+    #[test]
+    fn dollar_spans_are_tight() {
+        let doc = Document::default();
+        assert_eq!(
+            doc.render("It costs $5 and later $10.\n"),
+            "It costs $5 and later $10.\n"
+        );
+        assert_eq!(doc.render("$ x$ and $x $"), "$ x$ and $x $");
+        assert_eq!(doc.render("$x^2$ costs $5"), "x² costs $5");
+    }
+
+    #[test]
+    fn code_blocks_pass_through() {
+        let doc = Document::default();
+        assert_eq!(
+            doc.render("Intro `x^2`.\n\n```rust\nlet a = b * c; // `x`\n```\n\nafter `y`\n"),
+            "Intro x².\n\n```rust\nlet a = b * c; // `x`\n```\n\nafter y\n"
+        );
+        assert_eq!(
+            doc.render("~~~~\n`x`\n~~~\n`y`\n~~~~\n`z`"),
+            "~~~~\n`x`\n~~~\n`y`\n~~~~\nz"
+        );
+        assert_eq!(
+            doc.render("text\n\n    `a` $b$\n\tc `d`\n\n`e`"),
+            "text\n\n    `a` $b$\n\tc `d`\n\ne"
+        );
+        // indented lines continuing a paragraph aren't code
+        assert_eq!(doc.render("text\n    `a`"), "text\n    a");
+    }
+
+    #[test]
+    fn unclosed_spans_are_text() {
+        let doc = Document::default();
+        let mut out = Vec::new();
+        doc.stream("one $x\n+ y$ two `a`\n`z".as_bytes(), &mut out)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "one $x\n+ y$ two a\n`z");
+    }
+
+    #[test]
+    fn stream() {
+        let doc = Document::default();
+        let mut out = Vec::new();
+        doc.stream("`x`\n```\n`b`\n```\n`z`".as_bytes(), &mut out)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "x\n```\n`b`\n```\nz");
+    }
+}
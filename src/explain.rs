@@ -0,0 +1,831 @@
+//! Describing which rendering strategy was chosen for each node
+
+use asciimath_parser::tree::{
+    Expression, Frac, Func, Group, Intermediate, Matrix, Script, ScriptFunc, Simple, SimpleBinary,
+    SimpleScript, SimpleUnary,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::ptr;
+
+use super::Conf;
+use super::diagnostics::Scoped;
+use super::inline::{MapperConf, Sink};
+
+/// The layout the renderer chose for a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Layout {
+    VulgarFraction,
+    OneFraction,
+    ScriptFraction,
+    StackedFraction,
+    FlattenedFraction,
+    UnicodeScripts,
+    WrittenScripts,
+    StackedScripts,
+    LimitsAboveBelow,
+    LimitsBeside,
+    /// An operator written out by its name
+    ByName,
+}
+
+/// The kind of node a layout is chosen for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum NodeKind {
+    Fraction,
+    Script,
+    Operator,
+}
+
+impl Layout {
+    fn node_kind(self) -> NodeKind {
+        match self {
+            Layout::VulgarFraction
+            | Layout::OneFraction
+            | Layout::ScriptFraction
+            | Layout::StackedFraction
+            | Layout::FlattenedFraction => NodeKind::Fraction,
+            Layout::UnicodeScripts
+            | Layout::WrittenScripts
+            | Layout::StackedScripts
+            | Layout::LimitsAboveBelow
+            | Layout::LimitsBeside => NodeKind::Script,
+            Layout::ByName => NodeKind::Operator,
+        }
+    }
+}
+
+/// The key of a node of the rendered tree
+///
+/// Fractions are keyed by their numerator, scripts by their [`Script`], and operators by their
+/// first argument. A node can start at the same address as one of another kind, e.g. the
+/// numerator of `x_1/y` is a [`ScriptFunc`] that holds the script `_1`, but never at the address
+/// of another node of its kind, so the kind is part of the key.
+type NodeKey = (NodeKind, usize);
+
+fn node_key<T>(node: &T, kind: NodeKind) -> NodeKey {
+    (kind, ptr::from_ref(node).addr())
+}
+
+thread_local! {
+    /// The layouts chosen while rendering an explanation on this thread
+    static LAYOUTS: RefCell<Option<HashMap<NodeKey, Layout>>> = const { RefCell::new(None) };
+}
+
+/// Record the layout chosen for `node`, if an explanation is being rendered
+///
+/// A node rendered more than once keeps the layout of its last rendering. Probes, whose output
+/// is thrown away, mustn't record layouts; see [`tentative`] for renders that might be kept.
+pub(crate) fn choose<T>(node: &T, layout: Layout) {
+    LAYOUTS.with_borrow_mut(|layouts| {
+        if let Some(layouts) = layouts {
+            layouts.insert(node_key(node, layout.node_kind()), layout);
+        }
+    });
+}
+
+/// Layouts chosen by a render whose output might be thrown away
+#[must_use]
+pub(crate) struct Tentative(Option<HashMap<NodeKey, Layout>>);
+
+impl Tentative {
+    /// Record the layouts, once the output of the render is used
+    pub(crate) fn keep(self) {
+        LAYOUTS.with_borrow_mut(|layouts| {
+            if let (Some(layouts), Some(chosen)) = (layouts, self.0) {
+                layouts.extend(chosen);
+            }
+        });
+    }
+}
+
+/// Run `render`, holding back the layouts it chooses until they're kept
+pub(crate) fn tentative<T>(render: impl FnOnce() -> T) -> (T, Tentative) {
+    if LAYOUTS.with_borrow(Option::is_none) {
+        return (render(), Tentative(None));
+    }
+    let scoped = Scoped::new(&LAYOUTS, HashMap::new());
+    let res = render();
+    (res, Tentative(scoped.finish()))
+}
+
+/// Run `render`, collecting the layouts it chooses
+fn record(render: impl FnOnce()) -> HashMap<NodeKey, Layout> {
+    let scoped = Scoped::new(&LAYOUTS, HashMap::new());
+    render();
+    scoped.finish().unwrap_or_default()
+}
+
+/// An alternative strategy that wasn't used, and why
+type Rejected = (&'static str, &'static str);
+
+/// Writes one indented line per node
+struct Explainer<'w, W> {
+    out: &'w mut W,
+    depth: usize,
+    layouts: HashMap<NodeKey, Layout>,
+}
+
+impl<W: fmt::Write> Explainer<'_, W> {
+    /// The layout the renderer chose for `node`
+    ///
+    /// Nodes folded into a single character by a probe, like the `1/2` of `hat(1/2)`, have
+    /// none.
+    fn layout<T>(&self, node: &T, kind: NodeKind) -> Option<Layout> {
+        self.layouts.get(&node_key(node, kind)).copied()
+    }
+
+    fn node(&mut self, role: &str, desc: impl fmt::Display) -> fmt::Result {
+        write!(self.out, "{:1$}", "", self.depth * 2)?;
+        if !role.is_empty() {
+            write!(self.out, "{role}: ")?;
+        }
+        writeln!(self.out, "{desc}")
+    }
+
+    /// A node with its chosen strategy followed by the rejected alternatives
+    fn chosen(
+        &mut self,
+        role: &str,
+        desc: &str,
+        strategy: &str,
+        rejected: &[Rejected],
+    ) -> fmt::Result {
+        self.node(role, format_args!("{desc} => {strategy}"))?;
+        for (alt, reason) in rejected {
+            writeln!(
+                self.out,
+                "{:1$}- not {alt}: {reason}",
+                "",
+                self.depth * 2 + 2
+            )?;
+        }
+        Ok(())
+    }
+
+    /// A unary or binary operator node, labelled when it's written by name
+    fn operator(&mut self, role: &str, desc: &str, by_name: bool) -> fmt::Result {
+        if by_name {
+            self.chosen(
+                role,
+                desc,
+                "written by name",
+                &[("unicode form", "none applies to this operator and argument")],
+            )
+        } else {
+            self.node(role, desc)
+        }
+    }
+}
+
+/// Whether a fraction term is the number one, possibly in stripped brackets
+fn is_one(simple: &Simple<'_>, strip: bool) -> bool {
+    match simple {
+        Simple::Number("1") => true,
+        Simple::Group(Group { expr, .. }) if strip => matches!(
+            &**expr,
+            [Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript {
+                simple: Simple::Number("1"),
+                script: Script::None,
+            }))]
+        ),
+        _ => false,
+    }
+}
+
+/// The plain term of a fraction part, if it has no scripts or function
+fn plain<'e, 'a>(sf: &'e ScriptFunc<'a>) -> Option<&'e Simple<'a>> {
+    match sf {
+        ScriptFunc::Simple(SimpleScript {
+            simple,
+            script: Script::None,
+        }) => Some(simple),
+        ScriptFunc::Func(_) | ScriptFunc::Simple(_) => None,
+    }
+}
+
+impl Conf {
+    /// Whether `simple` renders in a script, stripping brackets like the fraction arms do
+    fn scriptable(self, simple: &Simple<'_>, conf: MapperConf) -> bool {
+        match simple {
+            Simple::Group(Group { expr, .. }) if self.strip_brackets => self
+                .inline_expression(expr, &mut conf.wrap(&mut Sink))
+                .is_ok(),
+            _ => self
+                .inline_simple(simple, &mut conf.wrap(&mut Sink))
+                .is_ok(),
+        }
+    }
+
+    /// Why a fraction couldn't be written with scripts
+    fn script_frac_reason(
+        self,
+        numer: Option<&Simple<'_>>,
+        denom: Option<&Simple<'_>>,
+        mconf: MapperConf,
+    ) -> &'static str {
+        let (Some(upper), Some(lower)) = (mconf.with_sup(), mconf.with_sub()) else {
+            return "already inside a script";
+        };
+        if numer.is_some_and(|num| !self.scriptable(num, upper)) {
+            "the numerator has no superscript form"
+        } else if denom.is_some_and(|den| !self.scriptable(den, lower)) {
+            "the denominator has no subscript form"
+        } else {
+            "a part has no script form"
+        }
+    }
+
+    /// The name of the layout the renderer chose for a fraction, and the alternatives before it
+    fn frac_strategy(
+        self,
+        numer: Option<&Simple<'_>>,
+        denom: Option<&Simple<'_>>,
+        layout: Layout,
+        mconf: MapperConf,
+    ) -> (&'static str, Vec<Rejected>) {
+        let chosen = match layout {
+            Layout::VulgarFraction => "vulgar fraction",
+            Layout::OneFraction => "one fraction",
+            Layout::ScriptFraction => "script fraction",
+            Layout::StackedFraction => "stacked fraction",
+            _ => "flattened with /",
+        };
+        let mut rejected = Vec::new();
+        if layout == Layout::VulgarFraction {
+            return (chosen, rejected);
+        }
+        rejected.push((
+            "vulgar fraction",
+            match (numer, denom) {
+                _ if !self.vulgar_fracs => "vulgar_fracs is off",
+                (Some(_), Some(_)) => "no vulgar fraction has these terms",
+                _ => "the terms have scripts or functions",
+            },
+        ));
+        if numer.is_some_and(|num| is_one(num, self.strip_brackets)) {
+            if !self.vulgar_fracs || !self.script_fracs {
+                rejected.push(("one fraction", "needs both vulgar_fracs and script_fracs"));
+            } else if layout == Layout::OneFraction {
+                return (chosen, rejected);
+            } else {
+                rejected.push(("one fraction", "the denominator has no subscript form"));
+                rejected.push((
+                    "script fraction",
+                    "a numerator of one only tries a one fraction",
+                ));
+                return (chosen, rejected);
+            }
+        }
+        if layout == Layout::ScriptFraction {
+            return (chosen, rejected);
+        }
+        rejected.push((
+            "script fraction",
+            if self.script_fracs {
+                self.script_frac_reason(numer, denom, mconf)
+            } else {
+                "script_fracs is off"
+            },
+        ));
+        (chosen, rejected)
+    }
+
+    fn explain_simplefrac(
+        self,
+        role: &str,
+        numer: &Simple<'_>,
+        denom: &Simple<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> fmt::Result {
+        if let Some(layout) = ex.layout(numer, NodeKind::Fraction) {
+            let (strategy, rejected) = self.frac_strategy(Some(numer), Some(denom), layout, mconf);
+            ex.chosen(role, "fraction", strategy, &rejected)?;
+        } else {
+            ex.node(role, "fraction")?;
+        }
+        ex.depth += 1;
+        self.explain_simple("numerator", numer, mconf, ex)?;
+        self.explain_simple("denominator", denom, mconf, ex)?;
+        ex.depth -= 1;
+        Ok(())
+    }
+
+    fn explain_frac(
+        self,
+        role: &str,
+        frac: &Frac<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> fmt::Result {
+        let (numer, denom) = (plain(&frac.numer), plain(&frac.denom));
+        if let (Some(num), Some(den)) = (numer, denom) {
+            return self.explain_simplefrac(role, num, den, mconf, ex);
+        }
+        if let Some(layout) = ex.layout(&frac.numer, NodeKind::Fraction) {
+            let (strategy, rejected) = self.frac_strategy(numer, denom, layout, mconf);
+            ex.chosen(role, "fraction", strategy, &rejected)?;
+        } else {
+            ex.node(role, "fraction")?;
+        }
+        ex.depth += 1;
+        self.explain_scriptfunc("numerator", &frac.numer, mconf, ex)?;
+        self.explain_scriptfunc("denominator", &frac.denom, mconf, ex)?;
+        ex.depth -= 1;
+        Ok(())
+    }
+
+    /// The name of the layout the renderer chose for scripts, the alternative it rejected, and
+    /// the configuration their contents render with
+    fn script_strategy(
+        self,
+        script: &Script<'_>,
+        layout: Layout,
+        mconf: MapperConf,
+    ) -> (&'static str, Vec<Rejected>, MapperConf, MapperConf) {
+        let (lower, upper) = (mconf.with_sub(), mconf.with_sup());
+        let strategy = match (layout, script) {
+            (Layout::LimitsAboveBelow, _) => "limits above and below",
+            (Layout::LimitsBeside, _) => "limits beside the tall glyph",
+            (Layout::UnicodeScripts, Script::Sub(_)) => "unicode subscript",
+            (Layout::UnicodeScripts, Script::Super(_)) => "unicode superscript",
+            (Layout::UnicodeScripts, _) => "unicode scripts",
+            (Layout::StackedScripts, _) => "stacked beside the base",
+            (_, Script::Sub(_)) => "written with _",
+            (_, Script::Super(_)) => "written with ^",
+            (_, Script::None | Script::Subsuper(..)) => "written with _ and ^",
+        };
+        match layout {
+            Layout::UnicodeScripts => {
+                let unwrap = |conf: Option<MapperConf>| conf.unwrap_or(mconf);
+                return (strategy, Vec::new(), unwrap(lower), unwrap(upper));
+            }
+            Layout::StackedScripts | Layout::WrittenScripts => {}
+            _ => return (strategy, Vec::new(), mconf, mconf),
+        }
+        let probe = |conf: Option<MapperConf>, arg: &Simple<'_>| {
+            conf.is_some_and(|conf| self.inline_simple(arg, &mut conf.wrap(&mut Sink)).is_ok())
+        };
+        let reason = if lower.is_none() {
+            "already inside a script"
+        } else if script.sub().is_none_or(|sub| probe(lower, sub)) {
+            "the superscript has no unicode form"
+        } else {
+            "the subscript has no unicode form"
+        };
+        (strategy, vec![("unicode scripts", reason)], mconf, mconf)
+    }
+
+    /// Explain the scripts of a node, with `base` written by the caller as the first child
+    fn explain_scripts(
+        self,
+        role: &str,
+        desc: &str,
+        base: Option<&Simple<'_>>,
+        script: &Script<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> Result<(), fmt::Error> {
+        let (lower, upper) = if let Some(layout) = ex.layout(script, NodeKind::Script) {
+            let (strategy, rejected, lower, upper) = self.script_strategy(script, layout, mconf);
+            ex.chosen(role, desc, strategy, &rejected)?;
+            (lower, upper)
+        } else {
+            ex.node(role, desc)?;
+            (mconf, mconf)
+        };
+        ex.depth += 1;
+        if let Some(base) = base {
+            self.explain_simple("base", base, mconf, ex)?;
+        }
+        if let Some(sub) = script.sub() {
+            self.explain_simple("subscript", sub, lower, ex)?;
+        }
+        if let Some(sup) = script.sup() {
+            self.explain_simple("superscript", sup, upper, ex)?;
+        }
+        ex.depth -= 1;
+        Ok(())
+    }
+
+    fn explain_unary(
+        self,
+        role: &str,
+        unary: &SimpleUnary<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> fmt::Result {
+        let desc = format!("unary {}", unary.op);
+        ex.operator(
+            role,
+            &desc,
+            ex.layout(unary.arg(), NodeKind::Operator) == Some(Layout::ByName),
+        )?;
+        ex.depth += 1;
+        self.explain_simple("argument", unary.arg(), mconf, ex)?;
+        ex.depth -= 1;
+        Ok(())
+    }
+
+    fn explain_binary(
+        self,
+        role: &str,
+        binary: &SimpleBinary<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> fmt::Result {
+        if binary.op == "frac" {
+            return self.explain_simplefrac(role, binary.first(), binary.second(), mconf, ex);
+        }
+        let desc = format!("binary {}", binary.op);
+        ex.operator(
+            role,
+            &desc,
+            ex.layout(binary.first(), NodeKind::Operator) == Some(Layout::ByName),
+        )?;
+        ex.depth += 1;
+        self.explain_simple("first", binary.first(), mconf, ex)?;
+        self.explain_simple("second", binary.second(), mconf, ex)?;
+        ex.depth -= 1;
+        Ok(())
+    }
+
+    fn explain_matrix(
+        self,
+        role: &str,
+        matrix: &Matrix<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> fmt::Result {
+        ex.node(
+            role,
+            format_args!(
+                "matrix {}{} with {} rows and {} columns",
+                matrix.left_bracket,
+                matrix.right_bracket,
+                matrix.num_rows(),
+                matrix.num_cols()
+            ),
+        )?;
+        ex.depth += 1;
+        for (row, cells) in matrix.rows().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                ex.node(&format!("cell {},{}", row + 1, col + 1), "expression")?;
+                ex.depth += 1;
+                self.explain_expression(cell, mconf, ex)?;
+                ex.depth -= 1;
+            }
+        }
+        ex.depth -= 1;
+        Ok(())
+    }
+
+    fn explain_simple(
+        self,
+        role: &str,
+        simple: &Simple<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> fmt::Result {
+        match simple {
+            Simple::Missing => ex.node(role, "missing"),
+            Simple::Number(num) => ex.node(role, format_args!("number {num}")),
+            Simple::Text(text) => ex.node(role, format_args!("text {text:?}")),
+            // identifiers like `+` that don't start with a letter are operators
+            Simple::Ident(ident) if !ident.starts_with(char::is_alphabetic) => {
+                ex.node(role, format_args!("operator {ident}"))
+            }
+            Simple::Ident(ident) => ex.node(role, format_args!("identifier {ident}")),
+            Simple::Symbol(sym) => ex.node(role, format_args!("symbol {sym}")),
+            Simple::Unary(unary) => self.explain_unary(role, unary, mconf, ex),
+            Simple::Func(func) => {
+                ex.node(role, format_args!("function {}", func.func))?;
+                ex.depth += 1;
+                self.explain_simple("argument", func.arg(), mconf, ex)?;
+                ex.depth -= 1;
+                Ok(())
+            }
+            Simple::Binary(binary) => self.explain_binary(role, binary, mconf, ex),
+            Simple::Group(group) => {
+                ex.node(
+                    role,
+                    format_args!("group {}{}", group.left_bracket, group.right_bracket),
+                )?;
+                ex.depth += 1;
+                self.explain_expression(&group.expr, mconf, ex)?;
+                ex.depth -= 1;
+                Ok(())
+            }
+            Simple::Matrix(matrix) => self.explain_matrix(role, matrix, mconf, ex),
+        }
+    }
+
+    fn explain_func(
+        self,
+        role: &str,
+        func: &Func<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> fmt::Result {
+        let desc = format!("function {}", func.func);
+        if matches!(func.script, Script::None) {
+            ex.node(role, &desc)?;
+        } else {
+            self.explain_scripts(role, &desc, None, &func.script, mconf, ex)?;
+        }
+        ex.depth += 1;
+        self.explain_scriptfunc("argument", func.arg(), mconf, ex)?;
+        ex.depth -= 1;
+        Ok(())
+    }
+
+    fn explain_scriptfunc(
+        self,
+        role: &str,
+        sf: &ScriptFunc<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> fmt::Result {
+        match sf {
+            ScriptFunc::Simple(SimpleScript {
+                simple,
+                script: Script::None,
+            }) => self.explain_simple(role, simple, mconf, ex),
+            ScriptFunc::Simple(SimpleScript { simple, script }) => {
+                self.explain_scripts(role, "scripts", Some(simple), script, mconf, ex)
+            }
+            ScriptFunc::Func(func) => self.explain_func(role, func, mconf, ex),
+        }
+    }
+
+    fn explain_expression(
+        self,
+        expr: &Expression<'_>,
+        mconf: MapperConf,
+        ex: &mut Explainer<'_, impl fmt::Write>,
+    ) -> fmt::Result {
+        for inter in expr.iter() {
+            match inter {
+                Intermediate::ScriptFunc(sf) => self.explain_scriptfunc("", sf, mconf, ex)?,
+                Intermediate::Frac(frac) => self.explain_frac("", frac, mconf, ex)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the tree of `expr`, one node per line, with the strategy chosen for each fraction
+    /// and script
+    pub(crate) fn explain(self, expr: &Expression<'_>, out: &mut impl fmt::Write) -> fmt::Result {
+        let layouts = record(|| {
+            if self.block {
+                self.block_render(expr);
+            } else {
+                self.inline_render(expr, &mut Sink)
+                    .unwrap_or_else(|_| unreachable!("write to Sink is infallible"));
+            }
+        });
+        // block rendering adjusts the configuration, e.g. stacking fractions for ascii
+        let conf = if self.block { self.block_conf() } else { self };
+        // highlighting would add escape sequences to the probed text
+        let conf = Conf {
+            highlight: false,
+            ..conf
+        };
+        conf.explain_expression(
+            expr,
+            MapperConf::default(),
+            &mut Explainer {
+                out,
+                depth: 0,
+                layouts,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LAYOUTS, Layout, NodeKind, choose, node_key, record, tentative};
+    use crate::Conf;
+    use std::panic;
+
+    fn explain(inp: &str, conf: Conf) -> String {
+        conf.parse(inp).explain()
+    }
+
+    #[test]
+    fn vulgar_fraction() {
+        assert_eq!(
+            explain("1/2", Conf::default()),
+            "fraction => vulgar fraction\n  numerator: number 1\n  denominator: number 2\n"
+        );
+    }
+
+    #[test]
+    fn rejected_alternatives() {
+        let conf = Conf {
+            vulgar_fracs: false,
+            ..Conf::default()
+        };
+        assert_eq!(
+            explain("1/2", conf),
+            "fraction => script fraction\n  - not vulgar fraction: vulgar_fracs is off\n  - not one fraction: needs both vulgar_fracs and script_fracs\n  numerator: number 1\n  denominator: number 2\n"
+        );
+        assert_eq!(
+            explain("1/x", Conf::default()),
+            "fraction => one fraction\n  - not vulgar fraction: no vulgar fraction has these terms\n  numerator: number 1\n  denominator: identifier x\n"
+        );
+        assert_eq!(
+            explain("x/y", Conf::default()),
+            "fraction => flattened with /\n  - not vulgar fraction: no vulgar fraction has these terms\n  - not script fraction: the denominator has no subscript form\n  numerator: identifier x\n  denominator: identifier y\n"
+        );
+    }
+
+    #[test]
+    fn block_fractions_stack() {
+        let conf = Conf {
+            block: true,
+            script_fracs: false,
+            ..Conf::default()
+        };
+        assert_eq!(
+            explain("(a+b)/c", conf),
+            "fraction => stacked fraction\n  - not vulgar fraction: no vulgar fraction has these terms\n  - not script fraction: script_fracs is off\n  numerator: group ()\n    identifier a\n    operator +\n    identifier b\n  denominator: identifier c\n"
+        );
+    }
+
+    #[test]
+    fn block_ascii_fractions_stack() {
+        let conf = Conf {
+            block: true,
+            ascii: true,
+            ..Conf::default()
+        };
+        assert_eq!(conf.parse("1/2").to_string(), "1\n-\n2");
+        assert_eq!(
+            explain("1/2", conf),
+            "fraction => stacked fraction\n  - not vulgar fraction: vulgar_fracs is off\n  - not one fraction: needs both vulgar_fracs and script_fracs\n  - not script fraction: script_fracs is off\n  numerator: number 1\n  denominator: number 2\n"
+        );
+    }
+
+    #[test]
+    fn fraction_strategies_match_output() {
+        let default = Conf::default();
+        let confs = [
+            default,
+            Conf {
+                vulgar_fracs: false,
+                ..default
+            },
+            Conf {
+                block: true,
+                ..default
+            },
+            Conf {
+                block: true,
+                script_fracs: false,
+                ..default
+            },
+            Conf {
+                block: true,
+                ascii: true,
+                ..default
+            },
+        ];
+        for conf in confs {
+            for inp in ["1/2", "1/x", "x/y", "2/3", "(a+b)/c", "1/(x+a)"] {
+                let out = conf.parse(inp).to_string();
+                let explained = explain(inp, conf);
+                let strategy = explained.lines().next().unwrap();
+                let matches = match strategy.trim_start_matches("fraction => ") {
+                    "vulgar fraction" => out.chars().count() == 1,
+                    "one fraction" => out.starts_with('⅟'),
+                    "script fraction" => out.contains('⁄'),
+                    "stacked fraction" => out.lines().count() > 1,
+                    "flattened with /" => out.contains('/') && !out.contains('\n'),
+                    _ => false,
+                };
+                assert!(matches, "{inp} with {conf:?}: {strategy} but {out:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(
+            explain("x_2^q", Conf::default()),
+            "scripts => written with _ and ^\n  - not unicode scripts: the superscript has no unicode form\n  base: identifier x\n  subscript: number 2\n  superscript: identifier q\n"
+        );
+        assert_eq!(
+            explain("sin^2 x", Conf::default()),
+            "function sin => unicode superscript\n  superscript: number 2\n  argument: identifier x\n"
+        );
+        let block = Conf {
+            block: true,
+            ..Conf::default()
+        };
+        assert_eq!(
+            explain("sum_i", block),
+            "scripts => limits above and below\n  base: symbol sum\n  subscript: identifier i\n"
+        );
+    }
+
+    #[test]
+    fn operators_by_name() {
+        assert_eq!(
+            explain("ubrace x", Conf::default()),
+            "unary ubrace => written by name\n  - not unicode form: none applies to this operator and argument\n  argument: identifier x\n"
+        );
+        assert_eq!(
+            explain("hat(xy)", Conf::default()),
+            "unary hat => written by name\n  - not unicode form: none applies to this operator and argument\n  argument: group ()\n    identifier x\n    identifier y\n"
+        );
+        assert_eq!(
+            explain("hat x", Conf::default()),
+            "unary hat\n  argument: identifier x\n"
+        );
+        assert_eq!(
+            explain("stackrel(ab)(=)", Conf::default()).lines().next(),
+            Some("binary stackrel => written by name")
+        );
+        let block = Conf {
+            block: true,
+            ..Conf::default()
+        };
+        assert_eq!(
+            explain("ubrace x", block),
+            "unary ubrace\n  argument: identifier x\n"
+        );
+    }
+
+    #[test]
+    fn block_scripts_stack() {
+        let block = Conf {
+            block: true,
+            ..Conf::default()
+        };
+        assert_eq!(
+            explain("e^(x_1)", block),
+            "scripts => stacked beside the base\n  - not unicode scripts: the superscript has no unicode form\n  base: identifier e\n  superscript: group ()\n    scripts => unicode subscript\n      base: identifier x\n      subscript: number 1\n"
+        );
+    }
+
+    #[test]
+    fn folded_nodes_have_no_strategy() {
+        // the fraction is only probed while combining the hat into a single character
+        assert_eq!(
+            explain("hat(1/2)", Conf::default()),
+            "unary hat\n  argument: group ()\n    fraction\n      numerator: number 1\n      denominator: number 2\n"
+        );
+    }
+
+    #[test]
+    fn nested_scripts() {
+        assert_eq!(
+            explain("e^(x_1)", Conf::default()),
+            "scripts => written with ^\n  - not unicode scripts: the superscript has no unicode form\n  base: identifier e\n  superscript: group ()\n    scripts => unicode subscript\n      base: identifier x\n      subscript: number 1\n"
+        );
+    }
+
+    #[test]
+    fn kinds_keep_separate_layouts() {
+        let node = 0;
+        let layouts = record(|| {
+            choose(&node, Layout::VulgarFraction);
+            choose(&node, Layout::UnicodeScripts);
+        });
+        assert_eq!(
+            layouts.get(&node_key(&node, NodeKind::Fraction)),
+            Some(&Layout::VulgarFraction)
+        );
+        assert_eq!(
+            layouts.get(&node_key(&node, NodeKind::Script)),
+            Some(&Layout::UnicodeScripts)
+        );
+    }
+
+    #[test]
+    fn tentative_layouts() {
+        let (kept, dropped) = (0, 0);
+        let layouts = record(|| {
+            let ((), layouts) = tentative(|| choose(&kept, Layout::StackedFraction));
+            layouts.keep();
+            let ((), _layouts) = tentative(|| choose(&dropped, Layout::StackedFraction));
+        });
+        assert!(layouts.contains_key(&node_key(&kept, NodeKind::Fraction)));
+        assert!(!layouts.contains_key(&node_key(&dropped, NodeKind::Fraction)));
+        // nothing is recorded outside of an explanation
+        let ((), layouts) = tentative(|| choose(&kept, Layout::StackedFraction));
+        layouts.keep();
+        assert!(LAYOUTS.with_borrow(Option::is_none));
+    }
+
+    #[test]
+    fn panicking_render_stops_recording() {
+        let res = panic::catch_unwind(|| record(|| panic!("render failed")));
+        assert!(res.is_err());
+        assert!(LAYOUTS.with_borrow(Option::is_none));
+    }
+}
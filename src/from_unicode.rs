@@ -0,0 +1,525 @@
+//! Conversion of rendered unicode math back into asciimath
+
+use asciimath_parser::Token;
+use emojis::SkinTone;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use unicode_normalization::char::decompose_canonical;
+
+use super::Conf;
+use super::ast::vulgar_frac_parts;
+use super::tokens::{
+    UNICODE_TOKENS, bold_map, cal_map, double_map, frak_map, italic_map, mono_map, sans_map,
+    subscript_char, superscript_char, symbol_str,
+};
+
+/// Unicode symbols and the first asciimath name that renders them
+static SYMBOLS: LazyLock<HashMap<char, &'static str>> = LazyLock::new(|| {
+    let mut symbols = HashMap::new();
+    for (name, tok) in UNICODE_TOKENS {
+        if tok == Token::Symbol
+            && let Some(chr) = only(symbol_str(name, SkinTone::Default))
+            && !chr.is_ascii()
+        {
+            symbols.entry(chr).or_insert(name);
+        }
+    }
+    symbols
+});
+
+/// Styled characters and the font command and plain character that produce them
+static FONTS: LazyLock<HashMap<char, (&'static str, char)>> = LazyLock::new(|| {
+    let fonts = [
+        ("bb", bold_map as fn(char) -> char),
+        ("bbb", double_map),
+        ("cc", cal_map),
+        ("fr", frak_map),
+        ("sf", sans_map),
+        ("tt", mono_map),
+        ("it", italic_map),
+    ];
+    let mut styled = HashMap::new();
+    for chr in ('A'..='Z')
+        .chain('a'..='z')
+        .chain('0'..='9')
+        .chain('Α'..='Ω')
+        .chain('α'..='ω')
+        .chain(['ϴ', '∂', 'ϵ', 'ϑ', 'ϰ', 'ϕ', 'ϱ', 'ϖ', '∇'])
+    {
+        for (name, font) in fonts {
+            let mapped = font(chr);
+            if mapped != chr {
+                styled.entry(mapped).or_insert((name, chr));
+            }
+        }
+    }
+    styled
+});
+
+/// Invert a script mapping over the characters it covers
+fn inverted(script: fn(char) -> Option<char>) -> HashMap<char, char> {
+    ('!'..='~')
+        .chain('α'..='ω')
+        .filter_map(|chr| script(chr).map(|mapped| (mapped, chr)))
+        .collect()
+}
+
+static SUPERSCRIPTS: LazyLock<HashMap<char, char>> = LazyLock::new(|| inverted(superscript_char));
+
+static SUBSCRIPTS: LazyLock<HashMap<char, char>> = LazyLock::new(|| inverted(subscript_char));
+
+fn only(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    let first = chars.next()?;
+    chars.next().is_none().then_some(first)
+}
+
+fn bracket(chr: char) -> Option<&'static str> {
+    match chr {
+        '⟨' => Some("(:"),
+        '⟩' => Some(":)"),
+        '⌊' => Some("|__"),
+        '⌋' => Some("__|"),
+        '⌈' => Some("|~"),
+        '⌉' => Some("~|"),
+        _ => None,
+    }
+}
+
+fn modifier(mark: char) -> Option<&'static str> {
+    match mark {
+        '\u{0305}' => Some("overline"),
+        '\u{0332}' => Some("underline"),
+        '\u{0336}' => Some("cancel"),
+        '\u{0302}' => Some("hat"),
+        '\u{0303}' => Some("tilde"),
+        '\u{0304}' => Some("bar"),
+        '\u{0307}' => Some("dot"),
+        '\u{0308}' => Some("ddot"),
+        '\u{0311}' => Some("overarc"),
+        '\u{20D7}' => Some("vec"),
+        _ => None,
+    }
+}
+
+/// Split a precomposed character into its base and a known modifier mark
+fn decomposed(chr: char) -> Option<(char, char)> {
+    let mut parts = Vec::with_capacity(2);
+    decompose_canonical(chr, |part| parts.push(part));
+    match parts[..] {
+        [base, mark] if modifier(mark).is_some() => Some((base, mark)),
+        _ => None,
+    }
+}
+
+/// Whether the text is a single parenthesized group
+fn enclosed(text: &str) -> bool {
+    let mut depth = 0;
+    for (i, chr) in text.char_indices() {
+        match chr {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if i == 0 => return false,
+            _ => {}
+        }
+        if depth == 0 {
+            return i == text.len() - 1;
+        }
+    }
+    false
+}
+
+/// Wrap converted script or argument content in brackets unless it's a single token
+fn wrapped(plain: &str, converted: String) -> String {
+    if plain.chars().count() == 1
+        || plain.chars().all(|c| c.is_ascii_digit())
+        || enclosed(&converted)
+    {
+        converted
+    } else {
+        format!("({converted})")
+    }
+}
+
+/// Apply a unary command to converted content
+fn applied(name: &str, plain: &str) -> String {
+    let arg = wrapped(plain, from_unicode(plain));
+    if arg.starts_with('(') {
+        format!("{name}{arg}")
+    } else {
+        format!("{name} {arg}")
+    }
+}
+
+/// Whether two adjacent pieces would run together into a different token
+fn needs_space(last: char, next: char) -> bool {
+    let punct = |c: char| c.is_ascii_punctuation() && !"()[]{},".contains(c);
+    (last.is_alphanumeric() && next.is_alphanumeric()) || (punct(last) && punct(next))
+}
+
+/// Accumulates asciimath, separating generated names from their neighbors
+///
+/// Text can be converted in several pieces, with raw text between them that doesn't separate
+/// their names, like the colour sequences of highlighted output.
+#[derive(Debug, Default)]
+pub(crate) struct Output {
+    text: String,
+    /// The last character converted, ignoring raw text
+    last: Option<char>,
+    generated: bool,
+    /// Whether to quote words that wouldn't read back as themselves, so the result parses back
+    quote_words: bool,
+}
+
+impl Output {
+    fn push(&mut self, piece: &str, generated: bool) {
+        if (generated || self.generated)
+            && let (Some(last), Some(next)) = (self.last, piece.chars().next())
+            && needs_space(last, next)
+        {
+            self.text.push(' ');
+        }
+        self.text.push_str(piece);
+        self.last = piece.chars().last().or(self.last);
+        self.generated = generated;
+    }
+
+    /// Copy text through unchanged
+    pub(crate) fn raw(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    pub(crate) fn into_text(self) -> String {
+        self.text
+    }
+
+    /// Copy through the word of letters starting at `start`, and return where it ends
+    fn word(&mut self, chars: &[char], start: usize) -> usize {
+        let word: String = chars[start..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect();
+        let mut end = start + word.len();
+        let spaced = chars.get(end).is_some_and(|c| c.is_whitespace());
+        if self.quote_words && reads_back_differently(&word, spaced) {
+            // spaces outside of quotes would be dropped, so they move inside
+            let before = self.text.len() - self.text.trim_end().len();
+            let leading = self.text.split_off(self.text.len() - before);
+            let trailing: String = chars[end..]
+                .iter()
+                .take_while(|c| c.is_whitespace())
+                .collect();
+            end += trailing.chars().count();
+            self.push(&format!("\"{leading}{word}{trailing}\""), true);
+        } else {
+            self.push(&word, false);
+        }
+        end
+    }
+
+    /// Convert a piece of unicode math text
+    pub(crate) fn convert(&mut self, inp: &str) {
+        let chars: Vec<char> = inp.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let chr = chars[i];
+            let den = if chr == '⅟' {
+                run(&chars, i + 1, &SUBSCRIPTS)
+            } else {
+                String::new()
+            };
+            if !den.is_empty() {
+                // a one over fraction with a subscript denominator
+                i += den.chars().count() + 1;
+                self.push(&format!("1/{}", wrapped(&den, from_unicode(&den))), true);
+                continue;
+            }
+            let sup = run(&chars, i, &SUPERSCRIPTS);
+            if !sup.is_empty() {
+                i += sup.chars().count();
+                let sub = if chars.get(i) == Some(&'⁄') {
+                    run(&chars, i + 1, &SUBSCRIPTS)
+                } else {
+                    String::new()
+                };
+                if sub.is_empty() {
+                    self.push(&format!("^{}", wrapped(&sup, from_unicode(&sup))), true);
+                } else {
+                    // a script fraction
+                    i += sub.chars().count() + 1;
+                    self.push(
+                        &format!(
+                            "{}/{}",
+                            wrapped(&sup, from_unicode(&sup)),
+                            wrapped(&sub, from_unicode(&sub))
+                        ),
+                        true,
+                    );
+                }
+                continue;
+            }
+            let sub = run(&chars, i, &SUBSCRIPTS);
+            if !sub.is_empty() {
+                i += sub.chars().count();
+                self.push(&format!("_{}", wrapped(&sub, from_unicode(&sub))), true);
+                continue;
+            }
+            i += 1;
+            if chr.is_whitespace() {
+                self.push(chr.encode_utf8(&mut [0; 4]), false);
+            } else if let Some((num, den)) = vulgar_frac_parts(chr) {
+                self.push(&format!("{num}/{den}"), true);
+            } else if chr == '√' {
+                self.push("sqrt", true);
+            } else if chr == '∛' {
+                self.push("root(3)", true);
+            } else if chr == '∜' {
+                self.push("root(4)", true);
+            } else if let Some(brack) = bracket(chr) {
+                self.push(brack, true);
+            } else if let Some((base, mark)) = chars
+                .get(i)
+                .and_then(|&mark| modifier(mark).map(|_| (chr, mark)))
+                .or_else(|| decomposed(chr))
+            {
+                if chars.get(i) == Some(&mark) {
+                    i += 1;
+                }
+                let mut plain = String::from(base);
+                // line modifiers are applied to every character of their argument
+                if matches!(mark, '\u{0305}' | '\u{0332}' | '\u{0336}') {
+                    while let (Some(&next), Some(&next_mark)) = (chars.get(i), chars.get(i + 1))
+                        && next_mark == mark
+                        && !next.is_whitespace()
+                    {
+                        plain.push(next);
+                        i += 2;
+                    }
+                }
+                let name = modifier(mark).unwrap_or_else(|| unreachable!("mark is a modifier"));
+                self.push(&applied(name, &plain), true);
+            } else if let Some(name) = SYMBOLS.get(&chr) {
+                self.push(name, true);
+            } else if let Some(&(font, _)) = FONTS.get(&chr) {
+                let plain: String = chars[i - 1..]
+                    .iter()
+                    .map_while(|c| FONTS.get(c).filter(|(f, _)| *f == font).map(|&(_, p)| p))
+                    .collect();
+                i += plain.chars().count() - 1;
+                self.push(&applied(font, &plain), true);
+            } else if chr.is_ascii_alphabetic() {
+                i = self.word(&chars, i - 1);
+            } else {
+                self.push(chr.encode_utf8(&mut [0; 4]), false);
+            }
+        }
+    }
+}
+
+/// Whether a word copied through would render as something else, like plain text reading `text`
+/// that would parse back as the `text` function
+///
+/// Operators written out by name, like the `hat` of `hat (xy)`, are followed by a space and read
+/// back as themselves.
+fn reads_back_differently(word: &str, spaced: bool) -> bool {
+    let rendered = Conf::default().parse(word).to_string();
+    rendered != word && !(spaced && rendered.trim_end() == word)
+}
+
+/// Take the run of characters starting at `start` that all map through `table`
+fn run(chars: &[char], start: usize, table: &HashMap<char, char>) -> String {
+    chars[start..].iter().map_while(|c| table.get(c)).collect()
+}
+
+/// Convert unicode math text back into asciimath
+///
+/// This inverts the character mappings used when rendering: super and subscripts, vulgar,
+/// script and one over fractions, styled letters, combining modifiers, roots and named symbols.
+/// Everything else is copied through, so the result parsed with
+/// [`parse_unicode`][crate::parse_unicode] renders back to the original text.
+///
+/// ```
+/// let res = asciimath_unicode::from_unicode("x² + ½ ∈ ℝ");
+/// assert_eq!(res, "x^2 + 1/2 in RR");
+/// ```
+#[must_use]
+pub fn from_unicode(inp: &str) -> String {
+    let mut out = Output {
+        quote_words: true,
+        ..Output::default()
+    };
+    out.convert(inp);
+    out.text
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::from_unicode;
+    use crate::parse_unicode;
+
+    /// Formulas round tripped here in addition to every default rendering in the tests of
+    /// `inline.rs`, which check the round trip of their own inputs
+    const CORPUS: &[&str] = &[
+        // scripts
+        "x^2",
+        "x_i",
+        "x_(i+1)",
+        "x_1^2",
+        "e^(i pi) + 1 = 0",
+        "a_(n+1) = a_n + d",
+        // fractions
+        "1/2",
+        "3/4 + 1/3",
+        "7/8",
+        "1/10",
+        "x/y",
+        "(a+b)/(c+d)",
+        "sinx / cosy",
+        "{a} / (s)",
+        "y / [x]",
+        // roots
+        "sqrt(x+1)",
+        "root(4)x",
+        // fonts
+        "bb v",
+        "bb(alpha x)",
+        "bbb(AB)",
+        "cc F + fr g",
+        // modifiers
+        "hat a",
+        "vec v",
+        "overline(xy)",
+        "ul(ab)",
+        // symbols and functions
+        "alpha + beta",
+        "alpha <= beta",
+        "a != b",
+        "x in RR",
+        "a xx b",
+        "a -> b",
+        "sqrt(x+1) -> oo",
+        "AA x EE y : x < y",
+        "del f / del x",
+        "O/ sube A",
+        "(:x, y:)",
+        "|__x__| + |~y~|",
+        "f(x) = x^2 + 2x + 1",
+        "int_0^1 f(x) dx",
+        "lim_(x->oo) 1/x = 0",
+        "[[1,2,|,3],[4,5,|,6]]",
+        ":hand:",
+        "全全全 = 全",
+    ];
+
+    /// Formulas that don't survive a round trip, and why
+    const NO_ROUND_TRIP: &[(&str, &str)] = &[
+        ("1 / sinx", "scripts split at the space after a function"),
+        ("sin x/x", "scripts split at the space after a function"),
+        ("x^sin x", "scripts split at the space after a function"),
+        (
+            "fr(g)",
+            "g is a function token, so it renders with a trailing space",
+        ),
+        (
+            "x^(alpha beta gamma delta epsilon theta iota phi varphi chi)",
+            "superscript varphi reads back as a plain character",
+        ),
+    ];
+
+    /// Assert that the rendering of `inp` converts back to asciimath that renders the same, or
+    /// that it still doesn't if it's a known exception
+    pub(crate) fn assert_round_trips(inp: &str) {
+        match NO_ROUND_TRIP.iter().find(|(skip, _)| *skip == inp) {
+            Some((_, reason)) => assert!(
+                round_trips(inp).is_err(),
+                "{inp:?} now round trips: {reason}"
+            ),
+            None => {
+                if let Err(err) = round_trips(inp) {
+                    panic!("{err}");
+                }
+            }
+        }
+    }
+
+    /// Whether the rendering of `inp` converts back to asciimath that renders the same
+    fn round_trips(inp: &str) -> Result<(), String> {
+        let unicode = parse_unicode(inp).to_string();
+        let ascii = from_unicode(&unicode);
+        let again = parse_unicode(&ascii).to_string();
+        if again == unicode {
+            Ok(())
+        } else {
+            Err(format!("{inp:?} -> {unicode:?} -> {ascii:?} -> {again:?}"))
+        }
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(from_unicode("x²"), "x^2");
+        assert_eq!(from_unicode("x²³"), "x^23");
+        assert_eq!(from_unicode("xⁿ⁺¹"), "x^(n+1)");
+        assert_eq!(from_unicode("xᵢ²"), "x_i^2");
+        assert_eq!(from_unicode("x² 3"), "x^2 3");
+        assert_eq!(from_unicode("eᵝ"), "e^beta");
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(from_unicode("½"), "1/2");
+        assert_eq!(from_unicode("3½"), "3 1/2");
+        assert_eq!(from_unicode("¹²⁄₅"), "12/5");
+        assert_eq!(from_unicode("ˣ⁄ᵧ"), "x/gamma");
+    }
+
+    #[test]
+    fn fonts() {
+        assert_eq!(from_unicode("𝐯"), "bb v");
+        assert_eq!(from_unicode("𝐱𝐲"), "bb(xy)");
+        assert_eq!(from_unicode("𝔸x"), "bbb A x");
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(from_unicode("α+β"), "alpha+beta");
+        assert_eq!(from_unicode("x∈ℝ"), "x in RR");
+        assert_eq!(from_unicode("a×b"), "a xx b");
+        assert_eq!(from_unicode("a→b"), "a rarr b");
+        assert_eq!(from_unicode("∑ᵢxᵢ"), "sum_i x_i");
+        assert_eq!(from_unicode("⟨x⟩"), "(:x:)");
+    }
+
+    #[test]
+    fn roots_and_modifiers() {
+        assert_eq!(from_unicode("√x"), "sqrt x");
+        assert_eq!(from_unicode("√(x+1)"), "sqrt(x+1)");
+        assert_eq!(from_unicode("∛x"), "root(3)x");
+        assert_eq!(from_unicode("x̅y̅"), "overline(xy)");
+        assert_eq!(from_unicode("ā"), "bar a");
+        assert_eq!(from_unicode("x\u{20D7}"), "vec x");
+    }
+
+    #[test]
+    fn one_over_fractions() {
+        assert_eq!(from_unicode("⅟ₓ"), "1/x");
+        assert_eq!(from_unicode("⅟₍ₓ₊ₐ₎"), "1/(x+a)");
+        assert_eq!(from_unicode("⅟"), "⅟");
+    }
+
+    #[test]
+    fn quoted_words() {
+        assert_eq!(from_unicode("text"), "\"text\"");
+        assert_eq!(from_unicode("sum"), "\"sum\"");
+        assert_eq!(from_unicode("a text b"), "a\" text \"b");
+        // words that read back as themselves aren't quoted
+        assert_eq!(from_unicode("sin x"), "sin x");
+        assert_eq!(from_unicode("hat (xy)"), "hat (xy)");
+    }
+
+    #[test]
+    fn round_trip() {
+        for inp in CORPUS {
+            assert_round_trips(inp);
+        }
+    }
+}
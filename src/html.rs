@@ -0,0 +1,485 @@
+//! Rendering of asciimath as html spans around the unicode rendering
+
+use asciimath_parser::tree::{
+    Expression, Frac, Func, Group, Intermediate, Matrix, Script, ScriptFunc, Simple, SimpleBinary,
+    SimpleFunc, SimpleScript,
+};
+use std::fmt;
+use std::fmt::Write;
+
+use super::Conf;
+use super::ascii::to_ascii;
+use super::ast::{extract_raw_text, extract_text};
+use super::inline::{MapperConf, Sink};
+use super::tokens::{left_bracket_str, right_bracket_str, symbol_str};
+
+/// Escapes text written to html, optionally transliterating it to ascii first
+#[derive(Debug)]
+pub struct Html<'a, W: ?Sized> {
+    inner: &'a mut W,
+    ascii: bool,
+}
+
+impl<'a, W: Write + ?Sized> Html<'a, W> {
+    pub fn new(inner: &'a mut W, ascii: bool) -> Self {
+        Html { inner, ascii }
+    }
+
+    /// Write markup without escaping
+    fn markup(&mut self, markup: &str) -> fmt::Result {
+        self.inner.write_str(markup)
+    }
+
+    fn open(&mut self, class: &str) -> fmt::Result {
+        write!(self.inner, "<span class=\"{class}\">")
+    }
+
+    fn close(&mut self) -> fmt::Result {
+        self.markup("</span>")
+    }
+}
+
+impl<W: Write + ?Sized> Write for Html<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let transliterated;
+        let text = if self.ascii {
+            transliterated = to_ascii(s);
+            &transliterated
+        } else {
+            s
+        };
+        escape(text, self.inner)
+    }
+}
+
+/// Escape text for use in an html or xml element or a quoted attribute
+pub(crate) fn escape(text: &str, out: &mut (impl Write + ?Sized)) -> fmt::Result {
+    for chr in text.chars() {
+        match chr {
+            '&' => out.write_str("&amp;")?,
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            '"' => out.write_str("&quot;")?,
+            chr => out.write_char(chr)?,
+        }
+    }
+    Ok(())
+}
+
+/// The class of a leaf, matching the highlight colours of the inline renderer
+fn leaf_class(rendered: &str) -> &'static str {
+    if rendered.chars().next().is_some_and(char::is_alphabetic) {
+        "am-ident"
+    } else {
+        "am-op"
+    }
+}
+
+impl Conf {
+    /// Write a node exactly as the inline renderer would, inside a span with `class`
+    fn html_text<W: Write + ?Sized>(
+        class: &str,
+        mconf: MapperConf,
+        out: &mut Html<'_, W>,
+        render: impl FnOnce(&mut super::inline::Mapper<'_, Html<'_, W>>) -> fmt::Result,
+    ) -> fmt::Result {
+        out.open(class)?;
+        render(&mut mconf.wrap(out))?;
+        out.close()
+    }
+
+    /// Write literal text mapped by `mconf`, like brackets and separators
+    fn html_str(
+        s: &str,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        mconf.wrap(out).write_str(s)
+    }
+
+    /// A fraction of simple parts, with its parts in spans when it falls back to a `/`
+    fn html_simplefrac(
+        self,
+        numer: &Simple<'_>,
+        denom: &Simple<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        if self
+            .inline_simplefrac(numer, denom, &mut mconf.wrap(&mut Sink))
+            .is_ok()
+        {
+            Self::html_text("am-frac", mconf, out, |out| {
+                self.inline_simplefrac(numer, denom, out)
+            })
+        } else {
+            out.open("am-frac")?;
+            self.html_simple(numer, mconf, out)?;
+            Self::html_str("/", mconf, out)?;
+            self.html_simple(denom, mconf, out)?;
+            out.close()
+        }
+    }
+
+    fn html_simplebinary(
+        self,
+        binary: &SimpleBinary<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match (binary.op, binary.first(), binary.second()) {
+            (attr @ ("id" | "class"), name, arg) => {
+                out.markup("<span ")?;
+                out.markup(attr)?;
+                out.markup("=\"")?;
+                // class lists keep the spaces between their names
+                let value = if attr == "class" {
+                    extract_text(name)
+                } else {
+                    extract_raw_text(name)
+                };
+                escape(&value, out.inner)?;
+                out.markup("\">")?;
+                match arg {
+                    Simple::Group(Group { expr, .. }) if self.strip_brackets => {
+                        self.html_expression(expr, mconf, out)?;
+                    }
+                    arg => self.html_simple(arg, mconf, out)?,
+                }
+                out.close()
+            }
+            ("frac", numer, denom) => self.html_simplefrac(numer, denom, mconf, out),
+            _ => Self::html_text("am-binary", mconf, out, |out| {
+                self.inline_simplebinary(binary, out)
+            }),
+        }
+    }
+
+    fn html_simplefunc(
+        self,
+        func: &SimpleFunc<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.open("am-func")?;
+        Self::html_str(func.func, mconf, out)?;
+        Self::html_str(" ", mconf, out)?;
+        self.html_simple(func.arg(), mconf, out)?;
+        out.close()
+    }
+
+    fn html_group(
+        self,
+        group: &Group<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.open("am-group")?;
+        Self::html_str(left_bracket_str(group.left_bracket), mconf, out)?;
+        self.html_expression(&group.expr, mconf, out)?;
+        Self::html_str(right_bracket_str(group.right_bracket), mconf, out)?;
+        out.close()
+    }
+
+    fn html_matrix(
+        self,
+        matrix: &Matrix<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        let left = left_bracket_str(matrix.left_bracket);
+        let right = right_bracket_str(matrix.right_bracket);
+        let rules = self.matrix_rules(matrix);
+        out.open("am-matrix")?;
+        Self::html_str(left, mconf, out)?;
+        for (i, row) in matrix.rows().enumerate() {
+            if i > 0 {
+                Self::html_str(",", mconf, out)?;
+            }
+            out.open("am-row")?;
+            Self::html_str(left, mconf, out)?;
+            for (j, expr) in row.iter().enumerate() {
+                if j > 0 && !rules[j - 1] && !rules[j] {
+                    Self::html_str(",", mconf, out)?;
+                }
+                out.open(if rules[j] { "am-rule" } else { "am-cell" })?;
+                self.html_expression(expr, mconf, out)?;
+                out.close()?;
+            }
+            Self::html_str(right, mconf, out)?;
+            out.close()?;
+        }
+        Self::html_str(right, mconf, out)?;
+        out.close()
+    }
+
+    fn html_simple(
+        self,
+        simple: &Simple<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match simple {
+            Simple::Missing => Ok(()),
+            Simple::Number(_) => {
+                Self::html_text("am-num", mconf, out, |out| self.inline_simple(simple, out))
+            }
+            Simple::Text(_) => {
+                Self::html_text("am-text", mconf, out, |out| self.inline_simple(simple, out))
+            }
+            &Simple::Ident(ident) => Self::html_text(leaf_class(ident), mconf, out, |out| {
+                self.inline_simple(simple, out)
+            }),
+            &Simple::Symbol(symbol) => {
+                let class = leaf_class(symbol_str(symbol, self.skin_tone));
+                Self::html_text(class, mconf, out, |out| self.inline_simple(simple, out))
+            }
+            Simple::Unary(unary) => Self::html_text("am-unary", mconf, out, |out| {
+                self.inline_simpleunary(unary, out)
+            }),
+            Simple::Func(func) => self.html_simplefunc(func, mconf, out),
+            Simple::Binary(binary) => self.html_simplebinary(binary, mconf, out),
+            Simple::Group(group) => self.html_group(group, mconf, out),
+            Simple::Matrix(matrix) => self.html_matrix(matrix, mconf, out),
+        }
+    }
+
+    /// A script in its own span, mapped into a script form when every part has one
+    fn html_script(
+        self,
+        script: &Script<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        let fits = |conf: Option<MapperConf>, arg: &Simple<'_>| {
+            conf.filter(|conf| self.inline_simple(arg, &mut conf.wrap(&mut Sink)).is_ok())
+        };
+        let (lower, upper) = match script {
+            Script::None => return Ok(()),
+            Script::Sub(sub) => (fits(mconf.with_sub(), sub).map(Some), Some(None)),
+            Script::Super(sup) => (Some(None), fits(mconf.with_sup(), sup).map(Some)),
+            Script::Subsuper(sub, sup) => (
+                fits(mconf.with_sub(), sub).map(Some),
+                fits(mconf.with_sup(), sup).map(Some),
+            ),
+        };
+        let mapped = lower.zip(upper);
+        if let Some(sub) = script.sub() {
+            out.open("am-sub")?;
+            if let Some((Some(conf), _)) = mapped {
+                self.html_simple(sub, conf, out)?;
+            } else {
+                Self::html_str("_", mconf, out)?;
+                self.html_simple(sub, mconf, out)?;
+            }
+            out.close()?;
+        }
+        if let Some(sup) = script.sup() {
+            out.open("am-sup")?;
+            if let Some((_, Some(conf))) = mapped {
+                self.html_simple(sup, conf, out)?;
+            } else {
+                Self::html_str("^", mconf, out)?;
+                self.html_simple(sup, mconf, out)?;
+            }
+            out.close()?;
+        }
+        Ok(())
+    }
+
+    fn html_func(
+        self,
+        func: &Func<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.open("am-func")?;
+        Self::html_str(func.func, mconf, out)?;
+        self.html_script(&func.script, mconf, out)?;
+        // a bare function name (e.g. `f`, `g`) has a missing argument and takes no separator
+        if !matches!(
+            func.arg(),
+            ScriptFunc::Simple(SimpleScript {
+                simple: Simple::Missing,
+                ..
+            })
+        ) {
+            Self::html_str(" ", mconf, out)?;
+        }
+        self.html_scriptfunc(func.arg(), mconf, out)?;
+        out.close()
+    }
+
+    fn html_scriptfunc(
+        self,
+        func: &ScriptFunc<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match func {
+            ScriptFunc::Simple(SimpleScript {
+                simple,
+                script: Script::None,
+            }) => self.html_simple(simple, mconf, out),
+            ScriptFunc::Simple(SimpleScript { simple, script }) => {
+                out.open("am-script")?;
+                self.html_simple(simple, mconf, out)?;
+                self.html_script(script, mconf, out)?;
+                out.close()
+            }
+            ScriptFunc::Func(func) => self.html_func(func, mconf, out),
+        }
+    }
+
+    fn html_frac(
+        self,
+        frac: &Frac<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        if let (
+            ScriptFunc::Simple(SimpleScript {
+                simple: numer,
+                script: Script::None,
+            }),
+            ScriptFunc::Simple(SimpleScript {
+                simple: denom,
+                script: Script::None,
+            }),
+        ) = (&frac.numer, &frac.denom)
+        {
+            self.html_simplefrac(numer, denom, mconf, out)
+        } else if self.inline_frac(frac, &mut mconf.wrap(&mut Sink)).is_ok() {
+            Self::html_text("am-frac", mconf, out, |out| self.inline_frac(frac, out))
+        } else {
+            out.open("am-frac")?;
+            self.html_scriptfunc(&frac.numer, mconf, out)?;
+            Self::html_str("/", mconf, out)?;
+            self.html_scriptfunc(&frac.denom, mconf, out)?;
+            out.close()
+        }
+    }
+
+    fn html_expression(
+        self,
+        expr: &Expression<'_>,
+        mconf: MapperConf,
+        out: &mut Html<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        for inter in expr.iter() {
+            match inter {
+                Intermediate::ScriptFunc(func) => self.html_scriptfunc(func, mconf, out)?,
+                Intermediate::Frac(frac) => self.html_frac(frac, mconf, out)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Write `expr` as nested spans whose text is the inline rendering
+    pub(crate) fn html(self, expr: &Expression<'_>, out: &mut impl Write) -> fmt::Result {
+        let mut out = Html::new(out, self.ascii);
+        out.open("am-math")?;
+        // colours are left to css
+        let mconf = MapperConf {
+            plain: true,
+            ..MapperConf::default()
+        };
+        Conf {
+            highlight: false,
+            ansi_color: false,
+            ..self
+        }
+        .html_expression(expr, mconf, &mut out)?;
+        out.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Conf;
+
+    fn html(inp: &str) -> String {
+        Conf::default().parse(inp).to_html()
+    }
+
+    /// The text content of html, as a browser would show it
+    fn text(html: &str) -> String {
+        let mut res = String::new();
+        let mut in_tag = false;
+        for chr in html.chars() {
+            match chr {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                chr if !in_tag => res.push(chr),
+                _ => {}
+            }
+        }
+        res.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&amp;", "&")
+    }
+
+    #[test]
+    fn leaves() {
+        assert_eq!(
+            html("x + 12"),
+            "<span class=\"am-math\"><span class=\"am-ident\">x</span><span class=\"am-op\">+</span><span class=\"am-num\">12</span></span>"
+        );
+        assert_eq!(
+            html("alpha < \"a&b\""),
+            "<span class=\"am-math\"><span class=\"am-ident\">α</span><span class=\"am-op\">&lt;</span><span class=\"am-text\">a&amp;b</span></span>"
+        );
+    }
+
+    #[test]
+    fn fractions_and_scripts() {
+        assert_eq!(
+            html("1/2"),
+            "<span class=\"am-math\"><span class=\"am-frac\">½</span></span>"
+        );
+        assert_eq!(
+            html("x/y"),
+            "<span class=\"am-math\"><span class=\"am-frac\"><span class=\"am-ident\">x</span>/<span class=\"am-ident\">y</span></span></span>"
+        );
+        assert_eq!(
+            html("x_1^y"),
+            "<span class=\"am-math\"><span class=\"am-script\"><span class=\"am-ident\">x</span><span class=\"am-sub\"><span class=\"am-num\">₁</span></span><span class=\"am-sup\"><span class=\"am-ident\">ʸ</span></span></span></span>"
+        );
+        assert_eq!(
+            html("x_q"),
+            "<span class=\"am-math\"><span class=\"am-script\"><span class=\"am-ident\">x</span><span class=\"am-sub\">_<span class=\"am-ident\">q</span></span></span></span>"
+        );
+    }
+
+    #[test]
+    fn ids_and_classes() {
+        assert_eq!(
+            html("id(eq)(x) class(\"big red\")(y)"),
+            "<span class=\"am-math\"><span id=\"eq\"><span class=\"am-ident\">x</span></span><span class=\"big red\"><span class=\"am-ident\">y</span></span></span>"
+        );
+        assert_eq!(
+            html("class(a b)(x)"),
+            "<span class=\"am-math\"><span class=\"a b\"><span class=\"am-ident\">x</span></span></span>"
+        );
+    }
+
+    #[test]
+    fn text_matches_inline() {
+        for inp in [
+            "sum_(i=1)^n i^2 = (n(n+1)(2n+1))/6",
+            "[[a,b],[c,d]] * vec x",
+            "sin^2 x + cos(x)^2 = 1",
+            "e^(x_1) -> oo",
+            "sqrt(x+1) / (a_q)",
+            "f(x) = root 3 x",
+        ] {
+            let conf = Conf::default();
+            assert_eq!(
+                text(&conf.parse(inp).to_html()),
+                conf.parse(inp).to_string(),
+                "{inp}"
+            );
+        }
+    }
+}
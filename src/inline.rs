@@ -9,7 +9,12 @@ use std::fmt::Write;
 use unicode_normalization::char::compose;
 
 use super::Conf;
-use super::ast::{extract_single_char, extract_vulgar_frac};
+use super::ansi::{self, Color, RESET, display_width};
+use super::ascii::to_ascii;
+use super::ast::{extract_raw_text, extract_single_char, extract_vulgar_frac};
+use super::block::{CONTINUATION_INDENT, break_segments};
+use super::diagnostics::{self, Span, WarningKind};
+use super::explain::{self, Layout};
 use super::tokens::{
     bold_map, cal_map, double_map, frak_map, italic_map, left_bracket_str, mono_map,
     right_bracket_str, sans_map, subscript_char, superscript_char, symbol_str,
@@ -29,6 +34,9 @@ pub struct MapperConf {
     pub font: Option<fn(char) -> char>,
     pub sub_sup: Option<fn(char) -> Option<char>>,
     pub modifier: Option<char>,
+    pub color: Option<Color>,
+    /// Set while probing rendered output, where colour sequences would get in the way
+    pub plain: bool,
 }
 
 impl MapperConf {
@@ -36,9 +44,8 @@ impl MapperConf {
     pub fn with_sub_sup(&self, sub_sup: fn(char) -> Option<char>) -> Option<MapperConf> {
         if self.sub_sup.is_none() {
             Some(MapperConf {
-                font: self.font,
                 sub_sup: Some(sub_sup),
-                modifier: self.modifier,
+                ..*self
             })
         } else {
             None
@@ -53,10 +60,12 @@ impl MapperConf {
         self.with_sub_sup(superscript_char)
     }
 
+    /// Wrap a writer for probing, degraded constructs written to it aren't reported
     pub fn wrap<S: Write>(self, other: &mut S) -> Mapper<'_, S> {
         Mapper {
             inner: other,
             conf: self,
+            report: false,
         }
     }
 }
@@ -65,6 +74,8 @@ impl MapperConf {
 pub struct Mapper<'a, W: ?Sized> {
     pub inner: &'a mut W,
     pub conf: MapperConf,
+    /// Whether this writes real output, whose degraded constructs are reported
+    pub report: bool,
 }
 
 impl<'a, W: fmt::Write + ?Sized> Mapper<'a, W> {
@@ -72,6 +83,7 @@ impl<'a, W: fmt::Write + ?Sized> Mapper<'a, W> {
         Mapper {
             inner,
             conf: MapperConf::default(),
+            report: true,
         }
     }
 
@@ -80,9 +92,9 @@ impl<'a, W: fmt::Write + ?Sized> Mapper<'a, W> {
             inner: &mut *self.inner,
             conf: MapperConf {
                 font: Some(f),
-                sub_sup: self.conf.sub_sup,
-                modifier: self.conf.modifier,
+                ..self.conf
             },
+            report: self.report,
         }
     }
 
@@ -90,23 +102,84 @@ impl<'a, W: fmt::Write + ?Sized> Mapper<'a, W> {
         Mapper {
             inner: &mut *self.inner,
             conf: MapperConf {
-                font: self.conf.font,
-                sub_sup: self.conf.sub_sup,
                 modifier: Some(c),
+                ..self.conf
             },
+            report: self.report,
+        }
+    }
+
+    pub fn reborrow(&mut self) -> Mapper<'_, W> {
+        Mapper {
+            inner: &mut *self.inner,
+            conf: self.conf,
+            report: self.report,
+        }
+    }
+
+    /// Continue writing to the same output with `conf`
+    pub fn with_conf(&mut self, conf: MapperConf) -> Mapper<'_, W> {
+        Mapper {
+            inner: &mut *self.inner,
+            conf,
+            report: self.report,
+        }
+    }
+
+    /// Report a degraded construct, unless this is only a probe
+    pub fn warn(&self, kind: WarningKind, cover: impl FnOnce(&mut Span)) {
+        if self.report {
+            diagnostics::warn(kind, cover);
+        }
+    }
+
+    /// Record the layout chosen for `node`, unless this is only a probe
+    pub fn choose<T>(&self, node: &T, layout: Layout) {
+        if self.report {
+            explain::choose(node, layout);
+        }
+    }
+
+    /// Write everything `write` produces in `color`
+    ///
+    /// The sequences only go around the whole output, so they never separate a character from
+    /// its combining marks, and the enclosing colour is restored afterwards.
+    pub fn colored(
+        &mut self,
+        color: Color,
+        write: impl FnOnce(&mut Mapper<'_, W>) -> fmt::Result,
+    ) -> fmt::Result {
+        if self.conf.plain {
+            return write(&mut self.reborrow());
+        }
+        write!(self.inner, "{color}")?;
+        write(&mut Mapper {
+            inner: &mut *self.inner,
+            conf: MapperConf {
+                color: Some(color),
+                ..self.conf
+            },
+            report: self.report,
+        })?;
+        match self.conf.color {
+            Some(outer) => write!(self.inner, "{outer}"),
+            None => self.inner.write_str(RESET),
         }
     }
 
     pub fn onto<'b, S: Write>(&self, other: &'b mut S) -> Mapper<'b, S> {
         Mapper {
             inner: other,
-            conf: self.conf,
+            conf: MapperConf {
+                color: None,
+                plain: true,
+                ..self.conf
+            },
+            report: false,
         }
     }
-}
 
-impl<W: fmt::Write + ?Sized> fmt::Write for Mapper<'_, W> {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
+    fn write_mapped(&mut self, s: &str) -> fmt::Result {
         if self.conf.font.is_none() && self.conf.sub_sup.is_none() && self.conf.modifier.is_none() {
             self.inner.write_str(s)
         } else {
@@ -127,6 +200,12 @@ impl<W: fmt::Write + ?Sized> fmt::Write for Mapper<'_, W> {
     }
 }
 
+impl<W: fmt::Write + ?Sized> fmt::Write for Mapper<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_mapped(s)
+    }
+}
+
 #[derive(Debug, Default)]
 struct SingleChar(Option<char>);
 
@@ -224,7 +303,30 @@ fn only<T>(mut iter: impl Iterator<Item = T>) -> Option<T> {
     if iter.next().is_none() { first } else { None }
 }
 
+/// The highlight colour for a leaf, identifiers start with a letter and anything else is an operator
+fn lexical(rendered: &str) -> Color {
+    if rendered.chars().next().is_some_and(char::is_alphabetic) {
+        ansi::IDENT
+    } else {
+        ansi::OPERATOR
+    }
+}
+
 impl Conf {
+    /// Write a leaf in its highlight colour, unless an explicit colour already applies
+    fn highlighted(
+        self,
+        color: Color,
+        text: &str,
+        out: &mut Mapper<impl fmt::Write>,
+    ) -> fmt::Result {
+        if self.highlight && out.conf.color.is_none() {
+            out.colored(color, |out| out.write_str(text))
+        } else {
+            out.write_str(text)
+        }
+    }
+
     fn inline_simplefunc(
         self,
         simple: &SimpleFunc<'_>,
@@ -313,13 +415,19 @@ impl Conf {
         }
     }
 
-    fn inline_bgeneric(
+    pub(crate) fn inline_bgeneric(
         self,
         op: &str,
         first: &Simple<'_>,
         second: &Simple<'_>,
         out: &mut Mapper<impl fmt::Write>,
     ) -> fmt::Result {
+        out.warn(WarningKind::UnsupportedBinary, |span| {
+            span.slice(op);
+            span.simple(first);
+            span.simple(second);
+        });
+        out.choose(first, Layout::ByName);
         out.write_str(op)?;
         out.write_char(' ')?;
         self.inline_simple(first, out)?;
@@ -343,11 +451,22 @@ impl Conf {
             ("root", sgroup!(expr), arg) if xnum!(expr, "3") => self.inline_root('∛', arg, out),
             ("root", sgroup!(expr), arg) if xnum!(expr, "4") => self.inline_root('∜', arg, out),
             // frac
-            ("frac", numer, denom) => self.inline_simplefrac(numer, denom, out).or_else(|_| {
-                self.inline_simple(numer, out)?;
-                out.write_char('/')?;
-                self.inline_simple(denom, out)
-            }),
+            ("frac", numer, denom) => {
+                let formed = self.inline_simplefrac(numer, denom, out).is_ok();
+                self.inline_flattened(formed, out, |span| {
+                    span.slice(simple.op);
+                    span.simple(numer);
+                    span.simple(denom);
+                });
+                if formed {
+                    Ok(())
+                } else {
+                    out.choose(numer, Layout::FlattenedFraction);
+                    self.inline_simple(numer, out)?;
+                    out.write_char('/')?;
+                    self.inline_simple(denom, out)
+                }
+            }
             // stackrel / overset combining
             (o @ ("stackrel" | "overset"), f @ iden!("a"), a) => {
                 self.inline_cover(o, f, a, '\u{0363}', out)
@@ -427,6 +546,17 @@ impl Conf {
             ("stackrel" | "overset", sgroup!(exp), arg) if sb && xiden!(exp, "x") => {
                 self.inline_cover(simple.op, simple.first(), arg, '\u{036f}', out)
             }
+            // colors
+            ("color", color, arg) if self.ansi_color => {
+                if let Some(color) = Color::parse(&extract_raw_text(color)) {
+                    out.colored(color, |out| match arg {
+                        sgroup!(expr) if sb => self.inline_expression(expr, out),
+                        arg => self.inline_simple(arg, out),
+                    })
+                } else {
+                    self.inline_bgeneric(simple.op, simple.first(), arg, out)
+                }
+            }
             // stackrel / overset equals
             ("stackrel" | "overset", arg, symb!("=")) => {
                 self.inline_equals(simple.op, arg, simple.second(), out)
@@ -522,12 +652,17 @@ impl Conf {
         }
     }
 
-    fn inline_ugeneric(
+    pub(crate) fn inline_ugeneric(
         self,
         op: &str,
         arg: &Simple<'_>,
         out: &mut Mapper<impl fmt::Write>,
     ) -> fmt::Result {
+        out.warn(WarningKind::UnsupportedUnary, |span| {
+            span.slice(op);
+            span.simple(arg);
+        });
+        out.choose(arg, Layout::ByName);
         out.write_str(op)?;
         out.write_char(' ')?;
         self.inline_simple(arg, out)
@@ -579,6 +714,7 @@ impl Conf {
     fn inline_matrix(self, matrix: &Matrix<'_>, out: &mut Mapper<impl fmt::Write>) -> fmt::Result {
         let left = left_bracket_str(matrix.left_bracket);
         let right = right_bracket_str(matrix.right_bracket);
+        let rules = self.matrix_rules(matrix);
         out.write_str(left)?;
         for (i, row) in matrix.rows().enumerate() {
             if i > 0 {
@@ -586,7 +722,8 @@ impl Conf {
             }
             out.write_str(left)?;
             for (j, expr) in row.iter().enumerate() {
-                if j > 0 {
+                // rules separate their neighbours on their own
+                if j > 0 && !rules[j - 1] && !rules[j] {
                     out.write_char(',')?;
                 }
                 self.inline_expression(expr, out)?;
@@ -609,10 +746,13 @@ impl Conf {
     ) -> fmt::Result {
         match simple {
             Simple::Missing => Ok(()),
-            &Simple::Number(num) => out.write_str(num),
+            &Simple::Number(num) => self.highlighted(ansi::NUMBER, num, out),
             &Simple::Text(text) => out.write_str(text),
-            &Simple::Ident(ident) => out.write_str(ident),
-            &Simple::Symbol(symbol) => out.write_str(symbol_str(symbol, self.skin_tone)),
+            &Simple::Ident(ident) => self.highlighted(lexical(ident), ident, out),
+            &Simple::Symbol(symbol) => {
+                let rendered = symbol_str(symbol, self.skin_tone);
+                self.highlighted(lexical(rendered), rendered, out)
+            }
             Simple::Func(func) => self.inline_simplefunc(func, out),
             Simple::Unary(unary) => self.inline_simpleunary(unary, out),
             Simple::Binary(binary) => self.inline_simplebinary(binary, out),
@@ -621,6 +761,27 @@ impl Conf {
         }
     }
 
+    /// Report a script written with `_` or `^`, which ascii output does even with a script form
+    fn inline_unscripted(self, scripted: bool, script: &Script<'_>, out: &Mapper<impl fmt::Write>) {
+        if !scripted || self.ascii {
+            out.warn(WarningKind::UnscriptableScript, |span| span.script(script));
+        }
+    }
+
+    /// Report a fraction written with `/`, which ascii output does even with a unicode form
+    ///
+    /// Fractions are only degraded when script fractions were enabled.
+    fn inline_flattened(
+        self,
+        formed: bool,
+        out: &Mapper<impl fmt::Write>,
+        cover: impl FnOnce(&mut Span),
+    ) {
+        if self.script_fracs && (!formed || self.ascii) {
+            out.warn(WarningKind::FlattenedFraction, cover);
+        }
+    }
+
     fn inline_script(self, script: &Script<'_>, out: &mut Mapper<impl fmt::Write>) -> fmt::Result {
         let mut sink = Sink;
         match script {
@@ -629,8 +790,12 @@ impl Conf {
                 if let Some(sconf) = out.conf.with_sub()
                     && self.inline_simple(sub, &mut sconf.wrap(&mut sink)).is_ok()
                 {
-                    self.inline_simple(sub, &mut sconf.wrap(out.inner))
+                    self.inline_unscripted(true, script, out);
+                    out.choose(script, Layout::UnicodeScripts);
+                    self.inline_simple(sub, &mut out.with_conf(sconf))
                 } else {
+                    self.inline_unscripted(false, script, out);
+                    out.choose(script, Layout::WrittenScripts);
                     out.write_char('_')?;
                     self.inline_simple(sub, out)
                 }
@@ -639,8 +804,12 @@ impl Conf {
                 if let Some(sconf) = out.conf.with_sup()
                     && self.inline_simple(sup, &mut sconf.wrap(&mut sink)).is_ok()
                 {
-                    self.inline_simple(sup, &mut sconf.wrap(out.inner))
+                    self.inline_unscripted(true, script, out);
+                    out.choose(script, Layout::UnicodeScripts);
+                    self.inline_simple(sup, &mut out.with_conf(sconf))
                 } else {
+                    self.inline_unscripted(false, script, out);
+                    out.choose(script, Layout::WrittenScripts);
                     out.write_char('^')?;
                     self.inline_simple(sup, out)
                 }
@@ -655,9 +824,13 @@ impl Conf {
                         .inline_simple(sup, &mut sup_conf.wrap(&mut sink))
                         .is_ok()
                 {
-                    self.inline_simple(sub, &mut sub_conf.wrap(out.inner))?;
-                    self.inline_simple(sup, &mut sup_conf.wrap(out.inner))
+                    self.inline_unscripted(true, script, out);
+                    out.choose(script, Layout::UnicodeScripts);
+                    self.inline_simple(sub, &mut out.with_conf(sub_conf))?;
+                    self.inline_simple(sup, &mut out.with_conf(sup_conf))
                 } else {
+                    self.inline_unscripted(false, script, out);
+                    out.choose(script, Layout::WrittenScripts);
                     out.write_char('_')?;
                     self.inline_simple(sub, out)?;
                     out.write_char('^')?;
@@ -704,7 +877,7 @@ impl Conf {
 
     fn inline_sone(
         self,
-        _num: &Simple<'_>,
+        num: &Simple<'_>,
         den: &Simple<'_>,
         out: &mut Mapper<impl fmt::Write>,
     ) -> fmt::Result {
@@ -716,8 +889,9 @@ impl Conf {
                         .inline_expression(expr, &mut sconf.wrap(&mut sink))
                         .is_ok()
                 {
+                    out.choose(num, Layout::OneFraction);
                     out.write_char('⅟')?;
-                    self.inline_expression(expr, &mut sconf.wrap(out.inner))
+                    self.inline_expression(expr, &mut out.with_conf(sconf))
                 } else {
                     Err(fmt::Error)
                 }
@@ -726,8 +900,9 @@ impl Conf {
                 if let Some(sconf) = out.conf.with_sub()
                     && self.inline_simple(den, &mut sconf.wrap(&mut sink)).is_ok()
                 {
+                    out.choose(num, Layout::OneFraction);
                     out.write_char('⅟')?;
-                    let mut w = sconf.wrap(out.inner);
+                    let mut w = out.with_conf(sconf);
                     self.inline_simple(den, &mut w)
                 } else {
                     Err(fmt::Error)
@@ -747,6 +922,7 @@ impl Conf {
         if self.vulgar_fracs
             && let Some(frac) = extract_vulgar_frac(numer, denom, self.strip_brackets)
         {
+            out.choose(numer, Layout::VulgarFraction);
             return out.write_char(frac);
         }
         let mut sink = Sink;
@@ -769,9 +945,10 @@ impl Conf {
                         .inline_expression(den, &mut sub_conf.wrap(&mut sink))
                         .is_ok()
                 {
-                    self.inline_expression(num, &mut sup_conf.wrap(out.inner))?;
+                    out.choose(numer, Layout::ScriptFraction);
+                    self.inline_expression(num, &mut out.with_conf(sup_conf))?;
                     out.write_char('⁄')?;
-                    self.inline_expression(den, &mut sub_conf.wrap(out.inner))
+                    self.inline_expression(den, &mut out.with_conf(sub_conf))
                 } else {
                     Err(fmt::Error)
                 }
@@ -786,9 +963,10 @@ impl Conf {
                         .inline_expression(den, &mut sub_conf.wrap(&mut sink))
                         .is_ok()
                 {
-                    self.inline_simple(num, &mut sup_conf.wrap(out.inner))?;
+                    out.choose(numer, Layout::ScriptFraction);
+                    self.inline_simple(num, &mut out.with_conf(sup_conf))?;
                     out.write_char('⁄')?;
-                    self.inline_expression(den, &mut sub_conf.wrap(out.inner))
+                    self.inline_expression(den, &mut out.with_conf(sub_conf))
                 } else {
                     Err(fmt::Error)
                 }
@@ -803,9 +981,10 @@ impl Conf {
                         .inline_simple(den, &mut sub_conf.wrap(&mut sink))
                         .is_ok()
                 {
-                    self.inline_expression(num, &mut sup_conf.wrap(out.inner))?;
+                    out.choose(numer, Layout::ScriptFraction);
+                    self.inline_expression(num, &mut out.with_conf(sup_conf))?;
                     out.write_char('⁄')?;
-                    self.inline_simple(den, &mut sub_conf.wrap(out.inner))
+                    self.inline_simple(den, &mut out.with_conf(sub_conf))
                 } else {
                     Err(fmt::Error)
                 }
@@ -821,9 +1000,10 @@ impl Conf {
                         .inline_simple(den, &mut sub_conf.wrap(&mut sink))
                         .is_ok()
                 {
-                    self.inline_simple(num, &mut sup_conf.wrap(out.inner))?;
+                    out.choose(numer, Layout::ScriptFraction);
+                    self.inline_simple(num, &mut out.with_conf(sup_conf))?;
                     out.write_char('⁄')?;
-                    self.inline_simple(den, &mut sub_conf.wrap(out.inner))
+                    self.inline_simple(den, &mut out.with_conf(sub_conf))
                 } else {
                     Err(fmt::Error)
                 }
@@ -831,15 +1011,21 @@ impl Conf {
         }
     }
 
-    fn inline_fone(self, den: &ScriptFunc<'_>, out: &mut Mapper<impl fmt::Write>) -> fmt::Result {
+    fn inline_fone(
+        self,
+        num: &ScriptFunc<'_>,
+        den: &ScriptFunc<'_>,
+        out: &mut Mapper<impl fmt::Write>,
+    ) -> fmt::Result {
         let mut sink = Sink;
         if let Some(sconf) = out.conf.with_sub()
             && self
                 .inline_scriptfunc(den, &mut sconf.wrap(&mut sink))
                 .is_ok()
         {
+            out.choose(num, Layout::OneFraction);
             out.write_char('⅟')?;
-            self.inline_scriptfunc(den, &mut sconf.wrap(out.inner))
+            self.inline_scriptfunc(den, &mut out.with_conf(sconf))
         } else {
             Err(fmt::Error)
         }
@@ -857,18 +1043,35 @@ impl Conf {
             // simple frac
             (script_func!(num), script_func!(den)) => {
                 self.inline_simplefrac(num, den, out).or_else(|_| {
+                    out.choose(num, Layout::FlattenedFraction);
+                    self.inline_flattened(false, out, |span| {
+                        span.simple(num);
+                        span.simple(den);
+                    });
                     self.inline_simple(num, out)?;
                     out.write_char('/')?;
                     self.inline_simple(den, out)
                 })
             }
             // one vulgar
-            (script_func!(num!("1")), den) if sv => self.inline_fone(den, out).or_else(|_| {
-                out.write_str("1/")?;
-                self.inline_scriptfunc(den, out)
-            }),
+            (script_func!(num!("1")), den) if sv => {
+                self.inline_fone(&frac.numer, den, out).or_else(|_| {
+                    self.inline_flattened(false, out, |span| {
+                        span.scriptfunc(&frac.numer);
+                        span.scriptfunc(den);
+                    });
+                    out.choose(&frac.numer, Layout::FlattenedFraction);
+                    out.write_str("1/")?;
+                    self.inline_scriptfunc(den, out)
+                })
+            }
             (script_func!(sgroup!(num)), den) if sv && self.strip_brackets && xnum!(num, "1") => {
-                self.inline_fone(den, out).or_else(|_| {
+                self.inline_fone(&frac.numer, den, out).or_else(|_| {
+                    self.inline_flattened(false, out, |span| {
+                        span.scriptfunc(&frac.numer);
+                        span.scriptfunc(den);
+                    });
+                    out.choose(&frac.numer, Layout::FlattenedFraction);
                     out.write_str("1/")?;
                     self.inline_scriptfunc(den, out)
                 })
@@ -884,9 +1087,10 @@ impl Conf {
                         .inline_scriptfunc(den, &mut sub_conf.wrap(&mut sink))
                         .is_ok()
                 {
-                    self.inline_expression(num, &mut sup_conf.wrap(out.inner))?;
+                    out.choose(&frac.numer, Layout::ScriptFraction);
+                    self.inline_expression(num, &mut out.with_conf(sup_conf))?;
                     out.write_char('⁄')?;
-                    self.inline_scriptfunc(den, &mut sub_conf.wrap(out.inner))
+                    self.inline_scriptfunc(den, &mut out.with_conf(sub_conf))
                 } else {
                     Err(fmt::Error)
                 }
@@ -901,9 +1105,10 @@ impl Conf {
                         .inline_expression(den, &mut sub_conf.wrap(&mut sink))
                         .is_ok()
                 {
-                    self.inline_scriptfunc(num, &mut sup_conf.wrap(out.inner))?;
+                    out.choose(&frac.numer, Layout::ScriptFraction);
+                    self.inline_scriptfunc(num, &mut out.with_conf(sup_conf))?;
                     out.write_char('⁄')?;
-                    self.inline_expression(den, &mut sub_conf.wrap(out.inner))
+                    self.inline_expression(den, &mut out.with_conf(sub_conf))
                 } else {
                     Err(fmt::Error)
                 }
@@ -919,9 +1124,10 @@ impl Conf {
                         .inline_scriptfunc(den, &mut sub_conf.wrap(&mut sink))
                         .is_ok()
                 {
-                    self.inline_scriptfunc(num, &mut sup_conf.wrap(out.inner))?;
+                    out.choose(&frac.numer, Layout::ScriptFraction);
+                    self.inline_scriptfunc(num, &mut out.with_conf(sup_conf))?;
                     out.write_char('⁄')?;
-                    self.inline_scriptfunc(den, &mut sub_conf.wrap(out.inner))
+                    self.inline_scriptfunc(den, &mut out.with_conf(sub_conf))
                 } else {
                     Err(fmt::Error)
                 }
@@ -936,11 +1142,21 @@ impl Conf {
     ) -> fmt::Result {
         match inter {
             Intermediate::ScriptFunc(sf) => self.inline_scriptfunc(sf, out),
-            Intermediate::Frac(frac) => self.inline_frac(frac, out).or_else(|_| {
-                self.inline_scriptfunc(&frac.numer, out)?;
-                out.write_char('/')?;
-                self.inline_scriptfunc(&frac.denom, out)
-            }),
+            Intermediate::Frac(frac) => {
+                let formed = self.inline_frac(frac, out).is_ok();
+                self.inline_flattened(formed, out, |span| {
+                    span.scriptfunc(&frac.numer);
+                    span.scriptfunc(&frac.denom);
+                });
+                if formed {
+                    Ok(())
+                } else {
+                    out.choose(&frac.numer, Layout::FlattenedFraction);
+                    self.inline_scriptfunc(&frac.numer, out)?;
+                    out.write_char('/')?;
+                    self.inline_scriptfunc(&frac.denom, out)
+                }
+            }
         }
     }
 
@@ -954,18 +1170,168 @@ impl Conf {
         }
         Ok(())
     }
+
+    /// Render a top level expression, applying line breaking and ascii conversion
+    pub(crate) fn inline_render(
+        self,
+        expr: &Expression<'_>,
+        out: &mut impl fmt::Write,
+    ) -> fmt::Result {
+        match self.max_width {
+            Some(width) => self.inline_wrapped(expr, width, &mut Mapper::new(out)),
+            None if self.ascii => {
+                let mut text = String::new();
+                self.inline_expression(expr, &mut Mapper::new(&mut text))?;
+                out.write_str(&to_ascii(&text))
+            }
+            None => self.inline_expression(expr, &mut Mapper::new(out)),
+        }
+    }
+
+    /// Render an expression, breaking it into lines no wider than `max_width` where possible
+    pub(crate) fn inline_wrapped(
+        self,
+        expr: &Expression<'_>,
+        max_width: usize,
+        out: &mut Mapper<impl fmt::Write>,
+    ) -> fmt::Result {
+        let mut line_width = 0;
+        for (idx, segment) in break_segments(expr).into_iter().enumerate() {
+            let mut text = String::new();
+            for inter in segment {
+                self.inline_intermediate(inter, &mut Mapper::new(&mut text))?;
+            }
+            if self.ascii {
+                text = to_ascii(&text);
+            }
+            let width = display_width(&text);
+            if idx > 0 && line_width + width > max_width {
+                write!(out, "\n{}", " ".repeat(CONTINUATION_INDENT))?;
+                line_width = CONTINUATION_INDENT;
+            }
+            out.write_str(&text)?;
+            line_width += width;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::super::{Conf, SkinTone};
+    use crate::from_unicode::tests::assert_round_trips;
+
+    /// Render `inp` with the default configuration
+    ///
+    /// Every default rendering is also converted back with [`crate::from_unicode`], so each
+    /// case here checks the round trip too.
+    fn render(inp: &str) -> String {
+        let res = super::super::parse_unicode(inp).to_string();
+        assert_round_trips(inp);
+        res
+    }
+
+    #[test]
+    fn ansi_color() {
+        let conf = Conf {
+            ansi_color: true,
+            ..Conf::default()
+        };
+        assert_eq!(
+            conf.parse("color(red)(x+1)").to_string(),
+            "\x1b[31mx+1\x1b[39m"
+        );
+        // nested colours restore the enclosing one
+        assert_eq!(
+            conf.parse("color(red)(a color(blue)(b) c)").to_string(),
+            "\x1b[31ma\x1b[34mb\x1b[31mc\x1b[39m"
+        );
+        assert_eq!(
+            conf.parse("color(#ff8000) bb x").to_string(),
+            "\x1b[38;2;255;128;0m𝐱\x1b[39m"
+        );
+        // combining marks stay next to the character they modify
+        assert_eq!(
+            conf.parse("color(red)(hat x)").to_string(),
+            "\x1b[31mx\u{302}\x1b[39m"
+        );
+        assert_eq!(conf.parse("color(xyz)(x)").to_string(), "color (xyz) (x)");
+        assert_eq!(
+            Conf::default().parse("color(red)(x)").to_string(),
+            "color (red) (x)"
+        );
+    }
+
+    #[test]
+    fn max_width() {
+        let conf = Conf {
+            max_width: Some(8),
+            ..Conf::default()
+        };
+        assert_eq!(
+            conf.parse("f(x) = a + b - c + d = e").to_string(),
+            "f (x)=a\n  +b-c+d\n  =e"
+        );
+        assert_eq!(conf.parse("a = -b + c").to_string(), "a=-b+c");
+        // widths are measured in display columns
+        assert_eq!(conf.parse("全全全 = 全").to_string(), "全全全\n  =全");
+    }
+
+    #[test]
+    fn ascii() {
+        let conf = Conf {
+            ascii: true,
+            ..Conf::default()
+        };
+        assert_eq!(conf.parse("alpha <= beta").to_string(), "alpha<=beta");
+        assert_eq!(conf.parse("sqrt(x+1) -> oo").to_string(), "sqrt(x+1)->oo");
+        assert_eq!(conf.parse("1/2 + x^2").to_string(), "1/2+x^2");
+        let highlighted = Conf {
+            highlight: true,
+            ..conf
+        };
+        assert_eq!(
+            highlighted.parse("alpha = 2").to_string(),
+            "\x1b[34malpha\x1b[39m\x1b[35m=\x1b[39m\x1b[36m2\x1b[39m"
+        );
+        let wrapped = Conf {
+            max_width: Some(12),
+            ..conf
+        };
+        assert_eq!(
+            wrapped.parse("alpha + beta + gamma").to_string(),
+            "alpha+beta\n  +gamma"
+        );
+    }
+
+    #[test]
+    fn highlight() {
+        let conf = Conf {
+            highlight: true,
+            ..Conf::default()
+        };
+        assert_eq!(
+            conf.parse("alpha = 2").to_string(),
+            "\x1b[34mα\x1b[39m\x1b[35m=\x1b[39m\x1b[36m2\x1b[39m"
+        );
+        // scripts still apply
+        assert_eq!(
+            conf.parse("x^2").to_string(),
+            "\x1b[34mx\x1b[39m\x1b[36m²\x1b[39m"
+        );
+        let both = Conf {
+            ansi_color: true,
+            ..conf
+        };
+        assert_eq!(both.parse("color(red)(1)").to_string(), "\x1b[31m1\x1b[39m");
+    }
 
     #[test]
     fn example() {
         let ex = "sum_(i=1)^n i^3=((n(n+1))/2)^2";
         let expected = "∑₍ᵢ₌₁₎ⁿi³=(ⁿ⁽ⁿ⁺¹⁾⁄₂)²";
 
-        let res = super::super::parse_unicode(ex).to_string();
+        let res = render(ex);
         assert_eq!(res, expected);
 
         let rend = Conf::default().parse(ex);
@@ -1035,10 +1401,10 @@ mod tests {
 
     #[test]
     fn one_fracs() {
-        let res = super::super::parse_unicode("1/x").to_string();
+        let res = render("1/x");
         assert_eq!(res, "⅟ₓ");
 
-        let res = super::super::parse_unicode("1 / sinx").to_string();
+        let res = render("1 / sinx");
         assert_eq!(res, "⅟ₛᵢₙ ₓ");
 
         let opts = Conf {
@@ -1067,88 +1433,88 @@ mod tests {
     #[test]
     #[allow(clippy::unicode_not_nfc)]
     fn unary() {
-        let res = super::super::parse_unicode("sqrt x").to_string();
+        let res = render("sqrt x");
         assert_eq!(res, "√x");
 
-        let res = super::super::parse_unicode("vec x").to_string();
+        let res = render("vec x");
         assert_eq!(res, "x\u{20D7}");
 
-        let res = super::super::parse_unicode("bbb E").to_string();
+        let res = render("bbb E");
         assert_eq!(res, "𝔼");
 
-        let res = super::super::parse_unicode("bbb (E)").to_string();
+        let res = render("bbb (E)");
         assert_eq!(res, "𝔼");
 
-        let res = super::super::parse_unicode("dot x").to_string();
+        let res = render("dot x");
         assert_eq!(res, "ẋ");
 
-        let res = super::super::parse_unicode("dot{x}").to_string();
+        let res = render("dot{x}");
         assert_eq!(res, "ẋ");
 
-        let res = super::super::parse_unicode("norm x").to_string();
+        let res = render("norm x");
         assert_eq!(res, "||x||");
 
-        let res = super::super::parse_unicode("sqrt overline x").to_string();
+        let res = render("sqrt overline x");
         assert_eq!(res, "√x̅");
 
-        let res = super::super::parse_unicode("sqrt overline(x)").to_string();
+        let res = render("sqrt overline(x)");
         assert_eq!(res, "√x̅");
     }
 
     #[test]
     fn binary() {
-        let res = super::super::parse_unicode("root 3 x").to_string();
+        let res = render("root 3 x");
         assert_eq!(res, "∛x");
 
-        let res = super::super::parse_unicode("root {4} x").to_string();
+        let res = render("root {4} x");
         assert_eq!(res, "∜x");
 
-        let res = super::super::parse_unicode("stackrel *** =").to_string();
+        let res = render("stackrel *** =");
         assert_eq!(res, "≛");
 
-        let res = super::super::parse_unicode("overset a x").to_string();
+        let res = render("overset a x");
         assert_eq!(res, "x\u{0363}");
 
-        let res = super::super::parse_unicode("overset (e) {y}").to_string();
+        let res = render("overset (e) {y}");
         assert_eq!(res, "y\u{0364}");
 
-        let res = super::super::parse_unicode("oversetasinx").to_string();
+        let res = render("oversetasinx");
         assert_eq!(res, "overset a sin x");
     }
 
     #[test]
     fn functions() {
-        let res = super::super::parse_unicode("sin x/x").to_string();
+        let res = render("sin x/x");
         assert_eq!(res, "ˢⁱⁿ ˣ⁄ₓ");
     }
 
     #[test]
     fn script() {
-        let res = super::super::parse_unicode("x^sin x").to_string();
+        let res = render("x^sin x");
         assert_eq!(res, "xˢⁱⁿ ˣ");
 
-        let res = super::super::parse_unicode("x^vec(x)").to_string();
+        let res = render("x^vec(x)");
         assert_eq!(res, "x^x\u{20D7}");
 
-        let res = super::super::parse_unicode("x_x^y").to_string();
+        let res = render("x_x^y");
         assert_eq!(res, "xₓʸ");
 
-        let res = super::super::parse_unicode("x_y^sin x").to_string();
+        let res = render("x_y^sin x");
         assert_eq!(res, "x_y^sin x");
 
-        let res = super::super::parse_unicode("x^sin rho").to_string();
+        let res = render("x^sin rho");
         assert_eq!(res, "x^sin ρ");
 
-        let res = super::super::parse_unicode("x_x").to_string();
+        let res = render("x_x");
         assert_eq!(res, "xₓ");
 
-        let res = super::super::parse_unicode("x_y").to_string();
+        let res = render("x_y");
         assert_eq!(res, "x_y");
     }
 
     #[test]
     fn text() {
-        let res = super::super::parse_unicode("\"text\"").to_string();
+        let res = render("\"text\"");
         assert_eq!(res, "text");
     }
 
@@ -1158,6 +1524,13 @@ mod tests {
 
         let res = opts.parse("[ [x, y], [a, b] ]").to_string();
         assert_eq!(res, "[[x,y],[a,b]]");
+
+        let opts = Conf {
+            column_rules: true,
+            ..Conf::default()
+        };
+        let res = opts.parse("[[1,2,|,3],[4,5,|,6]]").to_string();
+        assert_eq!(res, "[[1,2|3],[4,5|6]]");
     }
 
     #[test]
@@ -1179,80 +1552,80 @@ mod tests {
 
     #[test]
     fn empty_input() {
-        assert_eq!(super::super::parse_unicode("").to_string(), "");
+        assert_eq!(render(""), "");
     }
 
     #[test]
     fn gt_symbol() {
-        let res = super::super::parse_unicode("x > y").to_string();
+        let res = render("x > y");
         assert_eq!(res, "x>y");
     }
 
     #[test]
     fn land_lor() {
-        let res = super::super::parse_unicode("x land y").to_string();
+        let res = render("x land y");
         assert_eq!(res, "x∧y");
 
-        let res = super::super::parse_unicode("x lor y").to_string();
+        let res = render("x lor y");
         assert_eq!(res, "x∨y");
     }
 
     #[test]
     fn approx() {
-        let res = super::super::parse_unicode("x approx y").to_string();
+        let res = render("x approx y");
         assert_eq!(res, "x≈y");
     }
 
     #[test]
     #[allow(clippy::unicode_not_nfc)]
     fn unary_modifiers() {
-        let res = super::super::parse_unicode("hat x").to_string();
+        let res = render("hat x");
         assert_eq!(res, "x̂");
 
-        let res = super::super::parse_unicode("tilde x").to_string();
+        let res = render("tilde x");
         assert_eq!(res, "x̃");
 
-        let res = super::super::parse_unicode("bar x").to_string();
+        let res = render("bar x");
         assert_eq!(res, "x̄");
 
-        let res = super::super::parse_unicode("ddot x").to_string();
+        let res = render("ddot x");
         assert_eq!(res, "ẍ");
 
-        let res = super::super::parse_unicode("overarc x").to_string();
+        let res = render("overarc x");
         assert_eq!(res, "x̑");
 
-        let res = super::super::parse_unicode("overparen x").to_string();
+        let res = render("overparen x");
         assert_eq!(res, "x̑");
 
-        let res = super::super::parse_unicode("ul x").to_string();
+        let res = render("ul x");
         assert_eq!(res, "x̲");
 
-        let res = super::super::parse_unicode("underline x").to_string();
+        let res = render("underline x");
         assert_eq!(res, "x̲");
 
-        let res = super::super::parse_unicode("cancel x").to_string();
+        let res = render("cancel x");
         assert_eq!(res, "x\u{0336}");
 
-        let res = super::super::parse_unicode("vec x").to_string();
+        let res = render("vec x");
         assert_eq!(res, "x\u{20D7}");
 
         // non-precomposed: q has no precomposed dot or ddot form
-        let res = super::super::parse_unicode("dot q").to_string();
+        let res = render("dot q");
         assert_eq!(res, "q\u{0307}");
 
-        let res = super::super::parse_unicode("ddot q").to_string();
+        let res = render("ddot q");
         assert_eq!(res, "q\u{0308}");
 
-        let res = super::super::parse_unicode("hat q").to_string();
+        let res = render("hat q");
         assert_eq!(res, "q\u{0302}");
 
-        let res = super::super::parse_unicode("tilde q").to_string();
+        let res = render("tilde q");
         assert_eq!(res, "q\u{0303}");
 
-        let res = super::super::parse_unicode("bar q").to_string();
+        let res = render("bar q");
         assert_eq!(res, "q\u{0304}");
 
-        let res = super::super::parse_unicode("vec q").to_string();
+        let res = render("vec q");
         assert_eq!(res, "q\u{20D7}");
     }
 
@@ -1335,23 +1708,22 @@ mod tests {
 
     #[test]
     fn deeply_nested() {
-        let res = super::super::parse_unicode("((((x))))").to_string();
+        let res = render("((((x))))");
         assert_eq!(res, "((((x))))");
 
-        let res = super::super::parse_unicode("sqrt sqrt sqrt x").to_string();
+        let res = render("sqrt sqrt sqrt x");
         assert_eq!(res, "√√√x");
     }
 
     #[test]
     fn non_ascii_ident() {
         // non-ASCII characters pass through as identifiers
-        let res = super::super::parse_unicode("λ").to_string();
+        let res = render("λ");
         assert_eq!(res, "λ");
     }
 
     #[test]
     fn font_operators() {
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("bb(x)"), "𝐱");
         assert_eq!(render("mathbf(x)"), "𝐱");
         assert_eq!(render("bbb(R)"), "ℝ");
@@ -1376,7 +1748,6 @@ mod tests {
     #[test]
     fn bare_function_has_no_trailing_space() {
         // `f` and `g` are function tokens; with no argument they render as just the name
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("g"), "g");
         assert_eq!(render("f"), "f");
         assert_eq!(render("g^2"), "g²");
@@ -1388,7 +1759,6 @@ mod tests {
     #[test]
     fn explicit_frac_binary_falls_back() {
         // `frac(x)(y)` cannot script-render, but must not error/panic — it falls back to `/`
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("frac(x)(y)"), "(x)/(y)");
         // vulgar/script forms still apply when possible
         assert_eq!(render("frac(1)(2)"), "½");
@@ -1396,7 +1766,6 @@ mod tests {
 
     #[test]
     fn delimiter_functions() {
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("abs(x)"), "|x|");
         assert_eq!(render("ceil(x)"), "⌈x⌉");
         assert_eq!(render("floor(x)"), "⌊x⌋");
@@ -1407,7 +1776,6 @@ mod tests {
 
     #[test]
     fn overline_underline_cancel() {
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("overline(x)"), "x\u{0305}");
         assert_eq!(render("underline(x)"), "x\u{0332}");
         assert_eq!(render("cancel(x)"), "x\u{0336}");
@@ -1416,21 +1784,20 @@ mod tests {
     #[test]
     fn generic_unary_falls_through() {
         // an unrecognized unary op is emitted verbatim followed by its argument.
-        let res = super::super::parse_unicode("obrace x").to_string();
+        let res = render("obrace x");
         assert_eq!(res, "obrace x");
     }
 
     #[test]
     fn script_frac_grouped_numerator() {
         // (x+1)/2 with script fracs: grouped numerator superscripted over subscript
-        let res = super::super::parse_unicode("(x+1)/2").to_string();
+        let res = render("(x+1)/2");
         assert_eq!(res, "ˣ⁺¹⁄₂");
     }
 
     #[test]
     #[allow(clippy::unicode_not_nfc)]
     fn overset_combining_marks() {
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         // each latin-letter argument to overset/stackrel maps to a combining mark
         assert_eq!(render("overset(a)(N)"), "N\u{0363}");
         assert_eq!(render("overset(e)(N)"), "N\u{0364}");
@@ -1449,7 +1816,6 @@ mod tests {
 
     #[test]
     fn roots() {
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("root(2)(x)"), "√(x)");
         assert_eq!(render("root(3)(x)"), "∛(x)");
         assert_eq!(render("root(4)(x)"), "∜(x)");
@@ -1460,14 +1826,13 @@ mod tests {
     #[test]
     fn generic_binary_falls_through() {
         // an unrecognized binary op emits `op first second`, space-separated
-        let res = super::super::parse_unicode("color(red)(x)").to_string();
+        let res = render("color(red)(x)");
         assert_eq!(res, "color (red) (x)");
     }
 
     #[test]
     fn script_frac_grouped_paths() {
         // numerator superscriptable, denominator subscriptable: every grouped branch
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("(x+a)/(x+a)"), "ˣ⁺ᵃ⁄ₓ₊ₐ"); // (group, group)
         assert_eq!(render("x/(x+a)"), "ˣ⁄ₓ₊ₐ"); // (simple, group)
         assert_eq!(render("(x+a)/x"), "ˣ⁺ᵃ⁄ₓ"); // (group, simple)
@@ -1479,7 +1844,6 @@ mod tests {
     #[test]
     fn script_frac_non_subscriptable_fallbacks() {
         // capitals/symbols have no sub/superscript form, so these fall back to plain `/`
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("(A+B)/(C+D)"), "(A+B)/(C+D)");
         assert_eq!(render("x/(A B)"), "x/(AB)");
         assert_eq!(render("(A B)/y"), "(AB)/y");
@@ -1489,7 +1853,6 @@ mod tests {
     #[test]
     #[allow(clippy::unicode_not_nfc)]
     fn char_modifier_group_fallbacks() {
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         // single symbol in a group: no precomposed form, modifier combines anyway
         assert_eq!(render("hat(+)"), "+\u{0302}");
         assert_eq!(render("dot(+)"), "+\u{0307}");
@@ -1501,7 +1864,6 @@ mod tests {
     #[test]
     fn account_of_and_letter_vulgar_fracs() {
         // ast::vulgar_frac_char's non-digit entries
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("a/c"), "℀");
         assert_eq!(render("a/s"), "℁");
         assert_eq!(render("A/S"), "⅍");
@@ -1512,7 +1874,6 @@ mod tests {
 
     #[test]
     fn superscript_uppercase_and_greek() {
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         // every uppercase letter with a superscript form
         assert_eq!(render("x^(DEGHIJKLMNOPRTUVW)"), "x⁽ᴰᴱᴳᴴᴵᴶᴷᴸᴹᴺᴼᴾᴿᵀᵁⱽᵂ⁾");
         // greek letters with a superscript form
@@ -1524,7 +1885,6 @@ mod tests {
 
     #[test]
     fn subscript_greek() {
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("x_beta"), "xᵦ");
         assert_eq!(render("x_gamma"), "xᵧ");
         assert_eq!(render("x_rho"), "xᵨ");
@@ -1536,7 +1896,6 @@ mod tests {
     fn frac_level_arms_with_scripts() {
         // when an operand carries a script the frac uses the `Frac`-level arms,
         // which fall back to plain `/` when the script blocks sub/superscripting
-        let render = |inp: &str| super::super::parse_unicode(inp).to_string();
         assert_eq!(render("1/x^2"), "1/x²"); // one-over reciprocal
         assert_eq!(render("(1)/x^2"), "1/x²"); // grouped one-over
         assert_eq!(render("(x+a)/y^2"), "(x+a)/y²"); // grouped numerator
@@ -1546,7 +1905,7 @@ mod tests {
     #[test]
     fn modifier_on_grouped_single_symbol() {
         // a group whose only element is a symbol is not a precomposable char
-        let res = super::super::parse_unicode("hat((+))").to_string();
+        let res = render("hat((+))");
         assert_eq!(res, "hat ((+))");
     }
 
@@ -1554,14 +1913,14 @@ mod tests {
     #[allow(clippy::unicode_not_nfc)]
     fn modifier_on_bare_symbol() {
         // a bare (ungrouped) symbol argument takes the combining modifier directly
-        let res = super::super::parse_unicode("vec*").to_string();
+        let res = render("vec*");
         assert_eq!(res, "\u{22c5}\u{20d7}");
     }
 
     #[test]
     fn empty_trailing_superscript() {
         // `x^` leaves the script base `Simple::Missing`, which renders to nothing
-        let res = super::super::parse_unicode("x^").to_string();
+        let res = render("x^");
         assert_eq!(res, "x");
     }
 }
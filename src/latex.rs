@@ -0,0 +1,771 @@
+//! Rendering of asciimath as latex source
+
+use asciimath_parser::tree::{
+    Frac, Func, Group, Intermediate, Matrix, Script, ScriptFunc, Simple, SimpleBinary, SimpleFunc,
+    SimpleScript, SimpleUnary,
+};
+use std::fmt;
+use std::fmt::Write;
+
+use super::Conf;
+use super::ansi::Color;
+use super::ast::{extract_raw_text, extract_rows, extract_text, fused_unary, split_word};
+use super::tokens::{left_bracket_str, right_bracket_str, symbol_str};
+
+/// Separates a control word from a following letter, e.g. `\alpha x` instead of `\alphax`
+#[derive(Debug)]
+pub struct Latex<'a, W: ?Sized> {
+    inner: &'a mut W,
+    after_command: bool,
+}
+
+impl<'a, W: Write + ?Sized> Latex<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Latex {
+            inner,
+            after_command: false,
+        }
+    }
+}
+
+impl<W: Write + ?Sized> Write for Latex<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        if self.after_command && s.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            self.inner.write_char(' ')?;
+        }
+        let word = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        self.after_command = word.len() < s.len() && word.ends_with('\\');
+        self.inner.write_str(s)
+    }
+}
+
+/// The latex for a rendered unicode symbol, if it isn't valid latex as is
+#[allow(clippy::too_many_lines)]
+fn latex_symbol(unicode: &str) -> Option<&'static str> {
+    Some(match unicode {
+        // greek
+        "α" => "\\alpha",
+        "β" => "\\beta",
+        "χ" => "\\chi",
+        "δ" => "\\delta",
+        "Δ" => "\\Delta",
+        "ε" => "\\epsilon",
+        "ϵ" => "\\varepsilon",
+        "η" => "\\eta",
+        "γ" => "\\gamma",
+        "Γ" => "\\Gamma",
+        "ι" => "\\iota",
+        "κ" => "\\kappa",
+        "ϰ" => "\\varkappa",
+        "λ" => "\\lambda",
+        "Λ" => "\\Lambda",
+        "μ" => "\\mu",
+        "ν" => "\\nu",
+        "ω" => "\\omega",
+        "Ω" => "\\Omega",
+        "φ" => "\\phi",
+        "ϕ" => "\\varphi",
+        "Φ" => "\\Phi",
+        "π" => "\\pi",
+        "Π" => "\\Pi",
+        "ϖ" => "\\varpi",
+        "ψ" => "\\psi",
+        "Ψ" => "\\Psi",
+        "ρ" => "\\rho",
+        "ϱ" => "\\varrho",
+        "σ" => "\\sigma",
+        "Σ" => "\\Sigma",
+        "τ" => "\\tau",
+        "θ" => "\\theta",
+        "ϑ" => "\\vartheta",
+        "Θ" => "\\Theta",
+        "ϴ" => "\\varTheta",
+        "υ" => "\\upsilon",
+        "Υ" => "\\Upsilon",
+        "ξ" => "\\xi",
+        "Ξ" => "\\Xi",
+        "ζ" => "\\zeta",
+        // greek capitals that are latin letters in latex
+        "Α" => "A",
+        "Β" => "B",
+        "Χ" => "X",
+        "Ε" => "E",
+        "Η" => "H",
+        "Ι" => "I",
+        "Κ" => "K",
+        "Μ" => "M",
+        "Ν" => "N",
+        "Ρ" => "P",
+        "Τ" => "T",
+        "Ζ" => "Z",
+        // operations
+        "⋅" => "\\cdot",
+        "∗" => "\\ast",
+        "⋆" => "\\star",
+        "\\" => "\\setminus",
+        "×" => "\\times",
+        "⋉" => "\\ltimes",
+        "⋊" => "\\rtimes",
+        "⋈" => "\\bowtie",
+        "÷" => "\\div",
+        "∘" => "\\circ",
+        "⊕" => "\\oplus",
+        "⊗" => "\\otimes",
+        "⊙" => "\\odot",
+        "∑" => "\\sum",
+        "∏" => "\\prod",
+        "∧" => "\\wedge",
+        "⋀" => "\\bigwedge",
+        "∨" => "\\vee",
+        "⋁" => "\\bigvee",
+        "∩" => "\\cap",
+        "⋂" => "\\bigcap",
+        "∪" => "\\cup",
+        "⋃" => "\\bigcup",
+        "⊖" => "\\ominus",
+        "⊘" => "\\oslash",
+        // relations
+        "≠" => "\\ne",
+        "≤" => "\\le",
+        "≪" => "\\ll",
+        "≥" => "\\ge",
+        "≫" => "\\gg",
+        "≺" => "\\prec",
+        "≻" => "\\succ",
+        "⪯" => "\\preceq",
+        "⪰" => "\\succeq",
+        "∈" => "\\in",
+        "∉" => "\\notin",
+        "⊂" => "\\subset",
+        "⊃" => "\\supset",
+        "⊆" => "\\subseteq",
+        "⊇" => "\\supseteq",
+        "⊄" => "\\not\\subset",
+        "⊅" => "\\not\\supset",
+        "⊈" => "\\nsubseteq",
+        "⊉" => "\\nsupseteq",
+        "≡" => "\\equiv",
+        "≅" => "\\cong",
+        "≈" => "\\approx",
+        "~" => "\\sim",
+        "∝" => "\\propto",
+        // logical
+        "¬" => "\\neg",
+        "⇒" => "\\Rightarrow",
+        "⇔" => "\\Leftrightarrow",
+        "∀" => "\\forall",
+        "∃" => "\\exists",
+        "∄" => "\\nexists",
+        "⊥" => "\\bot",
+        "⊤" => "\\top",
+        "⊢" => "\\vdash",
+        "⊨" => "\\models",
+        " and " => "\\text{ and }",
+        " or " => "\\text{ or }",
+        " if " => "\\text{ if }",
+        // misc
+        "∫" => "\\int",
+        "∮" => "\\oint",
+        "∬" => "\\iint",
+        "∭" => "\\iiint",
+        "∯" => "\\oiint",
+        "∰" => "\\oiiint",
+        "∂" => "\\partial",
+        "∇" => "\\nabla",
+        "±" => "\\pm",
+        "∓" => "\\mp",
+        "∅" => "\\emptyset",
+        "∞" => "\\infty",
+        "ℵ" => "\\aleph",
+        "…" => "\\ldots",
+        "∴" => "\\therefore",
+        "∵" => "\\because",
+        "∠" => "\\angle",
+        "△" => "\\triangle",
+        " " => "\\ ",
+        "⌢" => "\\frown",
+        "⋯" => "\\cdots",
+        "⋮" => "\\vdots",
+        "⋱" => "\\ddots",
+        "⋄" => "\\diamond",
+        "□" => "\\square",
+        "ℂ" => "\\mathbb{C}",
+        "ℕ" => "\\mathbb{N}",
+        "ℚ" => "\\mathbb{Q}",
+        "ℝ" => "\\mathbb{R}",
+        "ℤ" => "\\mathbb{Z}",
+        "ℓ" => "\\ell",
+        "ℏ" => "\\hbar",
+        // arrows
+        "↑" => "\\uparrow",
+        "⇑" => "\\Uparrow",
+        "↓" => "\\downarrow",
+        "⇓" => "\\Downarrow",
+        "→" => "\\to",
+        "↣" => "\\rightarrowtail",
+        "↠" => "\\twoheadrightarrow",
+        "↦" => "\\mapsto",
+        "←" => "\\leftarrow",
+        "↔" => "\\leftrightarrow",
+        "⇐" => "\\Leftarrow",
+        // raw tokens
+        "^" => "\\hat{}",
+        "_" => "\\_",
+        _ => return None,
+    })
+}
+
+/// The latex for a function or underover identifier
+fn latex_operator(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "sin" => "\\sin",
+        "cos" => "\\cos",
+        "tan" => "\\tan",
+        "sinh" => "\\sinh",
+        "cosh" => "\\cosh",
+        "tanh" => "\\tanh",
+        "cot" => "\\cot",
+        "sec" => "\\sec",
+        "csc" => "\\csc",
+        "arcsin" => "\\arcsin",
+        "arccos" => "\\arccos",
+        "arctan" => "\\arctan",
+        "coth" => "\\coth",
+        "exp" => "\\exp",
+        "log" => "\\log",
+        "ln" => "\\ln",
+        "det" => "\\det",
+        "gcd" => "\\gcd",
+        "lim" => "\\lim",
+        "dim" => "\\dim",
+        "mod" => "\\bmod",
+        "min" => "\\min",
+        "max" => "\\max",
+        _ => return None,
+    })
+}
+
+fn latex_bracket(bracket: &str) -> &str {
+    match bracket {
+        "" => ".",
+        "{" => "\\{",
+        "}" => "\\}",
+        "⟨" => "\\langle",
+        "⟩" => "\\rangle",
+        "⌊" => "\\lfloor",
+        "⌋" => "\\rfloor",
+        "⌈" => "\\lceil",
+        "⌉" => "\\rceil",
+        bracket => bracket,
+    }
+}
+
+/// Escape an identifier in math mode
+fn escaped(ident: &str, out: &mut impl Write) -> fmt::Result {
+    for chr in ident.chars() {
+        match chr {
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                out.write_char('\\')?;
+                out.write_char(chr)?;
+            }
+            '\\' => out.write_str("\\backslash")?,
+            chr => out.write_char(chr)?,
+        }
+    }
+    Ok(())
+}
+
+/// Escape the argument of a `\text` in text mode, where math commands like `\backslash` aren't
+/// allowed
+fn escaped_text(text: &str, out: &mut impl Write) -> fmt::Result {
+    for chr in text.chars() {
+        match chr {
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                out.write_char('\\')?;
+                out.write_char(chr)?;
+            }
+            '\\' => out.write_str("\\textbackslash{}")?,
+            '~' => out.write_str("\\textasciitilde{}")?,
+            '^' => out.write_str("\\textasciicircum{}")?,
+            chr => out.write_char(chr)?,
+        }
+    }
+    Ok(())
+}
+
+impl Conf {
+    /// Render a command argument, stripping brackets from a group
+    fn latex_arg(self, simple: &Simple<'_>, out: &mut impl Write) -> fmt::Result {
+        out.write_char('{')?;
+        match simple {
+            Simple::Group(Group { expr, .. }) if self.strip_brackets => {
+                self.latex_expression(expr, out)?;
+            }
+            simple => self.latex_simple(simple, out)?,
+        }
+        out.write_char('}')
+    }
+
+    fn latex_command(self, cmd: &str, arg: &Simple<'_>, out: &mut impl Write) -> fmt::Result {
+        out.write_str(cmd)?;
+        self.latex_arg(arg, out)
+    }
+
+    fn latex_delimited(
+        self,
+        open: &str,
+        arg: &Simple<'_>,
+        close: &str,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        write!(out, "\\left{open}")?;
+        match arg {
+            Simple::Group(Group { expr, .. }) if self.strip_brackets => {
+                self.latex_expression(expr, out)?;
+            }
+            simple => self.latex_simple(simple, out)?,
+        }
+        write!(out, "\\right{close}")
+    }
+
+    fn latex_simpleunary(self, unary: &SimpleUnary<'_>, out: &mut impl Write) -> fmt::Result {
+        match (unary.op, unary.arg()) {
+            ("sqrt", arg) => self.latex_command("\\sqrt", arg, out),
+            // fonts
+            ("bb" | "mathbf", arg) => self.latex_command("\\mathbf", arg, out),
+            ("bbb" | "mathbb", arg) => self.latex_command("\\mathbb", arg, out),
+            ("cc" | "mathcal", arg) => self.latex_command("\\mathcal", arg, out),
+            ("tt" | "mathtt", arg) => self.latex_command("\\mathtt", arg, out),
+            ("fr" | "mathfrak", arg) => self.latex_command("\\mathfrak", arg, out),
+            ("sf" | "mathsf", arg) => self.latex_command("\\mathsf", arg, out),
+            ("it" | "mathit", arg) => self.latex_command("\\mathit", arg, out),
+            // functions
+            ("abs" | "Abs", arg) => self.latex_delimited("|", arg, "|", out),
+            ("ceil", arg) => self.latex_delimited("\\lceil", arg, "\\rceil", out),
+            ("floor", arg) => self.latex_delimited("\\lfloor", arg, "\\rfloor", out),
+            ("norm", arg) => self.latex_delimited("\\lVert", arg, "\\rVert", out),
+            ("text" | "mbox", arg) => {
+                out.write_str("\\text{")?;
+                escaped_text(&extract_text(arg), out)?;
+                out.write_char('}')
+            }
+            // modifiers
+            ("overline", arg) => self.latex_command("\\overline", arg, out),
+            ("underline" | "ul", arg) => self.latex_command("\\underline", arg, out),
+            ("cancel", arg) => self.latex_command("\\cancel", arg, out),
+            ("hat", arg) => self.latex_command("\\hat", arg, out),
+            ("tilde", arg) => self.latex_command("\\tilde", arg, out),
+            ("bar", arg) => self.latex_command("\\bar", arg, out),
+            ("dot", arg) => self.latex_command("\\dot", arg, out),
+            ("ddot", arg) => self.latex_command("\\ddot", arg, out),
+            ("vec", arg) => self.latex_command("\\vec", arg, out),
+            ("overarc" | "overparen", arg) => self.latex_command("\\overparen", arg, out),
+            ("ubrace" | "underbrace", arg) => self.latex_command("\\underbrace", arg, out),
+            ("obrace" | "overbrace", arg) => self.latex_command("\\overbrace", arg, out),
+            // generic
+            (op, arg) => {
+                write!(out, "\\operatorname{{{op}}}")?;
+                self.latex_simple(arg, out)
+            }
+        }
+    }
+
+    fn latex_simplebinary(self, binary: &SimpleBinary<'_>, out: &mut impl Write) -> fmt::Result {
+        match (binary.op, binary.first(), binary.second()) {
+            ("frac", numer, denom) => {
+                out.write_str("\\frac")?;
+                self.latex_arg(numer, out)?;
+                self.latex_arg(denom, out)
+            }
+            ("root", index, arg) => {
+                out.write_str("\\sqrt[")?;
+                match index {
+                    Simple::Group(Group { expr, .. }) if self.strip_brackets => {
+                        self.latex_expression(expr, out)?;
+                    }
+                    index => self.latex_simple(index, out)?,
+                }
+                out.write_char(']')?;
+                self.latex_arg(arg, out)
+            }
+            (op @ ("stackrel" | "overset" | "underset"), first, second) => {
+                write!(out, "\\{op}")?;
+                self.latex_arg(first, out)?;
+                self.latex_arg(second, out)
+            }
+            ("color", color, arg) => {
+                let color = extract_raw_text(color);
+                match Color::parse(&color) {
+                    // xcolor only reads hex digits in the html model
+                    Some(Color::Rgb(r, g, b)) if color.starts_with('#') => {
+                        write!(out, "\\textcolor[HTML]{{{r:02X}{g:02X}{b:02X}}}")?;
+                    }
+                    _ => {
+                        out.write_str("\\textcolor{")?;
+                        escaped(&color, out)?;
+                        out.write_char('}')?;
+                    }
+                }
+                self.latex_arg(arg, out)
+            }
+            // latex has no element ids or classes, so only the content remains
+            ("id" | "class", _, arg) => self.latex_arg(arg, out),
+            // generic
+            (op, first, second) => {
+                write!(out, "\\operatorname{{{op}}}")?;
+                self.latex_simple(first, out)?;
+                self.latex_simple(second, out)
+            }
+        }
+    }
+
+    fn latex_name(name: &str, out: &mut impl Write) -> fmt::Result {
+        match latex_operator(name) {
+            Some(op) => out.write_str(op),
+            None if name.chars().count() > 1 => write!(out, "\\operatorname{{{name}}}"),
+            None => out.write_str(name),
+        }
+    }
+
+    fn latex_simplefunc(self, func: &SimpleFunc<'_>, out: &mut impl Write) -> fmt::Result {
+        Self::latex_name(func.func, out)?;
+        self.latex_simple(func.arg(), out)
+    }
+
+    fn latex_group(self, group: &Group<'_>, out: &mut impl Write) -> fmt::Result {
+        let left = latex_bracket(left_bracket_str(group.left_bracket));
+        let right = latex_bracket(right_bracket_str(group.right_bracket));
+        if left == "." && right == "." {
+            self.latex_expression(&group.expr, out)
+        } else if let ("\\{", ".") = (left, right)
+            && let Some(rows) = extract_rows(&group.expr)
+            && rows[0].len() > 1
+        {
+            self.latex_cases(&rows, out)
+        } else {
+            write!(out, "\\left{left}")?;
+            self.latex_expression(&group.expr, out)?;
+            write!(out, "\\right{right}")
+        }
+    }
+
+    /// Piecewise cases like `{(x, x > 0), (-x, x <= 0):}`
+    fn latex_cases(self, rows: &[Vec<&[Intermediate<'_>]>], out: &mut impl Write) -> fmt::Result {
+        out.write_str("\\begin{cases}")?;
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                out.write_str(" \\\\ ")?;
+            }
+            for (j, cell) in row.iter().enumerate() {
+                if j > 0 {
+                    out.write_str(" & ")?;
+                    self.latex_condition(cell, out)?;
+                } else {
+                    self.latex_expression(cell, out)?;
+                }
+            }
+        }
+        out.write_str("\\end{cases}")
+    }
+
+    /// A condition of a case, with words like `otherwise` written as text rather than letters
+    fn latex_condition(self, cond: &[Intermediate<'_>], out: &mut impl Write) -> fmt::Result {
+        let mut start = 0;
+        let mut ind = 0;
+        while ind < cond.len() {
+            if let Some((word, rest)) = split_word(&cond[ind..]) {
+                self.latex_expression(&cond[start..ind], out)?;
+                write!(out, "\\text{{{word}}}")?;
+                ind = cond.len() - rest.len();
+                start = ind;
+            } else {
+                ind += 1;
+            }
+        }
+        self.latex_expression(&cond[start..], out)
+    }
+
+    fn latex_matrix(self, matrix: &Matrix<'_>, out: &mut impl Write) -> fmt::Result {
+        let left = left_bracket_str(matrix.left_bracket);
+        let right = right_bracket_str(matrix.right_bracket);
+        let env = match (left, right) {
+            ("(", ")") => Some("pmatrix"),
+            ("[", "]") => Some("bmatrix"),
+            ("{", "}") => Some("Bmatrix"),
+            ("|", "|") => Some("vmatrix"),
+            ("", "") => Some("matrix"),
+            _ => None,
+        };
+        if env.is_none() {
+            write!(out, "\\left{}", latex_bracket(left))?;
+        }
+        write!(out, "\\begin{{{}}}", env.unwrap_or("matrix"))?;
+        for (i, row) in matrix.rows().enumerate() {
+            if i > 0 {
+                out.write_str(" \\\\ ")?;
+            }
+            for (j, expr) in row.iter().enumerate() {
+                if j > 0 {
+                    out.write_str(" & ")?;
+                }
+                self.latex_expression(expr, out)?;
+            }
+        }
+        write!(out, "\\end{{{}}}", env.unwrap_or("matrix"))?;
+        if env.is_none() {
+            write!(out, "\\right{}", latex_bracket(right))?;
+        }
+        Ok(())
+    }
+
+    fn latex_simple(self, simple: &Simple<'_>, out: &mut impl Write) -> fmt::Result {
+        match simple {
+            Simple::Missing => Ok(()),
+            &Simple::Number(num) => out.write_str(num),
+            &Simple::Text(text) => {
+                out.write_str("\\text{")?;
+                escaped_text(text, out)?;
+                out.write_char('}')
+            }
+            &Simple::Ident(ident) => match latex_operator(ident) {
+                Some(op) => out.write_str(op),
+                None => escaped(ident, out),
+            },
+            &Simple::Symbol(symbol) => match symbol {
+                "quad" => out.write_str("\\quad"),
+                "qquad" => out.write_str("\\qquad"),
+                symbol => {
+                    let unicode = symbol_str(symbol, self.skin_tone);
+                    out.write_str(latex_symbol(unicode).unwrap_or(unicode))
+                }
+            },
+            Simple::Func(func) => self.latex_simplefunc(func, out),
+            Simple::Unary(unary) => self.latex_simpleunary(unary, out),
+            Simple::Binary(binary) => self.latex_simplebinary(binary, out),
+            Simple::Group(group) => self.latex_group(group, out),
+            Simple::Matrix(matrix) => self.latex_matrix(matrix, out),
+        }
+    }
+
+    fn latex_script(self, script: &Script<'_>, out: &mut impl Write) -> fmt::Result {
+        match script {
+            Script::None => Ok(()),
+            Script::Sub(sub) => {
+                out.write_char('_')?;
+                self.latex_arg(sub, out)
+            }
+            Script::Super(sup) => {
+                out.write_char('^')?;
+                self.latex_arg(sup, out)
+            }
+            Script::Subsuper(sub, sup) => {
+                out.write_char('_')?;
+                self.latex_arg(sub, out)?;
+                out.write_char('^')?;
+                self.latex_arg(sup, out)
+            }
+        }
+    }
+
+    fn latex_func(self, func: &Func<'_>, out: &mut impl Write) -> fmt::Result {
+        Self::latex_name(func.func, out)?;
+        self.latex_script(&func.script, out)?;
+        self.latex_scriptfunc(func.arg(), out)
+    }
+
+    fn latex_scriptfunc(self, func: &ScriptFunc<'_>, out: &mut impl Write) -> fmt::Result {
+        match func {
+            ScriptFunc::Simple(SimpleScript { simple, script }) => {
+                // a script on nothing still needs a base
+                if matches!(simple, Simple::Missing) && !matches!(script, Script::None) {
+                    out.write_str("{}")?;
+                }
+                self.latex_simple(simple, out)?;
+                self.latex_script(script, out)
+            }
+            ScriptFunc::Func(func) => self.latex_func(func, out),
+        }
+    }
+
+    /// A fraction operand, stripping brackets from an unscripted group
+    fn latex_frac_arg(self, func: &ScriptFunc<'_>, out: &mut impl Write) -> fmt::Result {
+        match func {
+            ScriptFunc::Simple(SimpleScript {
+                simple,
+                script: Script::None,
+            }) => self.latex_arg(simple, out),
+            func => {
+                out.write_char('{')?;
+                self.latex_scriptfunc(func, out)?;
+                out.write_char('}')
+            }
+        }
+    }
+
+    fn latex_frac(self, frac: &Frac<'_>, out: &mut impl Write) -> fmt::Result {
+        out.write_str("\\frac")?;
+        self.latex_frac_arg(&frac.numer, out)?;
+        self.latex_frac_arg(&frac.denom, out)
+    }
+
+    pub(crate) fn latex_expression(
+        self,
+        expr: &[Intermediate<'_>],
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        let mut rest = expr;
+        while let Some((inter, tail)) = rest.split_first() {
+            // `hbar` is tokenized as `h` directly followed by `bar`
+            if let Some(("h", bar, script)) = fused_unary(rest)
+                && bar.op == "bar"
+            {
+                out.write_str("\\hbar")?;
+                self.latex_simple(bar.arg(), out)?;
+                self.latex_script(script, out)?;
+                rest = &rest[2..];
+                continue;
+            }
+            rest = tail;
+            match inter {
+                Intermediate::ScriptFunc(func) => self.latex_scriptfunc(func, out)?,
+                Intermediate::Frac(frac) => self.latex_frac(frac, out)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Conf;
+
+    fn render(inp: &str) -> String {
+        Conf::default().parse(inp).to_latex()
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(render("x + y"), "x+y");
+        assert_eq!(render("alpha beta"), "\\alpha\\beta");
+        assert_eq!(render("alpha x"), "\\alpha x");
+        assert_eq!(render("a != b"), "a\\ne b");
+        assert_eq!(render("x in RR"), "x\\in\\mathbb{R}");
+        assert_eq!(render("a -> b"), "a\\to b");
+        assert_eq!(render("x quad y"), "x\\quad y");
+        assert_eq!(render("Alpha"), "A");
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(render("1/2"), "\\frac{1}{2}");
+        assert_eq!(render("(x+1)/y"), "\\frac{x+1}{y}");
+        assert_eq!(render("x^2/y"), "\\frac{x^{2}}{y}");
+        assert_eq!(render("frac(a)(b)"), "\\frac{a}{b}");
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(render("sqrt x"), "\\sqrt{x}");
+        assert_eq!(render("root(3)(x)"), "\\sqrt[3]{x}");
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(render("x^2"), "x^{2}");
+        assert_eq!(render("x_(i+1)"), "x_{i+1}");
+        assert_eq!(render("sum_(i=1)^n i"), "\\sum_{i=1}^{n}i");
+        assert_eq!(render("lim_(x->0)"), "\\lim_{x\\to0}");
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(render("sin x"), "\\sin x");
+        assert_eq!(render("sin^2 x"), "\\sin^{2}x");
+        assert_eq!(render("f(x)"), "f\\left(x\\right)");
+        assert_eq!(render("sech x"), "\\operatorname{sech}x");
+    }
+
+    #[test]
+    fn fonts_and_accents() {
+        assert_eq!(render("bbb R"), "\\mathbb{R}");
+        assert_eq!(render("bb(x)"), "\\mathbf{x}");
+        assert_eq!(render("vec x"), "\\vec{x}");
+        assert_eq!(render("ubrace(a+b)"), "\\underbrace{a+b}");
+        assert_eq!(render("text(if x)"), "\\text{if x}");
+        assert_eq!(render("text(a_b  c)"), "\\text{a\\_b c}");
+        assert_eq!(render("\"a b\""), "\\text{a b}");
+        assert_eq!(render("abs(x)"), "\\left|x\\right|");
+    }
+
+    #[test]
+    fn binaries() {
+        assert_eq!(render("overset(def)(=)"), "\\overset{def}{=}");
+        assert_eq!(render("color(red)(x)"), "\\textcolor{red}{x}");
+        assert_eq!(
+            render("color(#ff0000)(x) color(#0a0)(y)"),
+            "\\textcolor[HTML]{FF0000}{x}\\textcolor[HTML]{00AA00}{y}"
+        );
+        assert_eq!(render("class(big)(x)"), "{x}");
+    }
+
+    #[test]
+    fn brackets_and_matrices() {
+        assert_eq!(render("{x}"), "\\left\\{x\\right\\}");
+        assert_eq!(render("(:x:)"), "\\left\\langle x\\right\\rangle");
+        assert_eq!(
+            render("[[a,b],[c,d]]"),
+            "\\begin{bmatrix}a & b \\\\ c & d\\end{bmatrix}"
+        );
+        assert_eq!(
+            render("((1, x), (2, y))"),
+            "\\begin{pmatrix}1 & x \\\\ 2 & y\\end{pmatrix}"
+        );
+    }
+
+    #[test]
+    fn cases() {
+        assert_eq!(
+            render("{(x, x > 0), (-x, x <= 0):}"),
+            "\\begin{cases}x & x>0 \\\\ -x & x\\le0\\end{cases}"
+        );
+        assert_eq!(
+            render("{(1, \"if \" x>0), (0, \"otherwise\"):}"),
+            "\\begin{cases}1 & \\text{if }x>0 \\\\ 0 & \\text{otherwise}\\end{cases}"
+        );
+        assert_eq!(
+            render("{(x, if x > 0), (-x, otherwise):}"),
+            "\\begin{cases}x & \\text{ if }x>0 \\\\ -x & \\text{otherwise}\\end{cases}"
+        );
+        assert_eq!(
+            render("{(0, x in A), (1, else):}"),
+            "\\begin{cases}0 & x\\in A \\\\ 1 & \\text{else}\\end{cases}"
+        );
+    }
+
+    #[test]
+    fn hbar() {
+        assert_eq!(render("hbar"), "\\hbar");
+        assert_eq!(render("hbar omega"), "\\hbar\\omega");
+        assert_eq!(render("h bar x"), "h\\bar{x}");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(render("\"50% & #1\""), "\\text{50\\% \\& \\#1}");
+        // text mode has its own escapes for the characters that are commands in math
+        assert_eq!(
+            render("text(#$%&_{}\\~^)"),
+            concat!(
+                "\\text{\\#\\$\\%\\&\\_\\{\\}",
+                "\\textbackslash{}\\textasciitilde{}\\textasciicircum{}}",
+            )
+        );
+        assert_eq!(
+            render("\"50% \\ ~ ^\""),
+            "\\text{50\\% \\textbackslash{} \\textasciitilde{} \\textasciicircum{}}"
+        );
+    }
+}
@@ -41,18 +41,45 @@
 #![forbid(unsafe_code)]
 #![warn(clippy::pedantic, missing_docs)]
 
+mod ansi;
+mod ascii;
 mod ast;
 mod block;
+mod diagnostics;
+mod document;
+mod explain;
+mod from_unicode;
+mod html;
 mod inline;
+mod latex;
+mod mathml;
+mod renderer;
+#[cfg(feature = "serde")]
+mod serialize;
+mod speech;
+mod svg;
 mod tokens;
+mod typst;
 
 use asciimath_parser::tree::Expression;
+pub use block::{BlockStyle, MatrixAlign};
+pub use diagnostics::{Report, Warning, WarningKind};
+pub use document::Document;
 pub use emojis::SkinTone;
-use inline::Mapper;
+pub use from_unicode::from_unicode;
+pub use renderer::{Renderer, SymbolKind};
 use std::fmt;
 
 /// Configuration for unicode rendering of asciimath
+///
+/// With the `serde` feature this can be serialized, and missing fields deserialize to their
+/// defaults.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct Conf {
     /// If true, this will strip unnecessary parenthesis in some contexts
@@ -62,9 +89,30 @@ pub struct Conf {
     /// If true, this will try to render fractions using super- and sub-scripts
     pub script_fracs: bool,
     /// Default skin tone for emojis
+    #[cfg_attr(feature = "serde", serde(with = "serialize::skin_tone"))]
     pub skin_tone: SkinTone,
     /// If true, render as multi-line 2D block (stacked fractions, vertical scripts, matrix grids)
     pub block: bool,
+    /// If true, draw `sum` and `int` as multi-line glyphs in block mode
+    pub tall_operators: bool,
+    /// If true, render `color` with ANSI escape sequences for terminals
+    pub ansi_color: bool,
+    /// If true, color numbers, identifiers, and operators with ANSI escape sequences
+    pub highlight: bool,
+    /// If set, break long expressions before top level relations and additive operators so lines
+    /// fit within this many columns
+    pub max_width: Option<usize>,
+    /// If true, render pure ascii, using asciimath names for symbols and ascii block drawing
+    pub ascii: bool,
+    /// The glyphs used to draw brackets, fraction bars, radicals, and braces in block mode
+    pub block_style: BlockStyle,
+    /// How matrix cells line up within their column in block mode
+    pub matrix_align: MatrixAlign,
+    /// If true, draw matrix columns holding only `|` as rules, e.g. the augmented matrix
+    /// `[[1,2,|,3],[4,5,|,6]]`
+    pub column_rules: bool,
+    /// If true, separate matrix rows with horizontal rules in block mode
+    pub row_rules: bool,
 }
 
 impl Default for Conf {
@@ -75,6 +123,15 @@ impl Default for Conf {
             script_fracs: true,
             skin_tone: SkinTone::Default,
             block: false,
+            tall_operators: false,
+            ansi_color: false,
+            highlight: false,
+            max_width: None,
+            ascii: false,
+            block_style: BlockStyle::Unicode,
+            matrix_align: MatrixAlign::Center,
+            column_rules: false,
+            row_rules: false,
         }
     }
 }
@@ -88,6 +145,46 @@ impl Conf {
             expr: tokens::parse(inp),
         }
     }
+
+    /// Render several asciimath strings as block rows aligned on their first relation
+    ///
+    /// Left sides are right aligned before the relation, so a derivation can continue with rows
+    /// that start with the relation, and a row without a relation is a left side on its own.
+    /// This always uses the block layout, even if [`Conf::block`] is false. With
+    /// [`Conf::max_width`], right sides break to fit beside the column of relations, but left
+    /// sides are never broken.
+    ///
+    /// ```
+    /// let res = asciimath_unicode::Conf::default().render_aligned(["(a+b)^2", "= a^2 + 2ab + b^2"]);
+    /// assert_eq!(res, "(a + b)²\n         = a² + 2ab + b²");
+    /// ```
+    #[must_use]
+    pub fn render_aligned<'a>(self, formulas: impl IntoIterator<Item = &'a str>) -> String {
+        let exprs: Vec<_> = formulas.into_iter().map(tokens::parse).collect();
+        let rows: Vec<&[_]> = exprs.iter().map(|expr| &**expr).collect();
+        self.block_render_aligned(&rows).to_string()
+    }
+
+    /// Render an asciimath string, reporting every construct that was rendered in a degraded form
+    ///
+    /// Warnings cover operators written out by name, scripts written with `_` or `^`, and
+    /// fractions written with `/` even though `script_fracs` was enabled. Warnings are recorded
+    /// while rendering, so ascii output also reports the scripts and fractions it converts. In
+    /// block mode fractions stack and scripts move vertically instead, so only operators are
+    /// reported.
+    ///
+    /// ```
+    /// use asciimath_unicode::{Conf, WarningKind};
+    /// let report = Conf::default().render_with_report("x_y");
+    /// assert_eq!(report.output, "x_y");
+    /// assert_eq!(report.warnings[0].kind, WarningKind::UnscriptableScript);
+    /// assert_eq!(report.warnings[0].span, Some(2..3));
+    /// ```
+    #[must_use]
+    pub fn render_with_report(self, inp: &str) -> Report {
+        let parsed = self.parse(inp);
+        diagnostics::record(inp, || parsed.to_string())
+    }
 }
 
 /// Parsed asciimath expression ready for rendering
@@ -101,13 +198,164 @@ pub struct Asciimath<'a> {
     pub expr: Expression<'a>,
 }
 
+impl Asciimath<'_> {
+    /// Render as mathml presentation markup
+    ///
+    /// This uses the same symbols and font mappings as the unicode rendering, but renders
+    /// fractions, roots, scripts, and matrices as their mathml elements.
+    ///
+    /// ```
+    /// let res = asciimath_unicode::parse_unicode("x^2").to_mathml();
+    /// assert_eq!(res, "<math><msup><mi>x</mi><mn>2</mn></msup></math>");
+    /// ```
+    #[must_use]
+    pub fn to_mathml(&self) -> String {
+        let mut out = String::new();
+        self.conf
+            .mathml(&self.expr, &mut out)
+            .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+        out
+    }
+
+    /// Render the parsed asciimath as latex math source
+    ///
+    /// The output is meant to be placed inside a math environment, so no `$` delimiters are
+    /// added. Symbols without a latex command are emitted as their unicode rendering.
+    ///
+    /// ```
+    /// let res = asciimath_unicode::parse_unicode("sum_(i=1)^n x_i/2").to_latex();
+    /// assert_eq!(res, "\\sum_{i=1}^{n}\\frac{x_{i}}{2}");
+    /// ```
+    #[must_use]
+    pub fn to_latex(&self) -> String {
+        let mut out = String::new();
+        self.conf
+            .latex_expression(&self.expr, &mut latex::Latex::new(&mut out))
+            .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+        out
+    }
+
+    /// Render the parsed asciimath as typst math source
+    ///
+    /// The output is meant to be placed inside `$` delimiters. Symbols use their typst names
+    /// where typst has a common one, and their unicode rendering otherwise.
+    ///
+    /// ```
+    /// let res = asciimath_unicode::parse_unicode("sum_(i=1)^n x_i/2").to_typst();
+    /// assert_eq!(res, "sum_(i=1)^n frac(x_i, 2)");
+    /// ```
+    #[must_use]
+    pub fn to_typst(&self) -> String {
+        let mut out = String::new();
+        self.conf
+            .typst_expression(&self.expr, &mut typst::Typst::new(&mut out))
+            .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+        out
+    }
+
+    /// Render as html, with a span around each node of the expression
+    ///
+    /// The text content is the same unicode the inline renderer produces, so the spans only add
+    /// structure for styling. Spans have classes like `am-frac`, `am-sup`, `am-sub`,
+    /// `am-ident`, `am-num`, and `am-op`, and `id` and `class` become attributes of their span.
+    ///
+    /// ```
+    /// let res = asciimath_unicode::parse_unicode("class(hl)(x^2)").to_html();
+    /// assert_eq!(
+    ///     res,
+    ///     concat!(
+    ///         r#"<span class="am-math"><span class="hl"><span class="am-script">"#,
+    ///         r#"<span class="am-ident">x</span><span class="am-sup"><span class="am-num">²</span>"#,
+    ///         r#"</span></span></span></span>"#,
+    ///     )
+    /// );
+    /// ```
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        self.conf
+            .html(&self.expr, &mut out)
+            .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+        out
+    }
+
+    /// Render the block layout as an svg image
+    ///
+    /// The lines of the block are placed as text in a monospace font, with fraction bars,
+    /// radicals, and rules drawn as lines. This always uses the block layout, even if
+    /// [`Conf::block`] is false, and the output only depends on the input and configuration.
+    /// The terminal options [`Conf::ascii`], [`Conf::highlight`], and [`Conf::ansi_color`] are
+    /// ignored.
+    ///
+    /// ```
+    /// let res = asciimath_unicode::parse_unicode("(a+b)/c").to_svg();
+    /// assert!(res.starts_with("<svg "));
+    /// assert!(res.contains(r#"<line x1="0" y1="36" x2="60" y2="36"/>"#));
+    /// ```
+    #[must_use]
+    pub fn to_svg(&self) -> String {
+        let mut out = String::new();
+        self.conf
+            .svg(&self.expr, &mut out)
+            .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+        out
+    }
+
+    /// Render as text for reading aloud, for screen readers and other speech output
+    ///
+    /// Symbols are spoken by name, and structure is spoken in words, like "x squared" or "the sum
+    /// from i equals 1 to n of". Parts that span several words end with a marker like "end
+    /// fraction" so they can't be misheard.
+    ///
+    /// ```
+    /// let res = asciimath_unicode::parse_unicode("vec x + 1/7").to_speech();
+    /// assert_eq!(res, "x vector plus 1 over 7");
+    /// ```
+    #[must_use]
+    pub fn to_speech(&self) -> String {
+        let mut out = String::new();
+        self.conf
+            .speech_expression(&self.expr, &mut speech::Speech::new(&mut out))
+            .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+        out
+    }
+}
+
+impl Asciimath<'_> {
+    /// Describe the parsed tree and how each node is rendered
+    ///
+    /// Each node is written on its own line, indented under its parent. Fractions and scripts
+    /// note the strategy that was chosen, like a vulgar fraction or a stacked fraction, followed
+    /// by the alternatives that were tried first and why they didn't apply.
+    ///
+    /// ```
+    /// let res = asciimath_unicode::parse_unicode("x/2").explain();
+    /// assert_eq!(
+    ///     res,
+    ///     "\
+    /// fraction => script fraction
+    ///   - not vulgar fraction: no vulgar fraction has these terms
+    ///   numerator: identifier x
+    ///   denominator: number 2
+    /// "
+    /// );
+    /// ```
+    #[must_use]
+    pub fn explain(&self) -> String {
+        let mut out = String::new();
+        self.conf
+            .explain(&self.expr, &mut out)
+            .unwrap_or_else(|_| unreachable!("write to String is infallible"));
+        out
+    }
+}
+
 impl fmt::Display for Asciimath<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.conf.block {
-            let block = self.conf.block_expression(&self.expr);
-            write!(f, "{block}")
+            write!(f, "{}", self.conf.block_render(&self.expr))
         } else {
-            self.conf.inline_expression(&self.expr, &mut Mapper::new(f))
+            self.conf.inline_render(&self.expr, f)
         }
     }
 }
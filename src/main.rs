@@ -1,7 +1,10 @@
-use asciimath_unicode::{Conf, SkinTone};
+use asciimath_unicode::{BlockStyle, Conf, Document, MatrixAlign, SkinTone};
 use clap::{Parser, ValueEnum};
-use std::io;
-use std::io::{Read, Write};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs, io, process};
 
 #[derive(Debug, Clone, ValueEnum)]
 enum Tone {
@@ -11,6 +14,26 @@ enum Tone {
     Medium,
     MediumDark,
     Dark,
+    LightAndMediumLight,
+    LightAndMedium,
+    LightAndMediumDark,
+    LightAndDark,
+    MediumLightAndLight,
+    MediumLightAndMedium,
+    MediumLightAndMediumDark,
+    MediumLightAndDark,
+    MediumAndLight,
+    MediumAndMediumLight,
+    MediumAndMediumDark,
+    MediumAndDark,
+    MediumDarkAndLight,
+    MediumDarkAndMediumLight,
+    MediumDarkAndMedium,
+    MediumDarkAndDark,
+    DarkAndLight,
+    DarkAndMediumLight,
+    DarkAndMedium,
+    DarkAndMediumDark,
 }
 
 impl From<Tone> for SkinTone {
@@ -22,52 +45,738 @@ impl From<Tone> for SkinTone {
             Tone::Medium => SkinTone::Medium,
             Tone::MediumDark => SkinTone::MediumDark,
             Tone::Dark => SkinTone::Dark,
+            Tone::LightAndMediumLight => SkinTone::LightAndMediumLight,
+            Tone::LightAndMedium => SkinTone::LightAndMedium,
+            Tone::LightAndMediumDark => SkinTone::LightAndMediumDark,
+            Tone::LightAndDark => SkinTone::LightAndDark,
+            Tone::MediumLightAndLight => SkinTone::MediumLightAndLight,
+            Tone::MediumLightAndMedium => SkinTone::MediumLightAndMedium,
+            Tone::MediumLightAndMediumDark => SkinTone::MediumLightAndMediumDark,
+            Tone::MediumLightAndDark => SkinTone::MediumLightAndDark,
+            Tone::MediumAndLight => SkinTone::MediumAndLight,
+            Tone::MediumAndMediumLight => SkinTone::MediumAndMediumLight,
+            Tone::MediumAndMediumDark => SkinTone::MediumAndMediumDark,
+            Tone::MediumAndDark => SkinTone::MediumAndDark,
+            Tone::MediumDarkAndLight => SkinTone::MediumDarkAndLight,
+            Tone::MediumDarkAndMediumLight => SkinTone::MediumDarkAndMediumLight,
+            Tone::MediumDarkAndMedium => SkinTone::MediumDarkAndMedium,
+            Tone::MediumDarkAndDark => SkinTone::MediumDarkAndDark,
+            Tone::DarkAndLight => SkinTone::DarkAndLight,
+            Tone::DarkAndMediumLight => SkinTone::DarkAndMediumLight,
+            Tone::DarkAndMedium => SkinTone::DarkAndMedium,
+            Tone::DarkAndMediumDark => SkinTone::DarkAndMediumDark,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Style {
+    Unicode,
+    Light,
+    Heavy,
+    Rounded,
+    Ascii,
+}
+
+impl From<Style> for BlockStyle {
+    fn from(inp: Style) -> Self {
+        match inp {
+            Style::Unicode => BlockStyle::Unicode,
+            Style::Light => BlockStyle::Light,
+            Style::Heavy => BlockStyle::Heavy,
+            Style::Rounded => BlockStyle::Rounded,
+            Style::Ascii => BlockStyle::Ascii,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Align {
+    Center,
+    Left,
+    Right,
+    Decimal,
+}
+
+impl From<Align> for MatrixAlign {
+    fn from(inp: Align) -> Self {
+        match inp {
+            Align::Center => MatrixAlign::Center,
+            Align::Left => MatrixAlign::Left,
+            Align::Right => MatrixAlign::Right,
+            Align::Decimal => MatrixAlign::Decimal,
         }
     }
 }
 
 /// Convert asciimath in stdin to unicode in stdout
+///
+/// Defaults are read from $XDG_CONFIG_HOME/asciimath-unicode/config.toml (or config.json) when it
+/// exists, and flags override them.
 #[derive(Debug, Clone, Parser)]
 #[command(version, about)]
 struct Args {
-    /// Don't strip unnecessary parenthesis in some contexts
+    /// Read defaults from this toml or json file instead of the user config
+    #[arg(long, value_name = "PATH", conflicts_with = "no_config")]
+    config: Option<PathBuf>,
+
+    /// Don't read the user config file
     #[arg(long)]
+    no_config: bool,
+
+    /// Strip unnecessary parenthesis in some contexts, the default
+    #[arg(long, overrides_with = "no_strip_brackets")]
+    strip_brackets: bool,
+
+    /// Don't strip unnecessary parenthesis in some contexts
+    #[arg(long, overrides_with = "strip_brackets")]
     no_strip_brackets: bool,
 
+    /// Render fractions as vulgar fractions where possible, the default
+    #[arg(long, overrides_with = "no_vulgar_fracs")]
+    vulgar_fracs: bool,
+
     /// Don't render fractions as vulgar fractions
-    #[arg(long)]
+    #[arg(long, overrides_with = "vulgar_fracs")]
     no_vulgar_fracs: bool,
 
+    /// Render fractions using super- and sub-scripts where possible, the default
+    #[arg(long, overrides_with = "no_script_fracs")]
+    script_fracs: bool,
+
     /// Don't render fractions using super- and sub-scripts
-    #[arg(long)]
+    #[arg(long, overrides_with = "script_fracs")]
     no_script_fracs: bool,
 
-    /// Skin tone for emoji
-    #[arg(long, value_enum, default_value_t = Tone::Default)]
-    skin_tone: Tone,
+    /// Skin tone for emoji, or a tone for each of two people like light-and-dark
+    #[arg(long, value_enum)]
+    skin_tone: Option<Tone>,
 
     /// Render as multi-line 2D block (stacked fractions, vertical scripts, matrix grids)
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_block")]
     block: bool,
+
+    /// Render on a single line, the default
+    #[arg(long, overrides_with = "block")]
+    no_block: bool,
+
+    /// Draw sums and integrals as multi-line glyphs in block mode
+    #[arg(long, overrides_with = "no_tall_operators")]
+    tall_operators: bool,
+
+    /// Draw sums and integrals as single characters, the default
+    #[arg(long, overrides_with = "tall_operators")]
+    no_tall_operators: bool,
+
+    /// Render `color` with ANSI escape sequences
+    #[arg(long, overrides_with = "no_ansi_color")]
+    ansi_color: bool,
+
+    /// Don't render `color`, the default
+    #[arg(long, overrides_with = "ansi_color")]
+    no_ansi_color: bool,
+
+    /// Color numbers, identifiers, and operators with ANSI escape sequences
+    #[arg(long, overrides_with = "no_highlight")]
+    highlight: bool,
+
+    /// Don't color numbers, identifiers, and operators, the default
+    #[arg(long, overrides_with = "highlight")]
+    no_highlight: bool,
+
+    /// Break long expressions so lines fit within this many columns
+    #[arg(long, value_name = "COLUMNS", overrides_with = "no_max_width")]
+    max_width: Option<usize>,
+
+    /// Never break long expressions, the default
+    #[arg(long, overrides_with = "max_width")]
+    no_max_width: bool,
+
+    /// Render pure ascii, using asciimath names for symbols and ascii block drawing
+    #[arg(long, overrides_with = "no_ascii")]
+    ascii: bool,
+
+    /// Render unicode, the default
+    #[arg(long, overrides_with = "ascii")]
+    no_ascii: bool,
+
+    /// Glyphs used to draw brackets, fraction bars, radicals, and braces in block mode
+    #[arg(long, value_enum)]
+    block_style: Option<Style>,
+
+    /// How matrix cells line up within their column in block mode
+    #[arg(long, value_enum)]
+    matrix_align: Option<Align>,
+
+    /// Draw matrix columns holding only `|` as rules, e.g. for augmented matrices
+    #[arg(long, overrides_with = "no_column_rules")]
+    column_rules: bool,
+
+    /// Keep `|` columns as matrix cells, the default
+    #[arg(long, overrides_with = "column_rules")]
+    no_column_rules: bool,
+
+    /// Separate matrix rows with horizontal rules in block mode
+    #[arg(long, overrides_with = "no_row_rules")]
+    row_rules: bool,
+
+    /// Don't separate matrix rows with rules, the default
+    #[arg(long, overrides_with = "row_rules")]
+    no_row_rules: bool,
+
+    /// Describe how each node of the formula was rendered before the result
+    ///
+    /// Fractions and scripts list the strategy that was chosen and why the alternatives before it
+    /// didn't apply.
+    #[arg(long, conflicts_with_all = ["document", "lines", "null", "json", "repl"])]
+    explain: bool,
+
+    /// Render each non-blank line of stdin as one row of a block, aligned on its first relation
+    ///
+    /// Rows can start with a relation like = to continue the derivation of the row above.
+    #[arg(long, conflicts_with_all = ["explain", "document", "lines", "null", "json", "repl"])]
+    aligned: bool,
+
+    /// Treat stdin as a document and only convert the math between delimiters
+    #[arg(long, conflicts_with_all = ["lines", "null", "json", "repl"])]
+    document: bool,
+
+    /// Delimiters around math in a document, replacing the defaults of `...`, $...$, and \(...\)
+    #[arg(long, num_args = 2, value_names = ["OPEN", "CLOSE"], requires = "document")]
+    delimiter: Vec<String>,
+
+    /// Convert each line of stdin as an independent formula
+    #[arg(long, group = "records", conflicts_with_all = ["null", "json", "repl"])]
+    lines: bool,
+
+    /// Convert each NUL separated record of stdin as an independent formula
+    #[arg(short = '0', long, group = "records", conflicts_with_all = ["json", "repl"])]
+    null: bool,
+
+    /// Written after each result with --lines or -0, accepting \n, \t, and \0 escapes
+    ///
+    /// Defaults to the input separator. Block mode results span several lines, so a blank line
+    /// or other marker may be easier to split on.
+    #[arg(long, value_name = "SEP", requires = "records")]
+    record_separator: Option<String>,
+
+    /// Read a json request per line and write a json response per line
+    ///
+    /// Requests look like {"id": 1, "input": "x/y", "conf": {"block": true}}, where "id" is
+    /// echoed back and "conf" overrides the configuration from the command line. Responses look
+    /// like {"id": 1, "output": "...", "warnings": [...]}, or contain an "error" instead.
+    #[arg(long, conflicts_with = "repl")]
+    json: bool,
+
+    /// Interactively render formulas, changing the configuration with commands like :block on
+    ///
+    /// Type :help in the session for the list of commands.
+    #[arg(long)]
+    repl: bool,
 }
 
-impl From<Args> for Conf {
-    fn from(inp: Args) -> Self {
+impl Args {
+    /// Override `conf` with the flags that were given
+    fn apply(&self, conf: Conf) -> Conf {
+        // a flag pair overrides the config only when one of them was given
+        let flag = |on: bool, off: bool, current: bool| on || (current && !off);
         Conf {
-            strip_brackets: !inp.no_strip_brackets,
-            vulgar_fracs: !inp.no_vulgar_fracs,
-            script_fracs: !inp.no_script_fracs,
-            skin_tone: inp.skin_tone.into(),
-            block: inp.block,
+            strip_brackets: flag(
+                self.strip_brackets,
+                self.no_strip_brackets,
+                conf.strip_brackets,
+            ),
+            vulgar_fracs: flag(self.vulgar_fracs, self.no_vulgar_fracs, conf.vulgar_fracs),
+            script_fracs: flag(self.script_fracs, self.no_script_fracs, conf.script_fracs),
+            skin_tone: self.skin_tone.clone().map_or(conf.skin_tone, Into::into),
+            block: flag(self.block, self.no_block, conf.block),
+            tall_operators: flag(
+                self.tall_operators,
+                self.no_tall_operators,
+                conf.tall_operators,
+            ),
+            ansi_color: flag(self.ansi_color, self.no_ansi_color, conf.ansi_color),
+            highlight: flag(self.highlight, self.no_highlight, conf.highlight),
+            max_width: if self.no_max_width {
+                None
+            } else {
+                self.max_width.or(conf.max_width)
+            },
+            ascii: flag(self.ascii, self.no_ascii, conf.ascii),
+            block_style: self
+                .block_style
+                .clone()
+                .map_or(conf.block_style, Into::into),
+            matrix_align: self
+                .matrix_align
+                .clone()
+                .map_or(conf.matrix_align, Into::into),
+            column_rules: flag(self.column_rules, self.no_column_rules, conf.column_rules),
+            row_rules: flag(self.row_rules, self.no_row_rules, conf.row_rules),
+        }
+    }
+
+    /// The config file to read, if any
+    fn config_path(&self) -> Option<PathBuf> {
+        if self.no_config {
+            None
+        } else if let Some(path) = &self.config {
+            Some(path.clone())
+        } else {
+            let dir = env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?
+                .join("asciimath-unicode");
+            ["config.toml", "config.json"]
+                .into_iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.is_file())
+        }
+    }
+
+    /// The configuration from the config file with flags applied
+    fn conf(&self) -> Result<Conf, String> {
+        let base = match self.config_path() {
+            Some(path) => read_config(&path).map_err(|err| format!("{}: {err}", path.display()))?,
+            None => Conf::default(),
+        };
+        Ok(self.apply(base))
+    }
+}
+
+/// Read a toml or json configuration, choosing by extension
+fn read_config(path: &Path) -> Result<Conf, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&text).map_err(|err| err.to_string())
+    } else {
+        toml::from_str(&text).map_err(|err| err.to_string())
+    }
+}
+
+/// Override fields of `conf` with those in a json object
+fn merge_conf(conf: Conf, overrides: Map<String, Value>) -> serde_json::Result<Conf> {
+    let mut merged = match serde_json::to_value(conf)? {
+        Value::Object(fields) => fields,
+        _ => unreachable!("conf serializes to an object"),
+    };
+    merged.extend(overrides);
+    serde_json::from_value(Value::Object(merged))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    input: String,
+    #[serde(default)]
+    conf: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonWarning {
+    kind: &'static str,
+    message: String,
+    /// null for a construct that couldn't be located in the input
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warnings: Option<Vec<JsonWarning>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Response {
+    fn new(conf: Conf, line: &str) -> Self {
+        match serde_json::from_str::<Request>(line) {
+            Ok(req) => {
+                let conf = match merge_conf(conf, req.conf) {
+                    Ok(conf) => conf,
+                    Err(err) => return Response::error(req.id, &err),
+                };
+                let report = conf.render_with_report(&req.input);
+                let warnings = report
+                    .warnings
+                    .into_iter()
+                    .map(|warning| JsonWarning {
+                        kind: warning.kind.as_str(),
+                        message: warning.kind.to_string(),
+                        start: warning.span.as_ref().map(|span| span.start),
+                        end: warning.span.map(|span| span.end),
+                    })
+                    .collect();
+                Response {
+                    id: req.id,
+                    output: Some(report.output),
+                    warnings: Some(warnings),
+                    error: None,
+                }
+            }
+            // still echo the id of a well formed request with bad fields
+            Err(err) => Response::error(
+                serde_json::from_str::<Value>(line)
+                    .ok()
+                    .and_then(|val| val.get("id").cloned())
+                    .unwrap_or_default(),
+                &err,
+            ),
+        }
+    }
+
+    fn error(id: Value, err: impl fmt::Display) -> Self {
+        Response {
+            id,
+            output: None,
+            warnings: None,
+            error: Some(err.to_string()),
+        }
+    }
+}
+
+/// Answer each json request line with a json response line
+///
+/// A line that isn't utf-8 gets an error response, and the requests after it are still served.
+fn serve_json(conf: Conf, mut inp: impl BufRead, mut out: impl Write) -> io::Result<()> {
+    let mut line = Vec::new();
+    while inp.read_until(b'\n', &mut line)? > 0 {
+        let response = match std::str::from_utf8(&line) {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(Response::new(conf, line)),
+            Err(_) => Some(Response::error(Value::Null, "request is not utf-8")),
+        };
+        line.clear();
+        if let Some(response) = response {
+            serde_json::to_writer(&mut out, &response)?;
+            writeln!(out)?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+const REPL_HELP: &str = "\
+Type a formula to render it, or a command:
+  :block, :vulgar, :script, :strip, :tall, :color, :highlight, :ascii, :colrules,
+  :rowrules [on|off]   set or toggle a configuration field
+  :tone NAME           emoji skin tone (default, light, medium-light, medium, medium-dark, dark,
+                       or one for each of two people like light-and-dark)
+  :style NAME          block drawing glyphs (unicode, light, heavy, rounded, ascii)
+  :align NAME          matrix cell alignment (center, left, right, decimal)
+  :width COLUMNS|off   break long formulas to fit in a width
+  :tree [on|off]       also show how each node of a formula is rendered
+  :conf                show the current configuration
+  :history             list the formulas rendered so far
+  !N, !!               render formula N from the history, or the last one again
+  :help                show this message
+  :quit                end the session
+";
+
+/// Parse `on` or `off`, or toggle `current` without an argument
+fn switch(current: bool, arg: Option<&str>) -> Result<bool, String> {
+    match arg {
+        None => Ok(!current),
+        Some("on" | "true" | "yes") => Ok(true),
+        Some("off" | "false" | "no") => Ok(false),
+        Some(other) => Err(format!("expected on or off, got {other:?}")),
+    }
+}
+
+/// Parse the value of a `:name` command that picks one of the variants of `T`
+fn choice<T: ValueEnum>(name: &str, arg: Option<&str>) -> Result<T, String> {
+    let names = T::value_variants()
+        .iter()
+        .filter_map(ValueEnum::to_possible_value)
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_owned())
+        .collect::<Vec<_>>()
+        .join(", ");
+    match arg {
+        None => Err(format!("usage: :{name} NAME, one of {names}")),
+        Some(arg) => {
+            T::from_str(arg, true).map_err(|_| format!("expected one of {names}, got {arg:?}"))
+        }
+    }
+}
+
+/// State of an interactive session
+#[derive(Debug)]
+struct Repl {
+    conf: Conf,
+    tree: bool,
+    history: Vec<String>,
+}
+
+impl Repl {
+    fn new(conf: Conf) -> Self {
+        Repl {
+            conf,
+            tree: false,
+            history: Vec::new(),
+        }
+    }
+
+    /// Handle one line of input, returning false once the session should end
+    fn line(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let line = line.trim();
+        if line.is_empty() {
+            Ok(true)
+        } else if let Some((name, arg)) = Repl::parse_command(line) {
+            if matches!(name, "q" | "quit" | "exit") {
+                return Ok(false);
+            }
+            if let Err(err) = self.command(name, arg, out) {
+                writeln!(out, "error: {err}")?;
+            }
+            Ok(true)
+        } else if let Some(entry) = line.strip_prefix('!') {
+            let recalled = if entry == "!" {
+                self.history.last()
+            } else {
+                entry
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|ind| self.history.get(ind.wrapping_sub(1)))
+            };
+            match recalled.cloned() {
+                Some(formula) => self.render(formula, out)?,
+                None => writeln!(out, "error: no history entry {entry:?}")?,
+            }
+            Ok(true)
+        } else {
+            self.render(line.to_owned(), out)?;
+            Ok(true)
+        }
+    }
+
+    /// Split a command into its name and argument
+    ///
+    /// Lines like `:smile:` or `:=` are formulas rather than commands.
+    fn parse_command(line: &str) -> Option<(&str, Option<&str>)> {
+        let mut words = line.strip_prefix(':')?.split_whitespace();
+        let name = words.next()?;
+        if name.chars().all(|chr| chr.is_ascii_alphabetic()) || name == "?" {
+            Some((name, words.next()))
+        } else {
+            None
+        }
+    }
+
+    fn command(
+        &mut self,
+        name: &str,
+        arg: Option<&str>,
+        out: &mut impl Write,
+    ) -> Result<(), String> {
+        let conf = &mut self.conf;
+        match name {
+            "block" => conf.block = switch(conf.block, arg)?,
+            "vulgar" => conf.vulgar_fracs = switch(conf.vulgar_fracs, arg)?,
+            "script" => conf.script_fracs = switch(conf.script_fracs, arg)?,
+            "strip" => conf.strip_brackets = switch(conf.strip_brackets, arg)?,
+            "tall" => conf.tall_operators = switch(conf.tall_operators, arg)?,
+            "color" => conf.ansi_color = switch(conf.ansi_color, arg)?,
+            "highlight" => conf.highlight = switch(conf.highlight, arg)?,
+            "ascii" => conf.ascii = switch(conf.ascii, arg)?,
+            "colrules" => conf.column_rules = switch(conf.column_rules, arg)?,
+            "rowrules" => conf.row_rules = switch(conf.row_rules, arg)?,
+            "tree" => self.tree = switch(self.tree, arg)?,
+            "tone" => conf.skin_tone = choice::<Tone>(name, arg)?.into(),
+            "style" => conf.block_style = choice::<Style>(name, arg)?.into(),
+            "align" => conf.matrix_align = choice::<Align>(name, arg)?.into(),
+            "width" => {
+                conf.max_width = match arg {
+                    None | Some("off") => None,
+                    Some(cols) => Some(
+                        cols.parse()
+                            .map_err(|_| format!("invalid width {cols:?}"))?,
+                    ),
+                };
+            }
+            "conf" => {
+                let shown = toml::to_string(conf).map_err(|err| err.to_string())?;
+                out.write_all(shown.as_bytes())
+                    .map_err(|err| err.to_string())?;
+            }
+            "history" => {
+                for (ind, formula) in self.history.iter().enumerate() {
+                    writeln!(out, "{:>4}  {formula}", ind + 1).map_err(|err| err.to_string())?;
+                }
+            }
+            "help" | "h" | "?" => out
+                .write_all(REPL_HELP.as_bytes())
+                .map_err(|err| err.to_string())?,
+            _ => return Err(format!("unknown command :{name}, see :help")),
+        }
+        Ok(())
+    }
+
+    fn render(&mut self, formula: String, out: &mut impl Write) -> io::Result<()> {
+        let parsed = self.conf.parse(&formula);
+        if self.tree {
+            write!(out, "{}", parsed.explain())?;
+        }
+        writeln!(out, "{parsed}")?;
+        self.history.push(formula);
+        Ok(())
+    }
+}
+
+/// Run an interactive session, prompting when `prompt` is set
+fn repl(conf: Conf, inp: impl BufRead, mut out: impl Write, prompt: bool) -> io::Result<()> {
+    let mut session = Repl::new(conf);
+    let mut lines = inp.lines();
+    loop {
+        if prompt {
+            write!(out, "> ")?;
+        }
+        out.flush()?;
+        let Some(line) = lines.next() else {
+            if prompt {
+                writeln!(out)?;
+            }
+            return Ok(());
+        };
+        if !session.line(&line?, &mut out)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Replace `\n`, `\t`, `\0`, and `\\` escapes
+fn unescape(inp: &str) -> String {
+    let mut res = String::with_capacity(inp.len());
+    let mut chars = inp.chars();
+    while let Some(chr) = chars.next() {
+        if chr == '\\' {
+            match chars.next() {
+                Some('n') => res.push('\n'),
+                Some('t') => res.push('\t'),
+                Some('0') => res.push('\0'),
+                Some('\\') => res.push('\\'),
+                Some(other) => {
+                    res.push('\\');
+                    res.push(other);
+                }
+                None => res.push('\\'),
+            }
+        } else {
+            res.push(chr);
+        }
+    }
+    res
+}
+
+/// Convert each record independently, writing every result as soon as its record is read
+///
+/// A record that isn't utf-8 is reported on stderr and gives an empty result, so results still
+/// line up with their records. Returns whether every record was converted.
+fn convert_records(
+    conf: Conf,
+    mut inp: impl BufRead,
+    mut out: impl Write,
+    delim: u8,
+    separator: &str,
+) -> io::Result<bool> {
+    let mut record = Vec::new();
+    let mut valid = true;
+    let mut num = 0;
+    while inp.read_until(delim, &mut record)? > 0 {
+        num += 1;
+        if record.last() == Some(&delim) {
+            record.pop();
+        }
+        if delim == b'\n' && record.last() == Some(&b'\r') {
+            record.pop();
+        }
+        match std::str::from_utf8(&record) {
+            Ok(formula) => write!(out, "{}{separator}", conf.parse(formula))?,
+            Err(err) => {
+                eprintln!("error: record {num} is not utf-8: {err}");
+                valid = false;
+                out.write_all(separator.as_bytes())?;
+            }
+        }
+        out.flush()?;
+        record.clear();
+    }
+    Ok(valid)
+}
+
+/// Unwrap the result of reading stdin or writing stdout, exiting quietly once the reader of
+/// stdout has gone away, e.g. with `| head -1`
+fn or_exit<T>(res: io::Result<T>) -> T {
+    match res {
+        Ok(val) => val,
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+        Err(err) => {
+            eprintln!("error: {err}");
+            process::exit(1);
         }
     }
 }
 
 fn main() {
-    let conf: Conf = Args::parse().into();
-    let mut inp = String::new();
-    io::stdin().lock().read_to_string(&mut inp).unwrap();
-    let mut out = io::stdout().lock();
-    write!(out, "{}", conf.parse(&inp)).unwrap();
-    writeln!(out).unwrap();
+    let args = Args::parse();
+    let conf = args.conf().unwrap_or_else(|err| {
+        eprintln!("error: invalid config {err}");
+        process::exit(2);
+    });
+    if args.document {
+        let mut doc = Document::new(conf);
+        if !args.delimiter.is_empty() {
+            doc.clear_delimiters();
+            for pair in args.delimiter.chunks(2) {
+                doc.add_delimiter(&pair[0], &pair[1]);
+            }
+        }
+        or_exit(doc.stream(io::stdin().lock(), io::stdout().lock()));
+    } else if args.repl {
+        let prompt = io::stdin().is_terminal();
+        or_exit(repl(conf, io::stdin().lock(), io::stdout().lock(), prompt));
+    } else if args.json {
+        or_exit(serve_json(conf, io::stdin().lock(), io::stdout().lock()));
+    } else if args.aligned {
+        let mut inp = String::new();
+        or_exit(io::stdin().lock().read_to_string(&mut inp));
+        let rows = inp.lines().filter(|line| !line.trim().is_empty());
+        or_exit(writeln!(
+            io::stdout().lock(),
+            "{}",
+            conf.render_aligned(rows)
+        ));
+    } else if args.lines || args.null {
+        let delim = if args.null { b'\0' } else { b'\n' };
+        let separator = args
+            .record_separator
+            .as_deref()
+            .map_or_else(|| char::from(delim).to_string(), unescape);
+        let valid = or_exit(convert_records(
+            conf,
+            io::stdin().lock(),
+            io::stdout().lock(),
+            delim,
+            &separator,
+        ));
+        if !valid {
+            process::exit(1);
+        }
+    } else {
+        let mut inp = String::new();
+        or_exit(io::stdin().lock().read_to_string(&mut inp));
+        let mut out = io::stdout().lock();
+        let parsed = conf.parse(&inp);
+        if args.explain {
+            or_exit(writeln!(out, "{}", parsed.explain()));
+        }
+        or_exit(writeln!(out, "{parsed}"));
+    }
 }
@@ -0,0 +1,627 @@
+//! Rendering of asciimath as mathml presentation markup
+
+use asciimath_parser::tree::{
+    Expression, Frac, Func, Group, Intermediate, Matrix, Script, ScriptFunc, Simple, SimpleBinary,
+    SimpleFunc, SimpleScript, SimpleUnary,
+};
+use std::fmt;
+use std::fmt::Write;
+
+use super::Conf;
+use super::ast::{extract_raw_text, extract_text};
+use super::block::is_underover;
+use super::inline::Mapper;
+use super::tokens::{
+    bold_map, cal_map, double_map, frak_map, italic_map, left_bracket_str, mono_map,
+    right_bracket_str, sans_map, symbol_str,
+};
+
+/// The font mapping applied to token contents, mirroring `MapperConf::font`
+type Font = Option<fn(char) -> char>;
+
+fn escaped(text: &str, font: Font, out: &mut impl Write) -> fmt::Result {
+    for chr in text.chars() {
+        match font.map_or(chr, |font| font(chr)) {
+            '&' => out.write_str("&amp;")?,
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            '"' => out.write_str("&quot;")?,
+            chr => out.write_char(chr)?,
+        }
+    }
+    Ok(())
+}
+
+fn token(tag: &str, text: &str, font: Font, out: &mut impl Write) -> fmt::Result {
+    write!(out, "<{tag}>")?;
+    escaped(text, font, out)?;
+    write!(out, "</{tag}>")
+}
+
+/// The token element for rendered identifier or symbol text
+fn leaf_tag(text: &str) -> &'static str {
+    if text.is_empty() || text.trim() != text {
+        "mtext"
+    } else if text.chars().all(char::is_alphanumeric) || matches!(text, "∞" | "∅") {
+        "mi"
+    } else {
+        "mo"
+    }
+}
+
+impl Conf {
+    fn mathml_stripped(self, simple: &Simple<'_>, font: Font, out: &mut impl Write) -> fmt::Result {
+        match simple {
+            Simple::Group(Group { expr, .. }) if self.strip_brackets => {
+                self.mathml_row(expr, font, out)
+            }
+            simple => self.mathml_simple(simple, font, out),
+        }
+    }
+
+    fn mathml_fenced(
+        self,
+        open: &str,
+        arg: &Simple<'_>,
+        close: &str,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        out.write_str("<mrow>")?;
+        token("mo", open, None, out)?;
+        self.mathml_stripped(arg, font, out)?;
+        token("mo", close, None, out)?;
+        out.write_str("</mrow>")
+    }
+
+    fn mathml_accent(
+        self,
+        tag: &str,
+        arg: &Simple<'_>,
+        accent: &str,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        let attr = if tag == "munder" {
+            "accentunder"
+        } else {
+            "accent"
+        };
+        write!(out, "<{tag} {attr}=\"true\">")?;
+        self.mathml_stripped(arg, font, out)?;
+        token("mo", accent, None, out)?;
+        write!(out, "</{tag}>")
+    }
+
+    fn mathml_simpleunary(
+        self,
+        unary: &SimpleUnary<'_>,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        match (unary.op, unary.arg()) {
+            ("sqrt", arg) => {
+                out.write_str("<msqrt>")?;
+                self.mathml_stripped(arg, font, out)?;
+                out.write_str("</msqrt>")
+            }
+            // fonts
+            ("bb" | "mathbf", arg) => self.mathml_stripped(arg, Some(bold_map), out),
+            ("bbb" | "mathbb", arg) => self.mathml_stripped(arg, Some(double_map), out),
+            ("cc" | "mathcal", arg) => self.mathml_stripped(arg, Some(cal_map), out),
+            ("tt" | "mathtt", arg) => self.mathml_stripped(arg, Some(mono_map), out),
+            ("fr" | "mathfrak", arg) => self.mathml_stripped(arg, Some(frak_map), out),
+            ("sf" | "mathsf", arg) => self.mathml_stripped(arg, Some(sans_map), out),
+            ("it" | "mathit", arg) => self.mathml_stripped(arg, Some(italic_map), out),
+            // functions
+            ("abs" | "Abs", arg) => self.mathml_fenced("|", arg, "|", font, out),
+            ("ceil", arg) => self.mathml_fenced("⌈", arg, "⌉", font, out),
+            ("floor", arg) => self.mathml_fenced("⌊", arg, "⌋", font, out),
+            ("norm", arg) => self.mathml_fenced("‖", arg, "‖", font, out),
+            ("text" | "mbox", arg) => {
+                let mut text = String::new();
+                let mut mapper = Mapper::new(&mut text);
+                match arg {
+                    Simple::Group(Group { expr, .. }) if self.strip_brackets => {
+                        self.inline_expression(expr, &mut mapper)?;
+                    }
+                    arg => self.inline_simple(arg, &mut mapper)?,
+                }
+                token("mtext", &text, font, out)
+            }
+            // modifiers
+            ("overline" | "bar", arg) => self.mathml_accent("mover", arg, "¯", font, out),
+            ("underline" | "ul", arg) => self.mathml_accent("munder", arg, "_", font, out),
+            ("cancel", arg) => {
+                out.write_str("<menclose notation=\"updiagonalstrike\">")?;
+                self.mathml_stripped(arg, font, out)?;
+                out.write_str("</menclose>")
+            }
+            ("hat", arg) => self.mathml_accent("mover", arg, "^", font, out),
+            ("tilde", arg) => self.mathml_accent("mover", arg, "~", font, out),
+            ("dot", arg) => self.mathml_accent("mover", arg, ".", font, out),
+            ("ddot", arg) => self.mathml_accent("mover", arg, "..", font, out),
+            ("vec", arg) => self.mathml_accent("mover", arg, "→", font, out),
+            ("overarc" | "overparen", arg) => self.mathml_accent("mover", arg, "⏜", font, out),
+            ("ubrace" | "underbrace", arg) => self.mathml_accent("munder", arg, "⏟", font, out),
+            ("obrace" | "overbrace", arg) => self.mathml_accent("mover", arg, "⏞", font, out),
+            // generic
+            (op, arg) => {
+                out.write_str("<mrow>")?;
+                token("mi", op, font, out)?;
+                self.mathml_simple(arg, font, out)?;
+                out.write_str("</mrow>")
+            }
+        }
+    }
+
+    fn mathml_pair(
+        self,
+        tag: &str,
+        first: &Simple<'_>,
+        second: &Simple<'_>,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        write!(out, "<{tag}>")?;
+        self.mathml_stripped(first, font, out)?;
+        self.mathml_stripped(second, font, out)?;
+        write!(out, "</{tag}>")
+    }
+
+    fn mathml_attributed(
+        self,
+        tag: &str,
+        attr: &str,
+        value: &Simple<'_>,
+        arg: &Simple<'_>,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        write!(out, "<{tag} {attr}=\"")?;
+        // class lists keep the spaces between their names
+        let value = if attr == "class" {
+            extract_text(value)
+        } else {
+            extract_raw_text(value)
+        };
+        escaped(&value, None, out)?;
+        out.write_str("\">")?;
+        self.mathml_stripped(arg, font, out)?;
+        write!(out, "</{tag}>")
+    }
+
+    fn mathml_simplebinary(
+        self,
+        binary: &SimpleBinary<'_>,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        match (binary.op, binary.first(), binary.second()) {
+            ("frac", numer, denom) => self.mathml_pair("mfrac", numer, denom, font, out),
+            ("root", index, arg) => self.mathml_pair("mroot", arg, index, font, out),
+            ("stackrel" | "overset", over, arg) => self.mathml_pair("mover", arg, over, font, out),
+            ("underset", under, arg) => self.mathml_pair("munder", arg, under, font, out),
+            ("color", color, arg) => {
+                self.mathml_attributed("mstyle", "mathcolor", color, arg, font, out)
+            }
+            ("id", id, arg) => self.mathml_attributed("mrow", "id", id, arg, font, out),
+            ("class", class, arg) => self.mathml_attributed("mrow", "class", class, arg, font, out),
+            // generic
+            (op, first, second) => {
+                out.write_str("<mrow>")?;
+                token("mi", op, font, out)?;
+                self.mathml_simple(first, font, out)?;
+                self.mathml_simple(second, font, out)?;
+                out.write_str("</mrow>")
+            }
+        }
+    }
+
+    fn mathml_simplefunc(
+        self,
+        func: &SimpleFunc<'_>,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        out.write_str("<mrow>")?;
+        token("mi", func.func, font, out)?;
+        out.write_str("<mo>&#x2061;</mo>")?;
+        self.mathml_simple(func.arg(), font, out)?;
+        out.write_str("</mrow>")
+    }
+
+    fn mathml_group(self, group: &Group<'_>, font: Font, out: &mut impl Write) -> fmt::Result {
+        let left = left_bracket_str(group.left_bracket);
+        let right = right_bracket_str(group.right_bracket);
+        out.write_str("<mrow>")?;
+        if !left.is_empty() {
+            token("mo", left, None, out)?;
+        }
+        self.mathml_expression(&group.expr, font, out)?;
+        if !right.is_empty() {
+            token("mo", right, None, out)?;
+        }
+        out.write_str("</mrow>")
+    }
+
+    fn mathml_matrix(self, matrix: &Matrix<'_>, font: Font, out: &mut impl Write) -> fmt::Result {
+        let left = left_bracket_str(matrix.left_bracket);
+        let right = right_bracket_str(matrix.right_bracket);
+        out.write_str("<mrow>")?;
+        if !left.is_empty() {
+            token("mo", left, None, out)?;
+        }
+        out.write_str("<mtable>")?;
+        for row in matrix.rows() {
+            out.write_str("<mtr>")?;
+            for expr in row {
+                out.write_str("<mtd>")?;
+                self.mathml_row(expr, font, out)?;
+                out.write_str("</mtd>")?;
+            }
+            out.write_str("</mtr>")?;
+        }
+        out.write_str("</mtable>")?;
+        if !right.is_empty() {
+            token("mo", right, None, out)?;
+        }
+        out.write_str("</mrow>")
+    }
+
+    fn mathml_simple(self, simple: &Simple<'_>, font: Font, out: &mut impl Write) -> fmt::Result {
+        match simple {
+            Simple::Missing => out.write_str("<mrow></mrow>"),
+            &Simple::Number(num) => token("mn", num, font, out),
+            &Simple::Text(text) => token("mtext", text, font, out),
+            &Simple::Ident(ident) => token(leaf_tag(ident), ident, font, out),
+            &Simple::Symbol(symbol) => {
+                let text = symbol_str(symbol, self.skin_tone);
+                // emoji are pictures, not operators
+                let tag = if symbol.starts_with(':') {
+                    "mi"
+                } else {
+                    leaf_tag(text)
+                };
+                token(tag, text, font, out)
+            }
+            Simple::Func(func) => self.mathml_simplefunc(func, font, out),
+            Simple::Unary(unary) => self.mathml_simpleunary(unary, font, out),
+            Simple::Binary(binary) => self.mathml_simplebinary(binary, font, out),
+            Simple::Group(group) => self.mathml_group(group, font, out),
+            Simple::Matrix(matrix) => self.mathml_matrix(matrix, font, out),
+        }
+    }
+
+    /// Scripts on `base`, placed below and above it when they're the `limits` of an operator
+    fn mathml_scripted<W: Write>(
+        self,
+        base: impl FnOnce(&mut W) -> fmt::Result,
+        script: &Script<'_>,
+        limits: bool,
+        font: Font,
+        out: &mut W,
+    ) -> fmt::Result {
+        let (sub, sup, subsup) = if limits {
+            ("munder", "mover", "munderover")
+        } else {
+            ("msub", "msup", "msubsup")
+        };
+        match script {
+            Script::None => base(out),
+            Script::Sub(under) => {
+                write!(out, "<{sub}>")?;
+                base(out)?;
+                self.mathml_stripped(under, font, out)?;
+                write!(out, "</{sub}>")
+            }
+            Script::Super(over) => {
+                write!(out, "<{sup}>")?;
+                base(out)?;
+                self.mathml_stripped(over, font, out)?;
+                write!(out, "</{sup}>")
+            }
+            Script::Subsuper(under, over) => {
+                write!(out, "<{subsup}>")?;
+                base(out)?;
+                self.mathml_stripped(under, font, out)?;
+                self.mathml_stripped(over, font, out)?;
+                write!(out, "</{subsup}>")
+            }
+        }
+    }
+
+    fn mathml_func(self, func: &Func<'_>, font: Font, out: &mut impl Write) -> fmt::Result {
+        let name = |out: &mut _| token("mi", func.func, font, out);
+        // a bare function name (e.g. `f`, `g`) has a missing argument and isn't applied
+        if let ScriptFunc::Simple(SimpleScript {
+            simple: Simple::Missing,
+            ..
+        }) = func.arg()
+        {
+            self.mathml_scripted(name, &func.script, false, font, out)
+        } else {
+            out.write_str("<mrow>")?;
+            self.mathml_scripted(name, &func.script, false, font, out)?;
+            out.write_str("<mo>&#x2061;</mo>")?;
+            self.mathml_scriptfunc(func.arg(), font, out)?;
+            out.write_str("</mrow>")
+        }
+    }
+
+    fn mathml_scriptfunc(
+        self,
+        func: &ScriptFunc<'_>,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        match func {
+            ScriptFunc::Simple(SimpleScript { simple, script }) => self.mathml_scripted(
+                |out| self.mathml_simple(simple, font, out),
+                script,
+                is_underover(simple),
+                font,
+                out,
+            ),
+            ScriptFunc::Func(func) => self.mathml_func(func, font, out),
+        }
+    }
+
+    /// A fraction operand, stripping brackets from an unscripted group
+    fn mathml_frac_operand(
+        self,
+        func: &ScriptFunc<'_>,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        match func {
+            ScriptFunc::Simple(SimpleScript {
+                simple,
+                script: Script::None,
+            }) => self.mathml_stripped(simple, font, out),
+            func => self.mathml_scriptfunc(func, font, out),
+        }
+    }
+
+    fn mathml_frac(self, frac: &Frac<'_>, font: Font, out: &mut impl Write) -> fmt::Result {
+        out.write_str("<mfrac>")?;
+        self.mathml_frac_operand(&frac.numer, font, out)?;
+        self.mathml_frac_operand(&frac.denom, font, out)?;
+        out.write_str("</mfrac>")
+    }
+
+    fn mathml_expression(
+        self,
+        expr: &Expression<'_>,
+        font: Font,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        for inter in expr.iter() {
+            match inter {
+                Intermediate::ScriptFunc(func) => self.mathml_scriptfunc(func, font, out)?,
+                Intermediate::Frac(frac) => self.mathml_frac(frac, font, out)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// An expression as a single element, wrapping it in an `mrow` unless it already is one
+    fn mathml_row(self, expr: &Expression<'_>, font: Font, out: &mut impl Write) -> fmt::Result {
+        if expr.len() == 1 {
+            self.mathml_expression(expr, font, out)
+        } else {
+            out.write_str("<mrow>")?;
+            self.mathml_expression(expr, font, out)?;
+            out.write_str("</mrow>")
+        }
+    }
+
+    pub(crate) fn mathml(self, expr: &Expression<'_>, out: &mut impl Write) -> fmt::Result {
+        // escape sequences are not legal xml characters
+        let conf = Conf {
+            highlight: false,
+            ansi_color: false,
+            ..self
+        };
+        if conf.block {
+            out.write_str("<math display=\"block\">")?;
+        } else {
+            out.write_str("<math>")?;
+        }
+        conf.mathml_row(expr, None, out)?;
+        out.write_str("</math>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Conf;
+
+    fn render(inp: &str) -> String {
+        Conf::default().parse(inp).to_mathml()
+    }
+
+    /// Render and strip the outer `math` element
+    fn inner(inp: &str) -> String {
+        let out = render(inp);
+        out["<math>".len()..out.len() - "</math>".len()].to_string()
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(render("x"), "<math><mi>x</mi></math>");
+        assert_eq!(inner("42"), "<mn>42</mn>");
+        assert_eq!(inner("alpha"), "<mi>α</mi>");
+        assert_eq!(
+            inner("x + y"),
+            "<mrow><mi>x</mi><mo>+</mo><mi>y</mi></mrow>"
+        );
+        assert_eq!(inner("\"some text\""), "<mtext>some text</mtext>");
+        assert_eq!(
+            inner("a < b"),
+            "<mrow><mi>a</mi><mo>&lt;</mo><mi>b</mi></mrow>"
+        );
+        assert_eq!(inner("oo"), "<mi>∞</mi>");
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(inner("1/2"), "<mfrac><mn>1</mn><mn>2</mn></mfrac>");
+        assert_eq!(
+            inner("(x+1)/y"),
+            "<mfrac><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow><mi>y</mi></mfrac>"
+        );
+        assert_eq!(inner("frac(a)(b)"), "<mfrac><mi>a</mi><mi>b</mi></mfrac>");
+        assert_eq!(
+            inner("x^2/y"),
+            "<mfrac><msup><mi>x</mi><mn>2</mn></msup><mi>y</mi></mfrac>"
+        );
+    }
+
+    #[test]
+    fn no_strip_brackets() {
+        let conf = Conf {
+            strip_brackets: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            conf.parse("(x)/y").to_mathml(),
+            "<math><mfrac><mrow><mo>(</mo><mi>x</mi><mo>)</mo></mrow><mi>y</mi></mfrac></math>"
+        );
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(inner("x^2"), "<msup><mi>x</mi><mn>2</mn></msup>");
+        assert_eq!(inner("x_i"), "<msub><mi>x</mi><mi>i</mi></msub>");
+    }
+
+    #[test]
+    fn limits() {
+        assert_eq!(
+            inner("sum_(i=1)^n"),
+            "<munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover>"
+        );
+        assert_eq!(inner("prod^n"), "<mover><mo>∏</mo><mi>n</mi></mover>");
+        assert_eq!(
+            inner("lim_(x->0)"),
+            "<munder><mi>lim</mi><mrow><mi>x</mi><mo>→</mo><mn>0</mn></mrow></munder>"
+        );
+        assert_eq!(inner("max_x"), "<munder><mi>max</mi><mi>x</mi></munder>");
+        assert_eq!(inner("min_x"), "<munder><mi>min</mi><mi>x</mi></munder>");
+        assert_eq!(
+            inner("ubrace(a)_k"),
+            "<munder><munder accentunder=\"true\"><mi>a</mi><mo>⏟</mo></munder><mi>k</mi></munder>"
+        );
+        // integrals keep their limits to the side
+        assert_eq!(
+            inner("int_0^1"),
+            "<msubsup><mo>∫</mo><mn>0</mn><mn>1</mn></msubsup>"
+        );
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(inner("sqrt x"), "<msqrt><mi>x</mi></msqrt>");
+        assert_eq!(inner("root(3)(x)"), "<mroot><mi>x</mi><mn>3</mn></mroot>");
+    }
+
+    #[test]
+    fn fonts() {
+        assert_eq!(inner("bb(x)"), "<mi>𝐱</mi>");
+        assert_eq!(inner("bbb R"), "<mi>ℝ</mi>");
+        assert_eq!(
+            inner("cc(A+B)"),
+            "<mrow><mi>𝒜</mi><mo>+</mo><mi>ℬ</mi></mrow>"
+        );
+    }
+
+    #[test]
+    fn accents_and_functions() {
+        assert_eq!(
+            inner("hat x"),
+            "<mover accent=\"true\"><mi>x</mi><mo>^</mo></mover>"
+        );
+        assert_eq!(
+            inner("ubrace(a+b)"),
+            "<munder accentunder=\"true\"><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow><mo>⏟</mo></munder>"
+        );
+        assert_eq!(
+            inner("abs(x)"),
+            "<mrow><mo>|</mo><mi>x</mi><mo>|</mo></mrow>"
+        );
+        assert_eq!(inner("text(hi)"), "<mtext>hi</mtext>");
+        assert_eq!(
+            inner("sin x"),
+            "<mrow><mi>sin</mi><mo>&#x2061;</mo><mi>x</mi></mrow>"
+        );
+        assert_eq!(inner("f"), "<mi>f</mi>");
+        assert_eq!(inner("g^2"), "<msup><mi>g</mi><mn>2</mn></msup>");
+    }
+
+    #[test]
+    fn binaries() {
+        assert_eq!(
+            inner("overset(def)(=)"),
+            "<mover><mo>=</mo><mrow><mi>d</mi><mi>e</mi><mi>f</mi></mrow></mover>"
+        );
+        assert_eq!(
+            inner("color(red)(x)"),
+            "<mstyle mathcolor=\"red\"><mi>x</mi></mstyle>"
+        );
+        assert_eq!(
+            inner("color(#ff0000)(x)"),
+            "<mstyle mathcolor=\"#ff0000\"><mi>x</mi></mstyle>"
+        );
+        assert_eq!(
+            inner("class(big)(x)"),
+            "<mrow class=\"big\"><mi>x</mi></mrow>"
+        );
+        assert_eq!(
+            inner("class(a b)(x)"),
+            "<mrow class=\"a b\"><mi>x</mi></mrow>"
+        );
+    }
+
+    #[test]
+    fn groups_and_matrices() {
+        assert_eq!(inner("(x)"), "<mrow><mo>(</mo><mi>x</mi><mo>)</mo></mrow>");
+        assert_eq!(
+            inner("[[a,b],[c,d]]"),
+            "<mrow><mo>[</mo><mtable><mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr>\
+             <mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr></mtable><mo>]</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn block_display() {
+        let conf = Conf {
+            block: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            conf.parse("x").to_mathml(),
+            "<math display=\"block\"><mi>x</mi></math>"
+        );
+    }
+
+    #[test]
+    fn no_escapes() {
+        let conf = Conf {
+            highlight: true,
+            ansi_color: true,
+            ..Default::default()
+        };
+        let out = conf.parse("text(x+1) + x").to_mathml();
+        assert!(!out.contains('\x1b'), "{out:?}");
+        assert_eq!(
+            out,
+            "<math><mrow><mtext>x+1</mtext><mo>+</mo><mi>x</mi></mrow></math>"
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(render(""), "<math><mrow></mrow></math>");
+    }
+}
@@ -0,0 +1,355 @@
+//! Rendering with user defined symbols and macros
+
+use asciimath_parser::prefix_map::{HashPrefixMap, PrefixMap};
+use asciimath_parser::{Token, Tokenizer};
+use std::collections::HashMap;
+
+use super::tokens::TOKEN_MAP;
+use super::{Asciimath, Conf};
+
+/// How a user defined symbol is parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SymbolKind {
+    /// A standalone symbol like `hbar`
+    Symbol,
+    /// A function applied to the following argument like `sin`
+    Function,
+}
+
+impl SymbolKind {
+    fn token(self) -> Token {
+        match self {
+            // the output is already unicode, which `symbol_str` renders as itself
+            SymbolKind::Symbol => Token::Symbol,
+            SymbolKind::Function => Token::Function,
+        }
+    }
+}
+
+/// The built in tokens with user defined tokens taking precedence
+#[derive(Debug, Clone)]
+struct Layered(HashPrefixMap<String, Token>);
+
+impl PrefixMap<Token> for Layered {
+    fn get_longest_prefix<P: AsRef<str>>(&self, inp: P) -> Option<(usize, &Token)> {
+        let inp = inp.as_ref();
+        match (
+            self.0.get_longest_prefix(inp),
+            TOKEN_MAP.get_longest_prefix(inp),
+        ) {
+            (Some(custom), Some(builtin)) if builtin.0 > custom.0 => Some(builtin),
+            (Some(custom), _) => Some(custom),
+            (None, builtin) => builtin,
+        }
+    }
+}
+
+/// Split a macro body into literal text and the indices of its parameters
+fn body_parts(body: &str) -> impl Iterator<Item = Result<&str, usize>> {
+    let mut rest = body;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let mut search = 0;
+        while let Some(pos) = rest[search..].find('#').map(|pos| pos + search) {
+            match rest[pos + 1..].chars().next() {
+                Some(digit @ '1'..='9') if pos == 0 => {
+                    rest = &rest[2..];
+                    return Some(Err(digit as usize - '1' as usize));
+                }
+                Some('1'..='9') => {
+                    let (lit, remaining) = rest.split_at(pos);
+                    rest = remaining;
+                    return Some(Ok(lit));
+                }
+                _ => search = pos + 1,
+            }
+        }
+        let lit = rest;
+        rest = "";
+        Some(Ok(lit))
+    })
+}
+
+/// Take a macro argument, either the contents of a bracketed group or a single token
+fn take_arg<'a>(tokens: &mut impl Iterator<Item = (&'a str, Token)>) -> Vec<(&'a str, Token)> {
+    match tokens.next() {
+        Some((_, Token::OpenBracket)) => {
+            let mut depth = 0;
+            let mut arg = Vec::new();
+            for (text, tok) in tokens {
+                match tok {
+                    Token::OpenBracket => depth += 1,
+                    Token::CloseBracket if depth == 0 => break,
+                    Token::CloseBracket => depth -= 1,
+                    _ => {}
+                }
+                arg.push((text, tok));
+            }
+            arg
+        }
+        Some(token) => vec![token],
+        None => Vec::new(),
+    }
+}
+
+/// Keep an argument of several tokens together as an invisible group
+///
+/// Without it an argument like `a+b` spliced into `#1/2` would only divide `b`.
+fn grouped(arg: Vec<(&str, Token)>) -> Vec<(&str, Token)> {
+    if arg.len() > 1 {
+        let mut group = Vec::with_capacity(arg.len() + 2);
+        group.push(("{:", Token::OpenBracket));
+        group.extend(arg);
+        group.push((":}", Token::CloseBracket));
+        group
+    } else {
+        arg
+    }
+}
+
+/// Renders asciimath with user defined symbols and macros
+///
+/// Symbols are new tokens that render as fixed unicode, like `hbar` as `ℏ`. Macros are textual
+/// replacements applied before parsing, where `#1` through `#9` in the body are replaced by the
+/// arguments following the macro name. Arguments in brackets lose their outer brackets, any
+/// other argument is a single token. Arguments of several tokens are kept together in an
+/// invisible group, so `#1/2` divides the whole argument. User defined names take precedence
+/// over built in tokens of the same length.
+///
+/// ```
+/// use asciimath_unicode::{Conf, Renderer, SymbolKind};
+///
+/// let mut renderer = Renderer::new(Conf::default());
+/// renderer
+///     .add_symbol("hbar", "ℏ", SymbolKind::Symbol)
+///     .add_macro("ev", "bbb E[#1]");
+/// assert_eq!(renderer.parse("ev(X) = hbar^2").to_string(), "𝔼[X]=ℏ²");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    /// The configuration used for rendering
+    pub conf: Conf,
+    symbols: HashMap<String, (String, SymbolKind)>,
+    macros: HashMap<String, String>,
+    tokens: Layered,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer::new(Conf::default())
+    }
+}
+
+impl Renderer {
+    /// Create a renderer without any user defined symbols or macros
+    #[must_use]
+    pub fn new(conf: Conf) -> Self {
+        Renderer {
+            conf,
+            symbols: HashMap::new(),
+            macros: HashMap::new(),
+            tokens: Layered(HashPrefixMap::from_iter([])),
+        }
+    }
+
+    fn rebuild_tokens(&mut self) {
+        self.tokens = Layered(
+            self.symbols
+                .iter()
+                .map(|(name, &(_, kind))| (name.clone(), kind.token()))
+                .chain(self.macros.keys().map(|name| (name.clone(), Token::Ident)))
+                .collect(),
+        );
+    }
+
+    /// Add a symbol `name` that renders as `output`
+    ///
+    /// This replaces any previous symbol or macro with the same name.
+    pub fn add_symbol(
+        &mut self,
+        name: impl Into<String>,
+        output: impl Into<String>,
+        kind: SymbolKind,
+    ) -> &mut Self {
+        let name = name.into();
+        self.macros.remove(&name);
+        self.symbols.insert(name, (output.into(), kind));
+        self.rebuild_tokens();
+        self
+    }
+
+    /// Add a macro `name` that is replaced by the asciimath `body`
+    ///
+    /// This replaces any previous symbol or macro with the same name.
+    pub fn add_macro(&mut self, name: impl Into<String>, body: impl Into<String>) -> &mut Self {
+        let name = name.into();
+        self.symbols.remove(&name);
+        self.macros.insert(name, body.into());
+        self.rebuild_tokens();
+        self
+    }
+
+    fn tokenize<'a>(&'a self, inp: &'a str) -> Tokenizer<'a, 'a, Layered> {
+        Tokenizer::with_tokens(inp, &self.tokens, true)
+    }
+
+    fn expand<'a>(
+        &'a self,
+        mut tokens: impl Iterator<Item = (&'a str, Token)>,
+        active: &mut Vec<&'a str>,
+        out: &mut Vec<(&'a str, Token)>,
+    ) {
+        while let Some((text, tok)) = tokens.next() {
+            if let Some((name, body)) = self.macros.get_key_value(text)
+                && !active.contains(&name.as_str())
+            {
+                let num_args = body_parts(body)
+                    .filter_map(|part| part.err().map(|ind| ind + 1))
+                    .max()
+                    .unwrap_or(0);
+                // arguments are expanded where they're written, not inside the body
+                let args: Vec<_> = (0..num_args)
+                    .map(|_| {
+                        let mut arg = Vec::new();
+                        self.expand(take_arg(&mut tokens).into_iter(), active, &mut arg);
+                        grouped(arg)
+                    })
+                    .collect();
+                let mut expanded = Vec::new();
+                for part in body_parts(body) {
+                    match part {
+                        Ok(lit) => expanded.extend(self.tokenize(lit)),
+                        Err(ind) => expanded.extend_from_slice(&args[ind]),
+                    }
+                }
+                active.push(name);
+                self.expand(expanded.into_iter(), active, out);
+                active.pop();
+            } else if let Some((output, kind)) = self.symbols.get(text) {
+                out.push((output, kind.token()));
+            } else {
+                out.push((text, tok));
+            }
+        }
+    }
+
+    /// Parse an asciimath string into an [`Asciimath`] value that implements [`std::fmt::Display`]
+    #[must_use]
+    pub fn parse<'a>(&'a self, inp: &'a str) -> Asciimath<'a> {
+        let mut tokens = Vec::new();
+        self.expand(self.tokenize(inp), &mut Vec::new(), &mut tokens);
+        Asciimath {
+            conf: self.conf,
+            expr: asciimath_parser::parse_tokens(tokens),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Renderer, SymbolKind};
+    use crate::Conf;
+
+    fn render(renderer: &Renderer, inp: &str) -> String {
+        renderer.parse(inp).to_string()
+    }
+
+    #[test]
+    fn no_customization() {
+        let renderer = Renderer::default();
+        assert_eq!(render(&renderer, "x^2 + alpha"), "x²+α");
+    }
+
+    #[test]
+    fn symbols() {
+        let mut renderer = Renderer::default();
+        renderer
+            .add_symbol("hbar", "ℏ", SymbolKind::Symbol)
+            .add_symbol("degC", "℃", SymbolKind::Symbol);
+        assert_eq!(render(&renderer, "hbar omega"), "ℏω");
+        assert_eq!(render(&renderer, "20 degC"), "20℃");
+        assert_eq!(render(&renderer, "bb hbar_0"), "ℏ₀");
+    }
+
+    #[test]
+    fn functions() {
+        let mut renderer = Renderer::default();
+        renderer.add_symbol("erf", "erf", SymbolKind::Function);
+        assert_eq!(render(&renderer, "erf(x)"), "erf (x)");
+        assert_eq!(render(&renderer, "erf^2 x"), "erf² x");
+    }
+
+    #[test]
+    fn override_builtin() {
+        let mut renderer = Renderer::default();
+        renderer.add_symbol("oo", "∞∞", SymbolKind::Symbol);
+        assert_eq!(render(&renderer, "oo"), "∞∞");
+        // longer builtin tokens still win
+        renderer.add_symbol("E", "𝔼", SymbolKind::Symbol);
+        assert_eq!(render(&renderer, "E[X] EE x"), "𝔼[X]∃x");
+    }
+
+    #[test]
+    fn macros() {
+        let mut renderer = Renderer::default();
+        renderer
+            .add_macro("avg", "(: #1 :)")
+            .add_macro("pd", "(del #1)/(del #2)");
+        assert_eq!(render(&renderer, "avg(x+y)"), "⟨x+y⟩");
+        assert_eq!(render(&renderer, "avg x"), "⟨x⟩");
+        assert_eq!(render(&renderer, "pd(f)(x)"), "(∂f)/(∂x)");
+    }
+
+    #[test]
+    fn grouped_args() {
+        let mut renderer = Renderer::default();
+        renderer.add_macro("half", "#1/2");
+        assert_eq!(render(&renderer, "half(a+b)"), "ᵃ⁺ᵇ⁄₂");
+        assert_eq!(renderer.parse("half(a+b)").to_latex(), "\\frac{a+b}{2}");
+        assert_eq!(render(&renderer, "half x"), "ˣ⁄₂");
+    }
+
+    #[test]
+    fn nested_macros() {
+        let mut renderer = Renderer::default();
+        renderer
+            .add_symbol("hbar", "ℏ", SymbolKind::Symbol)
+            .add_macro("half", "#1/2")
+            .add_macro("E", "half(hbar) E");
+        assert_eq!(render(&renderer, "half(half x)"), "ˣ⁄₂/2");
+        // a macro isn't expanded inside its own body
+        assert_eq!(render(&renderer, "E"), "ℏ/2E");
+    }
+
+    #[test]
+    fn missing_args() {
+        let mut renderer = Renderer::default();
+        renderer.add_macro("pair", "(#1, #2)");
+        assert_eq!(render(&renderer, "pair a"), "(a,)");
+    }
+
+    #[test]
+    fn literal_hash() {
+        let mut renderer = Renderer::default();
+        renderer.add_macro("num", "#a #1");
+        assert_eq!(render(&renderer, "num 3"), "#a3");
+    }
+
+    #[test]
+    fn other_outputs() {
+        let mut renderer = Renderer::new(Conf {
+            block: true,
+            ..Conf::default()
+        });
+        renderer.add_symbol("hbar", "ℏ", SymbolKind::Symbol);
+        assert_eq!(
+            renderer.parse("hbar").to_mathml(),
+            "<math display=\"block\"><mi>ℏ</mi></math>"
+        );
+        // symbols render through the same tables as built in ones
+        assert_eq!(renderer.parse("hbar/2").to_latex(), "\\frac{\\hbar}{2}");
+    }
+}
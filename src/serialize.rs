@@ -0,0 +1,72 @@
+//! Serde support for types from other crates
+
+/// Serialize a [`SkinTone`][emojis::SkinTone] by the same kebab case names the cli uses
+///
+/// Tones for several people are named by each person's tone, like `light-and-dark`.
+pub mod skin_tone {
+    use emojis::SkinTone;
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::{self, Serializer};
+
+    const TONES: [(SkinTone, &str); 26] = [
+        (SkinTone::Default, "default"),
+        (SkinTone::Light, "light"),
+        (SkinTone::MediumLight, "medium-light"),
+        (SkinTone::Medium, "medium"),
+        (SkinTone::MediumDark, "medium-dark"),
+        (SkinTone::Dark, "dark"),
+        (SkinTone::LightAndMediumLight, "light-and-medium-light"),
+        (SkinTone::LightAndMedium, "light-and-medium"),
+        (SkinTone::LightAndMediumDark, "light-and-medium-dark"),
+        (SkinTone::LightAndDark, "light-and-dark"),
+        (SkinTone::MediumLightAndLight, "medium-light-and-light"),
+        (SkinTone::MediumLightAndMedium, "medium-light-and-medium"),
+        (
+            SkinTone::MediumLightAndMediumDark,
+            "medium-light-and-medium-dark",
+        ),
+        (SkinTone::MediumLightAndDark, "medium-light-and-dark"),
+        (SkinTone::MediumAndLight, "medium-and-light"),
+        (SkinTone::MediumAndMediumLight, "medium-and-medium-light"),
+        (SkinTone::MediumAndMediumDark, "medium-and-medium-dark"),
+        (SkinTone::MediumAndDark, "medium-and-dark"),
+        (SkinTone::MediumDarkAndLight, "medium-dark-and-light"),
+        (
+            SkinTone::MediumDarkAndMediumLight,
+            "medium-dark-and-medium-light",
+        ),
+        (SkinTone::MediumDarkAndMedium, "medium-dark-and-medium"),
+        (SkinTone::MediumDarkAndDark, "medium-dark-and-dark"),
+        (SkinTone::DarkAndLight, "dark-and-light"),
+        (SkinTone::DarkAndMediumLight, "dark-and-medium-light"),
+        (SkinTone::DarkAndMedium, "dark-and-medium"),
+        (SkinTone::DarkAndMediumDark, "dark-and-medium-dark"),
+    ];
+
+    /// The names of [`TONES`], for listing in errors
+    const NAMES: [&str; 26] = {
+        let mut names = [""; 26];
+        let mut ind = 0;
+        while ind < names.len() {
+            names[ind] = TONES[ind].1;
+            ind += 1;
+        }
+        names
+    };
+
+    #[allow(clippy::trivially_copy_pass_by_ref)] // serde's signature
+    pub fn serialize<S: Serializer>(tone: &SkinTone, serializer: S) -> Result<S::Ok, S::Error> {
+        match TONES.iter().find(|(known, _)| known == tone) {
+            Some((_, name)) => serializer.serialize_str(name),
+            None => Err(ser::Error::custom(format!("unknown skin tone {tone:?}"))),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SkinTone, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match TONES.iter().find(|(_, known)| *known == name) {
+            Some(&(tone, _)) => Ok(tone),
+            None => Err(de::Error::unknown_variant(&name, &NAMES)),
+        }
+    }
+}
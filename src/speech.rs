@@ -0,0 +1,908 @@
+//! Rendering of asciimath as text for reading aloud
+
+use asciimath_parser::tree::{
+    Expression, Frac, Func, Group, Intermediate, Matrix, Script, ScriptFunc, Simple, SimpleBinary,
+    SimpleFunc, SimpleScript, SimpleUnary,
+};
+use std::fmt;
+use std::fmt::Write;
+
+use super::Conf;
+use super::ast::{extract_raw_text, extract_rows, extract_text, split_word};
+use super::block::{is_integral, is_underover};
+use super::tokens::{left_bracket_str, right_bracket_str, symbol_str};
+
+/// Joins spoken words with single spaces
+#[derive(Debug)]
+pub struct Speech<'a, W: ?Sized> {
+    inner: &'a mut W,
+    started: bool,
+    /// Set while speaking the subscript of a limit, where arrows read as "approaches"
+    limit: bool,
+}
+
+impl<'a, W: Write + ?Sized> Speech<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Speech {
+            inner,
+            started: false,
+            limit: false,
+        }
+    }
+
+    fn word(&mut self, word: &str) -> fmt::Result {
+        if word.is_empty() {
+            return Ok(());
+        }
+        if self.started {
+            self.inner.write_char(' ')?;
+        }
+        self.started = true;
+        self.inner.write_str(word)
+    }
+
+    /// Punctuation attached to the previous word, giving a pause
+    fn pause(&mut self, punct: char) -> fmt::Result {
+        if self.started {
+            self.inner.write_char(punct)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The spoken form of a symbol or identifier, by its asciimath name
+#[allow(clippy::too_many_lines)]
+fn spoken_symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        // arithmetic
+        "+" => "plus",
+        "-" => "minus",
+        "!" => "factorial",
+        "/" | "//" => "slash",
+        "*" | "cdot" | "xx" | "times" => "times",
+        "**" | "ast" => "asterisk",
+        "***" | "star" => "star",
+        "\\\\" | "backslash" => "backslash",
+        "setminus" => "set minus",
+        "|><" | "ltimes" => "left semidirect product",
+        "><|" | "rtimes" => "right semidirect product",
+        "|><|" | "bowtie" => "bowtie",
+        "-:" | "div" | "divide" => "divided by",
+        "@" | "circ" => "composed with",
+        "o+" | "oplus" => "circled plus",
+        "ox" | "otimes" => "circled times",
+        "o." | "odot" => "circled dot",
+        "ominus" => "circled minus",
+        "oslash" => "circled slash",
+        "sum" => "sum",
+        "prod" => "product",
+        "^^" | "wedge" | "land" | "^^^" | "bigwedge" => "and",
+        "vv" | "vee" | "lor" | "vvv" | "bigvee" => "or",
+        "nn" | "cap" => "intersect",
+        "nnn" | "bigcap" => "intersection",
+        "uu" | "cup" | "uuu" | "bigcup" => "union",
+        // relations
+        "=" => "equals",
+        "!=" | "ne" => "is not equal to",
+        "<" | "lt" => "is less than",
+        "<=" | "le" | "lt=" | "leq" => "is less than or equal to",
+        ">" | "gt" => "is greater than",
+        ">=" | "ge" | "gt=" | "geq" => "is greater than or equal to",
+        "mlt" | "ll" => "is much less than",
+        "mgt" | "gg" => "is much greater than",
+        "-<" | "prec" | "-lt" => "precedes",
+        ">-" | "succ" => "succeeds",
+        "-<=" | "preceq" => "precedes or equals",
+        ">-=" | "succeq" => "succeeds or equals",
+        "in" => "is in",
+        "!in" | "notin" => "is not in",
+        "sub" | "subset" => "is a subset of",
+        "sup" | "supset" => "is a superset of",
+        "sube" | "subseteq" => "is a subset of or equal to",
+        "supe" | "supseteq" => "is a superset of or equal to",
+        "!sub" | "nsub" => "is not a subset of",
+        "!sup" | "nsup" => "is not a superset of",
+        "!sube" | "nsubseteq" => "is not a subset of or equal to",
+        "!supe" | "nsupseteq" => "is not a superset of or equal to",
+        "-=" | "equiv" => "is equivalent to",
+        "~=" | "cong" => "is congruent to",
+        "~~" | "approx" => "is approximately equal to",
+        "~" | "sim" => "is similar to",
+        "prop" | "propto" => "is proportional to",
+        ":=" => "is defined as",
+        // logic
+        "not" | "neg" => "not",
+        "=>" | "implies" => "implies",
+        "<=>" | "iff" => "if and only if",
+        "AA" | "forall" => "for all",
+        "EE" | "exists" => "there exists",
+        "!EE" | "notexists" => "there does not exist",
+        "_|_" | "bot" => "bottom",
+        "TT" | "top" => "top",
+        "|--" | "vdash" => "proves",
+        "|==" | "models" => "models",
+        // misc
+        ":|:" => "such that",
+        "int" => "integral",
+        "oint" => "contour integral",
+        "iint" => "double integral",
+        "iiint" => "triple integral",
+        "oiint" => "surface integral",
+        "oiiint" => "volume integral",
+        "del" | "partial" => "partial",
+        "grad" | "nabla" => "nabla",
+        "+-" | "pm" => "plus or minus",
+        "-+" | "mp" => "minus or plus",
+        "O/" | "emptyset" => "the empty set",
+        "oo" | "infty" => "infinity",
+        "..." | "ldots" | "cdots" => "dot dot dot",
+        "vdots" => "vertical dots",
+        "ddots" => "diagonal dots",
+        ":." | "therefore" => "therefore",
+        ":'" | "because" => "because",
+        "/_" | "angle" => "angle",
+        "/_\\" | "triangle" => "triangle",
+        "'" | "prime" => "prime",
+        "\\ " | "quad" | "qquad" => "",
+        "CC" => "the complex numbers",
+        "NN" => "the natural numbers",
+        "QQ" => "the rational numbers",
+        "RR" => "the real numbers",
+        "ZZ" => "the integers",
+        // arrows
+        "uarr" | "uparrow" => "up arrow",
+        "uArr" | "Uparrow" => "double up arrow",
+        "darr" | "downarrow" => "down arrow",
+        "dArr" | "Downarrow" => "double down arrow",
+        "rarr" | "rightarrow" | "->" => "right arrow",
+        ">->" | "rightarrowtail" => "right arrow with tail",
+        "->>" | "twoheadrightarrow" => "two headed right arrow",
+        ">->>" | "twoheadrightarrowtail" => "two headed right arrow with tail",
+        "|->" | "mapsto" => "maps to",
+        "larr" | "leftarrow" | "<-" => "left arrow",
+        "harr" | "leftrightarrow" | "<->" => "left right arrow",
+        "rArr" | "Rightarrow" | "==>" => "double right arrow",
+        "lArr" | "Leftarrow" | "<==" => "double left arrow",
+        "hArr" | "Leftrightarrow" | "<==>" => "double left right arrow",
+        // identifiers
+        "dx" => "d x",
+        "dy" => "d y",
+        "dz" => "d z",
+        "dt" => "d t",
+        "lim" | "Lim" => "limit",
+        "dim" => "dimension",
+        "lub" => "least upper bound",
+        "glb" => "greatest lower bound",
+        "min" => "minimum",
+        "max" => "maximum",
+        _ => return None,
+    })
+}
+
+/// Whether `func` is a lone prime, like the `'` of `f'(x)`
+fn is_prime(func: &ScriptFunc<'_>) -> bool {
+    matches!(
+        func,
+        ScriptFunc::Simple(SimpleScript {
+            simple: Simple::Symbol("'" | "prime"),
+            script: Script::None,
+        })
+    )
+}
+
+/// The spoken name of a function
+fn spoken_function(name: &str) -> &str {
+    match name {
+        "sin" | "Sin" => "sine",
+        "cos" | "Cos" => "cosine",
+        "tan" | "Tan" => "tangent",
+        "cot" | "Cot" => "cotangent",
+        "sec" | "Sec" => "secant",
+        "csc" | "Csc" => "cosecant",
+        "sinh" | "Sinh" => "hyperbolic sine",
+        "cosh" | "Cosh" => "hyperbolic cosine",
+        "tanh" | "Tanh" => "hyperbolic tangent",
+        "coth" => "hyperbolic cotangent",
+        "sech" => "hyperbolic secant",
+        "csch" => "hyperbolic cosecant",
+        "arcsin" | "Arcsin" => "arc sine",
+        "arccos" | "Arccos" => "arc cosine",
+        "arctan" | "Arctan" => "arc tangent",
+        "exp" => "exponential",
+        "log" | "Log" => "log",
+        "ln" | "Ln" => "natural log",
+        "det" => "determinant",
+        "gcd" => "greatest common divisor",
+        "lcm" => "least common multiple",
+        name => name,
+    }
+}
+
+/// The spoken names of a pair of brackets
+fn spoken_brackets(left: &str, right: &str) -> (&'static str, &'static str) {
+    let left = match left {
+        "(" | "left(" => "open paren",
+        "[" | "left[" => "open bracket",
+        "{" => "open brace",
+        "(:" | "langle" | "<<" => "open angle bracket",
+        "|__" | "lfloor" => "open floor",
+        "|~" | "lceiling" => "open ceiling",
+        "|:" | "|" => "vertical bar",
+        _ => "",
+    };
+    let right = match right {
+        ")" | "right)" => "close paren",
+        "]" | "right]" => "close bracket",
+        "}" => "close brace",
+        ":)" | "rangle" | ">>" => "close angle bracket",
+        "__|" | "rfloor" => "close floor",
+        "~|" | "rceiling" => "close ceiling",
+        ":|" | "|" => "vertical bar",
+        _ => "",
+    };
+    (left, right)
+}
+
+/// An ordinal like `3rd` or `nth` for the index of a root
+fn ordinal(index: &str) -> String {
+    let suffix = match index.parse::<u64>() {
+        Ok(num) if (11..=13).contains(&(num % 100)) => "th",
+        Ok(num) if num % 10 == 1 => "st",
+        Ok(num) if num % 10 == 2 => "nd",
+        Ok(num) if num % 10 == 3 => "rd",
+        _ => "th",
+    };
+    format!("{index}{suffix}")
+}
+
+/// Speak a fraction, marking where it ends unless both parts are a single word
+fn speech_fraction<W: Write + ?Sized>(
+    atomic: bool,
+    out: &mut Speech<'_, W>,
+    numer: impl FnOnce(&mut Speech<'_, W>) -> fmt::Result,
+    denom: impl FnOnce(&mut Speech<'_, W>) -> fmt::Result,
+) -> fmt::Result {
+    if !atomic {
+        out.word("the fraction")?;
+    }
+    numer(out)?;
+    out.word("over")?;
+    denom(out)?;
+    if !atomic {
+        out.pause(',')?;
+        out.word("end fraction")?;
+    }
+    Ok(())
+}
+
+impl Conf {
+    /// The contents of `simple`, without brackets if they'd be stripped
+    fn stripped<'e, 'a>(
+        self,
+        simple: &'e Simple<'a>,
+    ) -> Result<&'e Expression<'a>, &'e Simple<'a>> {
+        match simple {
+            Simple::Group(Group { expr, .. }) if self.strip_brackets => Ok(expr),
+            simple => Err(simple),
+        }
+    }
+
+    /// Whether `simple` is spoken as a single word, so it needs no end marker
+    fn speech_atomic(self, simple: &Simple<'_>) -> bool {
+        match self.stripped(simple) {
+            Ok(expr) => matches!(
+                &**expr,
+                [Intermediate::ScriptFunc(ScriptFunc::Simple(SimpleScript {
+                    simple: Simple::Number(_) | Simple::Ident(_) | Simple::Symbol(_),
+                    script: Script::None,
+                }))]
+            ),
+            Err(simple) => matches!(
+                simple,
+                Simple::Number(_) | Simple::Ident(_) | Simple::Symbol(_) | Simple::Text(_)
+            ),
+        }
+    }
+
+    /// Speak an argument, stripping brackets, and follow it with `end` unless it's one word
+    fn speech_arg(
+        self,
+        simple: &Simple<'_>,
+        end: &str,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match self.stripped(simple) {
+            Ok(expr) => self.speech_expression(expr, out)?,
+            Err(simple) => self.speech_simple(simple, out)?,
+        }
+        if end.is_empty() || self.speech_atomic(simple) {
+            Ok(())
+        } else {
+            out.pause(',')?;
+            out.word(end)
+        }
+    }
+
+    fn speech_simpleunary(
+        self,
+        unary: &SimpleUnary<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        let prefix = match unary.op {
+            "sqrt" => Some(("the square root of", "end root")),
+            "abs" | "Abs" => Some(("the absolute value of", "end absolute value")),
+            "norm" => Some(("the norm of", "end norm")),
+            "floor" => Some(("the floor of", "end floor")),
+            "ceil" => Some(("the ceiling of", "end ceiling")),
+            "bb" | "mathbf" => Some(("bold", "end bold")),
+            "bbb" | "mathbb" => Some(("double struck", "end double struck")),
+            "cc" | "mathcal" => Some(("script", "end script")),
+            "tt" | "mathtt" => Some(("monospace", "end monospace")),
+            "fr" | "mathfrak" => Some(("fraktur", "end fraktur")),
+            "sf" | "mathsf" => Some(("sans serif", "end sans serif")),
+            "it" | "mathit" => Some(("italic", "end italic")),
+            "cancel" => Some(("cancelled", "end cancelled")),
+            "underline" | "ul" => Some(("underlined", "end underline")),
+            "text" | "mbox" => return out.word(&extract_text(unary.arg())),
+            _ => None,
+        };
+        if let Some((prefix, end)) = prefix {
+            out.word(prefix)?;
+            return self.speech_arg(unary.arg(), end, out);
+        }
+        let suffix = match unary.op {
+            "hat" => "hat",
+            "bar" | "overline" => "bar",
+            "vec" => "vector",
+            "dot" => "dot",
+            "ddot" => "double dot",
+            "tilde" => "tilde",
+            "overarc" | "overparen" => "arc",
+            "ubrace" | "underbrace" => "with underbrace",
+            "obrace" | "overbrace" => "with overbrace",
+            op => {
+                out.word(op)?;
+                return self.speech_simple(unary.arg(), out);
+            }
+        };
+        self.speech_arg(unary.arg(), "", out)?;
+        out.word(suffix)
+    }
+
+    fn speech_simplebinary(
+        self,
+        binary: &SimpleBinary<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match (binary.op, binary.first(), binary.second()) {
+            ("frac", numer, denom) => {
+                let atomic = self.speech_atomic(numer) && self.speech_atomic(denom);
+                speech_fraction(
+                    atomic,
+                    out,
+                    |out| self.speech_arg(numer, "", out),
+                    |out| self.speech_arg(denom, "", out),
+                )
+            }
+            ("root", index, arg) => {
+                match self.stripped(index) {
+                    Ok(_) if !self.speech_atomic(index) => {
+                        out.word("the root with index")?;
+                        self.speech_arg(index, "", out)?;
+                        out.pause(',')?;
+                        out.word("of")?;
+                    }
+                    _ => {
+                        let name = extract_raw_text(index);
+                        match name.as_str() {
+                            "2" => out.word("the square root of")?,
+                            "3" => out.word("the cube root of")?,
+                            _ => {
+                                out.word("the")?;
+                                out.word(&ordinal(&name))?;
+                                out.word("root of")?;
+                            }
+                        }
+                    }
+                }
+                self.speech_arg(arg, "end root", out)
+            }
+            ("stackrel" | "overset", over, base) => {
+                self.speech_arg(base, "", out)?;
+                out.word("with")?;
+                self.speech_arg(over, "", out)?;
+                out.word("above")
+            }
+            ("underset", under, base) => {
+                self.speech_arg(base, "", out)?;
+                out.word("with")?;
+                self.speech_arg(under, "", out)?;
+                out.word("below")
+            }
+            // colors, ids, and classes aren't spoken
+            ("color" | "id" | "class", _, arg) => self.speech_arg(arg, "", out),
+            (op, first, second) => {
+                out.word(op)?;
+                self.speech_simple(first, out)?;
+                self.speech_simple(second, out)
+            }
+        }
+    }
+
+    fn speech_simplefunc(
+        self,
+        func: &SimpleFunc<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.word(spoken_function(func.func))?;
+        if !matches!(func.arg(), Simple::Missing) {
+            out.word("of")?;
+            self.speech_arg(func.arg(), "", out)?;
+        }
+        Ok(())
+    }
+
+    fn speech_group(
+        self,
+        group: &Group<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        if let ("{", "") = (
+            left_bracket_str(group.left_bracket),
+            right_bracket_str(group.right_bracket),
+        ) && let Some(rows) = extract_rows(&group.expr)
+            && rows[0].len() > 1
+        {
+            return self.speech_cases(&rows, out);
+        }
+        match (group.left_bracket, group.right_bracket) {
+            ("|:" | "|", ":|" | "|") => {
+                out.word("the absolute value of")?;
+                self.speech_expression(&group.expr, out)?;
+                out.pause(',')?;
+                out.word("end absolute value")
+            }
+            (left, right) => {
+                let (left, right) = spoken_brackets(left, right);
+                out.word(left)?;
+                self.speech_expression(&group.expr, out)?;
+                out.word(right)
+            }
+        }
+    }
+
+    /// Piecewise cases like `{(x, if x > 0), (-x, otherwise):}`, read one case at a time
+    fn speech_cases(
+        self,
+        rows: &[Vec<&[Intermediate<'_>]>],
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.word(&format!("the {} cases", rows.len()))?;
+        for (i, row) in rows.iter().enumerate() {
+            out.pause(';')?;
+            out.word(&format!("case {}", i + 1))?;
+            out.pause(':')?;
+            let (value, conditions) = row
+                .split_first()
+                .unwrap_or_else(|| unreachable!("cases have two or more cells per row"));
+            self.speech_expression(value, out)?;
+            for cond in conditions {
+                out.pause(',')?;
+                self.speech_condition(cond, out)?;
+            }
+        }
+        out.pause(';')?;
+        out.word("end cases")
+    }
+
+    /// A condition of a case, with words like `otherwise` read as words rather than letters
+    fn speech_condition(
+        self,
+        mut cond: &[Intermediate<'_>],
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        while !cond.is_empty() {
+            if let Some((word, rest)) = split_word(cond) {
+                out.word(&word)?;
+                cond = rest;
+            } else {
+                self.speech_expression(&cond[..1], out)?;
+                cond = &cond[1..];
+            }
+        }
+        Ok(())
+    }
+
+    fn speech_matrix(
+        self,
+        matrix: &Matrix<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.word(&format!(
+            "the {} by {} matrix",
+            matrix.num_rows(),
+            matrix.num_cols()
+        ))?;
+        for (i, row) in matrix.rows().enumerate() {
+            out.pause(';')?;
+            out.word(&format!("row {}", i + 1))?;
+            out.pause(':')?;
+            for (j, expr) in row.iter().enumerate() {
+                if j > 0 {
+                    out.pause(',')?;
+                }
+                self.speech_expression(expr, out)?;
+            }
+        }
+        out.pause(';')?;
+        out.word("end matrix")
+    }
+
+    fn speech_name(self, name: &str, out: &mut Speech<'_, impl Write + ?Sized>) -> fmt::Result {
+        if out.limit && matches!(name, "->" | "rarr" | "rightarrow" | "to") {
+            return out.word("approaches");
+        }
+        if let Some(spoken) = spoken_symbol(name) {
+            return out.word(spoken);
+        }
+        if let Some(code) = name
+            .strip_prefix(':')
+            .and_then(|name| name.strip_suffix(':'))
+            && let Some(emoji) = emojis::get_by_shortcode(code)
+        {
+            return out.word(emoji.name());
+        }
+        if name.len() > 1 && name.chars().all(|chr| chr.is_ascii_alphabetic()) {
+            // greek letters and other named symbols read as their names
+            let lower = name.to_ascii_lowercase();
+            let base = lower.strip_prefix("var").unwrap_or(&lower);
+            let base = match base {
+                "epsi" => "epsilon",
+                "lamda" => "lambda",
+                base => base,
+            };
+            if name.starts_with(|chr: char| chr.is_ascii_uppercase()) {
+                out.word("capital")?;
+            }
+            out.word(base)
+        } else {
+            out.word(symbol_str(name, self.skin_tone))
+        }
+    }
+
+    fn speech_simple(
+        self,
+        simple: &Simple<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match simple {
+            Simple::Missing => Ok(()),
+            &Simple::Number(num) => out.word(num),
+            &Simple::Text(text) => out.word(text),
+            &Simple::Ident(ident) => match spoken_symbol(ident) {
+                Some(spoken) => out.word(spoken),
+                None => out.word(ident),
+            },
+            // separators are pauses rather than words
+            Simple::Symbol(",") => out.pause(','),
+            &Simple::Symbol(symbol) => self.speech_name(symbol, out),
+            Simple::Func(func) => self.speech_simplefunc(func, out),
+            Simple::Unary(unary) => self.speech_simpleunary(unary, out),
+            Simple::Binary(binary) => self.speech_simplebinary(binary, out),
+            Simple::Group(group) => self.speech_group(group, out),
+            Simple::Matrix(matrix) => self.speech_matrix(matrix, out),
+        }
+    }
+
+    /// Speak scripts after their base, like "squared" or "sub i"
+    fn speech_script(
+        self,
+        script: &Script<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        if let Some(sub) = script.sub() {
+            out.word("sub")?;
+            self.speech_arg(sub, "end sub", out)?;
+        }
+        if let Some(sup) = script.sup() {
+            match sup {
+                Simple::Number("2") => out.word("squared")?,
+                Simple::Number("3") => out.word("cubed")?,
+                Simple::Symbol("'" | "prime") => out.word("prime")?,
+                sup => {
+                    out.word("to the")?;
+                    self.speech_arg(sup, "end exponent", out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Speak a large operator with limits, like "the sum from i equals 1 to n of"
+    fn speech_limits(
+        self,
+        base: &Simple<'_>,
+        script: &Script<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.word("the")?;
+        self.speech_simple(base, out)?;
+        let limit = matches!(base, Simple::Ident("lim" | "Lim"));
+        if let Some(sub) = script.sub() {
+            out.word(if limit {
+                "as"
+            } else if script.sup().is_some() {
+                "from"
+            } else {
+                "over"
+            })?;
+            let outer = out.limit;
+            out.limit = limit;
+            self.speech_arg(sub, "", out)?;
+            out.limit = outer;
+        }
+        if let Some(sup) = script.sup() {
+            out.word("to")?;
+            self.speech_arg(sup, "", out)?;
+        }
+        out.word("of")
+    }
+
+    fn speech_func(
+        self,
+        func: &Func<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.word(spoken_function(func.func))?;
+        self.speech_script(&func.script, out)?;
+        if let ScriptFunc::Simple(SimpleScript {
+            simple: Simple::Missing,
+            script: Script::None,
+        }) = func.arg()
+        {
+            return Ok(());
+        }
+        self.speech_func_arg(func.arg(), out)
+    }
+
+    fn speech_func_arg(
+        self,
+        arg: &ScriptFunc<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.word("of")?;
+        match arg {
+            // brackets around a single word aren't spoken, like "f of x"
+            ScriptFunc::Simple(SimpleScript {
+                simple,
+                script: Script::None,
+            }) if self.speech_atomic(simple) => self.speech_arg(simple, "", out),
+            arg => self.speech_scriptfunc(arg, out),
+        }
+    }
+
+    /// Speak a function with primes, like "f prime of x" for `f'(x)`, returning how many of
+    /// the intermediates after it were spoken too
+    ///
+    /// The parser takes the first prime as the argument of the function, with any further
+    /// primes and the bracketed argument following it.
+    fn speech_primed_func(
+        self,
+        func: &Func<'_>,
+        rest: &[Intermediate<'_>],
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> Result<usize, fmt::Error> {
+        out.word(spoken_function(func.func))?;
+        self.speech_script(&func.script, out)?;
+        out.word("prime")?;
+        let primes = rest
+            .iter()
+            .take_while(|inter| matches!(inter, Intermediate::ScriptFunc(arg) if is_prime(arg)))
+            .count();
+        for _ in 0..primes {
+            out.word("prime")?;
+        }
+        match rest.get(primes) {
+            Some(Intermediate::ScriptFunc(
+                arg @ ScriptFunc::Simple(SimpleScript {
+                    simple: Simple::Group(_),
+                    script: Script::None,
+                }),
+            )) => {
+                self.speech_func_arg(arg, out)?;
+                Ok(primes + 1)
+            }
+            _ => Ok(primes),
+        }
+    }
+
+    fn speech_scriptfunc(
+        self,
+        func: &ScriptFunc<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match func {
+            ScriptFunc::Simple(SimpleScript { simple, script })
+                if !matches!(script, Script::None)
+                    && !matches!(simple, Simple::Unary(_))
+                    && (is_underover(simple) || is_integral(simple)) =>
+            {
+                self.speech_limits(simple, script, out)
+            }
+            // the script of a brace labels it rather than bounding an operand
+            ScriptFunc::Simple(SimpleScript {
+                simple: simple @ Simple::Unary(unary),
+                script,
+            }) if is_underover(simple) => {
+                self.speech_simpleunary(unary, out)?;
+                for label in [script.sub(), script.sup()].into_iter().flatten() {
+                    out.word("labelled")?;
+                    self.speech_arg(label, "end label", out)?;
+                }
+                Ok(())
+            }
+            ScriptFunc::Simple(SimpleScript { simple, script }) => {
+                self.speech_simple(simple, out)?;
+                self.speech_script(script, out)
+            }
+            ScriptFunc::Func(func) => self.speech_func(func, out),
+        }
+    }
+
+    /// A fraction operand, stripping brackets from an unscripted group
+    fn speech_frac_arg(
+        self,
+        func: &ScriptFunc<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match func {
+            ScriptFunc::Simple(SimpleScript {
+                simple,
+                script: Script::None,
+            }) => self.speech_arg(simple, "", out),
+            func => self.speech_scriptfunc(func, out),
+        }
+    }
+
+    fn speech_frac(
+        self,
+        frac: &Frac<'_>,
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        let atomic = [&frac.numer, &frac.denom].into_iter().all(|part| {
+            matches!(part, ScriptFunc::Simple(SimpleScript { simple, script: Script::None })
+                if self.speech_atomic(simple))
+        });
+        speech_fraction(
+            atomic,
+            out,
+            |out| self.speech_frac_arg(&frac.numer, out),
+            |out| self.speech_frac_arg(&frac.denom, out),
+        )
+    }
+
+    pub(crate) fn speech_expression(
+        self,
+        expr: &[Intermediate<'_>],
+        out: &mut Speech<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        let mut rest = expr;
+        while let Some((inter, tail)) = rest.split_first() {
+            rest = tail;
+            match inter {
+                Intermediate::ScriptFunc(ScriptFunc::Func(func)) if is_prime(func.arg()) => {
+                    let spoken = self.speech_primed_func(func, rest, out)?;
+                    rest = &rest[spoken..];
+                }
+                Intermediate::ScriptFunc(func) => self.speech_scriptfunc(func, out)?,
+                Intermediate::Frac(frac) => self.speech_frac(frac, out)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Conf;
+
+    fn speak(inp: &str) -> String {
+        Conf::default().parse(inp).to_speech()
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(
+            speak("alpha + Delta <= oo"),
+            "alpha plus capital delta is less than or equal to infinity"
+        );
+        assert_eq!(speak("x -> y"), "x right arrow y");
+        assert_eq!(
+            speak("AA x in RR, x >= 0"),
+            "for all x is in the real numbers, x is greater than or equal to 0"
+        );
+        assert_eq!(speak(":smile:"), "grinning face with smiling eyes");
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(speak("1/7"), "1 over 7");
+        assert_eq!(
+            speak("(a+b)/c"),
+            "the fraction a plus b over c, end fraction"
+        );
+        assert_eq!(speak("1/x_i"), "the fraction 1 over x sub i, end fraction");
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(speak("x^2 + y_i^3"), "x squared plus y sub i cubed");
+        assert_eq!(speak("e^(i pi)"), "e to the i pi, end exponent");
+        assert_eq!(speak("x'"), "x prime");
+    }
+
+    #[test]
+    fn limits() {
+        assert_eq!(speak("sum_(i=1)^n i"), "the sum from i equals 1 to n of i");
+        assert_eq!(
+            speak("lim_(x->0) f(x)"),
+            "the limit as x approaches 0 of f of x"
+        );
+        assert_eq!(speak("int_0^1 x dx"), "the integral from 0 to 1 of x d x");
+        // braces are labelled rather than taking an operand
+        assert_eq!(speak("obrace(a+b)^n"), "a plus b with overbrace labelled n");
+        assert_eq!(
+            speak("ubrace(a+b)_(n-1) + c"),
+            "a plus b with underbrace labelled n minus 1, end label plus c"
+        );
+    }
+
+    #[test]
+    fn accents_and_roots() {
+        assert_eq!(speak("vec x"), "x vector");
+        assert_eq!(speak("bb x"), "bold x");
+        assert_eq!(speak("sqrt(x+1)"), "the square root of x plus 1, end root");
+        assert_eq!(speak("root 3 x"), "the cube root of x");
+        assert_eq!(speak("root n x"), "the nth root of x");
+        assert_eq!(speak("|x|"), "the absolute value of x, end absolute value");
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(speak("sin^2 x"), "sine squared of x");
+        assert_eq!(speak("ln x"), "natural log of x");
+        assert_eq!(speak("f'(x)"), "f prime of x");
+        assert_eq!(
+            speak("f''(x+1)"),
+            "f prime prime of open paren x plus 1 close paren"
+        );
+        assert_eq!(speak("f' + g'"), "f prime plus g prime");
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(speak("text(if x)"), "if x");
+        assert_eq!(speak("1 text( for  all ) x"), "1 for all x");
+    }
+
+    #[test]
+    fn cases() {
+        assert_eq!(
+            speak("{(x, if x > 0), (-x, otherwise):}"),
+            "the 2 cases; case 1: x, if x is greater than 0; case 2: minus x, otherwise; end cases"
+        );
+        assert_eq!(
+            speak("{(1, x in A), (0, text(otherwise)):}"),
+            "the 2 cases; case 1: 1, x is in A; case 2: 0, otherwise; end cases"
+        );
+    }
+
+    #[test]
+    fn matrices() {
+        assert_eq!(
+            speak("[[a,b],[c,d]]"),
+            "the 2 by 2 matrix; row 1: a, b; row 2: c, d; end matrix"
+        );
+    }
+}
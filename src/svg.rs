@@ -0,0 +1,281 @@
+//! Rendering of block layouts as svg images
+
+use asciimath_parser::tree::Expression;
+use std::fmt;
+use std::fmt::Write;
+use unicode_width::UnicodeWidthChar;
+
+use super::Conf;
+use super::block::{Block, BlockStyle};
+use super::html::escape;
+
+/// The font size of text in pixels
+const FONT_SIZE: usize = 20;
+/// The width of one display column, the advance of a monospace font at [`FONT_SIZE`]
+const CELL_WIDTH: usize = 12;
+/// The height of one line
+const CELL_HEIGHT: usize = 24;
+/// The distance from the top of a line to the text baseline
+const ASCENT: usize = 18;
+
+/// The grapheme-ish clusters of a line with their starting columns and widths
+///
+/// Zero width characters like combining accents stay with the character they modify.
+fn cells(line: &str) -> Vec<(usize, usize, &str)> {
+    let mut starts: Vec<(usize, usize, usize)> = Vec::new();
+    let mut col = 0;
+    for (ind, chr) in line.char_indices() {
+        let width = chr.width().unwrap_or_default();
+        if width > 0 || starts.is_empty() {
+            starts.push((col, width, ind));
+        }
+        col += width;
+    }
+    let ends = starts.iter().skip(1).map(|&(_, _, ind)| ind);
+    starts
+        .iter()
+        .zip(ends.chain([line.len()]))
+        .map(|(&(col, width, start), end)| (col, width, &line[start..end]))
+        .collect()
+}
+
+/// A straight line between two points in pixels
+type Stroke = (usize, usize, usize, usize);
+
+/// Writes the strokes and positioned text of a block
+#[derive(Debug, Default)]
+struct Canvas {
+    texts: Vec<(usize, usize, String)>,
+    strokes: Vec<Stroke>,
+}
+
+impl Canvas {
+    fn text(&mut self, col: usize, row: usize, text: String) {
+        if !text.is_empty() {
+            self.texts.push((col, row, text));
+        }
+    }
+
+    /// Lay out `block`, drawing fraction bars, radicals, and matrix rules as strokes
+    fn draw(block: &Block) -> Self {
+        let rows: Vec<_> = block.lines().iter().map(|line| cells(line)).collect();
+        // the vinculum of a radical is the run of `─` right of the top of its stem
+        let is_vinculum = |row: usize, col: usize| {
+            rows[row]
+                .iter()
+                .any(|&(stem, _, cluster)| stem + 1 == col && cluster == "╱")
+        };
+        let mut canvas = Canvas::default();
+        for (row, cells) in rows.iter().enumerate() {
+            let top = row * CELL_HEIGHT;
+            let bottom = top + CELL_HEIGHT;
+            let mut start = 0;
+            let mut run = String::new();
+            let mut iter = cells.iter().peekable();
+            while let Some(&(col, width, cluster)) = iter.next() {
+                let left = col * CELL_WIDTH;
+                let right = left + width * CELL_WIDTH;
+                let drawn = match cluster {
+                    "─" => {
+                        // extend over the whole run of the same glyph
+                        let mut end = right;
+                        while let Some(&(_, next_width, _)) =
+                            iter.next_if(|&&(_, _, next)| next == cluster)
+                        {
+                            end += next_width * CELL_WIDTH;
+                        }
+                        let height = if is_vinculum(row, col) {
+                            top
+                        } else {
+                            top + CELL_HEIGHT / 2
+                        };
+                        Some((left, height, end, height))
+                    }
+                    "╱" => Some((left, bottom, right, top)),
+                    "╲" => Some((left, top, right, bottom)),
+                    "│" => {
+                        let center = left + CELL_WIDTH / 2;
+                        Some((center, top, center, bottom))
+                    }
+                    "┼" => {
+                        let center = left + CELL_WIDTH / 2;
+                        canvas.strokes.push((center, top, center, bottom));
+                        let middle = top + CELL_HEIGHT / 2;
+                        Some((left, middle, right, middle))
+                    }
+                    _ => None,
+                };
+                if let Some(stroke) = drawn {
+                    canvas.text(start, row, std::mem::take(&mut run));
+                    canvas.strokes.push(stroke);
+                } else if cluster == " " {
+                    canvas.text(start, row, std::mem::take(&mut run));
+                } else if width == 1 && !run.is_empty() {
+                    run.push_str(cluster);
+                } else {
+                    // wide characters get their own position since fonts rarely match two columns
+                    canvas.text(start, row, std::mem::take(&mut run));
+                    start = col;
+                    run.push_str(cluster);
+                    if width != 1 {
+                        canvas.text(start, row, std::mem::take(&mut run));
+                    }
+                }
+            }
+            canvas.text(start, row, run);
+        }
+        canvas
+    }
+
+    fn write(&self, width: usize, height: usize, out: &mut impl Write) -> fmt::Result {
+        let (width, height) = (width * CELL_WIDTH, height * CELL_HEIGHT);
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        )?;
+        writeln!(
+            out,
+            r#"<g font-family="monospace" font-size="{FONT_SIZE}" fill="currentColor">"#
+        )?;
+        for (col, row, text) in &self.texts {
+            let (x, y) = (col * CELL_WIDTH, row * CELL_HEIGHT + ASCENT);
+            write!(out, r#"<text x="{x}" y="{y}">"#)?;
+            escape(text, out)?;
+            writeln!(out, "</text>")?;
+        }
+        writeln!(out, "</g>")?;
+        if !self.strokes.is_empty() {
+            writeln!(
+                out,
+                r#"<g stroke="currentColor" stroke-width="1.5" stroke-linecap="round">"#
+            )?;
+            for (x1, y1, x2, y2) in &self.strokes {
+                writeln!(out, r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}"/>"#)?;
+            }
+            writeln!(out, "</g>")?;
+        }
+        write!(out, "</svg>")
+    }
+}
+
+impl Conf {
+    /// Write `expr` as an svg image of its block layout
+    pub(crate) fn svg(self, expr: &Expression<'_>, out: &mut impl Write) -> fmt::Result {
+        // the drawing glyphs are replaced by strokes, so they must be the known unicode ones, and
+        // escape sequences aren't valid in xml
+        let conf = Conf {
+            ansi_color: false,
+            highlight: false,
+            ascii: false,
+            block_style: BlockStyle::Unicode,
+            ..self
+        };
+        let block = conf.block_render(expr);
+        Canvas::draw(&block).write(block.width(), block.lines().len(), out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cells;
+    use crate::Conf;
+
+    fn svg(inp: &str) -> String {
+        Conf::default().parse(inp).to_svg()
+    }
+
+    #[test]
+    fn clusters() {
+        assert_eq!(
+            cells("a⃗ 😀b"),
+            [(0, 1, "a⃗"), (1, 1, " "), (2, 2, "😀"), (4, 1, "b")]
+        );
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            svg("x + y"),
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="60" height="24" viewBox="0 0 60 24">"#,
+                "\n",
+                r#"<g font-family="monospace" font-size="20" fill="currentColor">"#,
+                "\n",
+                r#"<text x="0" y="18">x</text>"#,
+                "\n",
+                r#"<text x="24" y="18">+</text>"#,
+                "\n",
+                r#"<text x="48" y="18">y</text>"#,
+                "\n</g>\n</svg>",
+            )
+        );
+        assert!(svg("a < b").contains(">&lt;</text>"));
+    }
+
+    #[test]
+    fn fraction_bar() {
+        let res = svg("(a+b)/c");
+        assert!(!res.contains('─'), "{res}");
+        assert!(
+            res.contains(r#"<line x1="0" y1="36" x2="60" y2="36"/>"#),
+            "{res}"
+        );
+        assert!(res.contains(r#"<text x="24" y="66">c</text>"#), "{res}");
+    }
+
+    #[test]
+    fn radical() {
+        let res = svg("sqrt(x/y)");
+        assert!(!res.contains('╱') && !res.contains('─'), "{res}");
+        // the hook, the stem, the vinculum, and the fraction bar
+        assert_eq!(res.matches("<line ").count(), 7, "{res}");
+        // the vinculum continues from the top of the stem
+        assert!(
+            res.contains(r#"<line x1="48" y1="24" x2="60" y2="0"/>"#),
+            "{res}"
+        );
+        assert!(
+            res.contains(r#"<line x1="60" y1="0" x2="72" y2="0"/>"#),
+            "{res}"
+        );
+    }
+
+    #[test]
+    fn underscores_stay_text() {
+        assert!(svg("text(a_b)").contains(">a_b</text>"));
+    }
+
+    #[test]
+    fn terminal_options_ignored() {
+        let conf = Conf {
+            highlight: true,
+            ascii: true,
+            ..Conf::default()
+        };
+        let res = conf.parse("alpha/b").to_svg();
+        assert_eq!(
+            res,
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="12" height="72" viewBox="0 0 12 72">"#,
+                "\n",
+                r#"<g font-family="monospace" font-size="20" fill="currentColor">"#,
+                "\n",
+                r#"<text x="0" y="18">α</text>"#,
+                "\n",
+                r#"<text x="0" y="66">b</text>"#,
+                "\n</g>\n",
+                r#"<g stroke="currentColor" stroke-width="1.5" stroke-linecap="round">"#,
+                "\n",
+                r#"<line x1="0" y1="36" x2="12" y2="36"/>"#,
+                "\n</g>\n</svg>",
+            )
+        );
+        assert_eq!(res, svg("alpha/b"));
+    }
+
+    #[test]
+    fn deterministic() {
+        let inp = "sum_(i=1)^n 1/i^2 = pi^2/6";
+        assert_eq!(svg(inp), svg(inp));
+    }
+}
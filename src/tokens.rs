@@ -19,7 +19,7 @@ macro_rules! tokens {
     };
 }
 
-const UNICODE_TOKENS: [(&str, Token); 399] = tokens!(
+pub const UNICODE_TOKENS: [(&str, Token); 399] = tokens!(
     Frac => "/";
     Super => "^";
     Sub => "_";
@@ -396,11 +396,15 @@ pub fn symbol_str(inp: &str, skin_tone: SkinTone) -> &str {
         "larr" | "leftarrow" | "<-" => "←",
         "harr" | "leftrightarrow" | "<->" => "↔",
         "lArr" | "Leftarrow" | "<==" => "⇐",
-        // emoji
-        chr => {
-            let emoji = emojis::get_by_shortcode(&chr[1..chr.len() - 1]).unwrap();
-            emoji.with_skin_tone(skin_tone).unwrap_or(emoji).as_str()
-        }
+        // emoji, anything else like the output of a user defined symbol renders as itself
+        chr => match chr
+            .strip_prefix(':')
+            .and_then(|name| name.strip_suffix(':'))
+            .and_then(emojis::get_by_shortcode)
+        {
+            Some(emoji) => emoji.with_skin_tone(skin_tone).unwrap_or(emoji).as_str(),
+            None => chr,
+        },
     }
 }
 
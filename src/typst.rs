@@ -0,0 +1,787 @@
+//! Rendering of asciimath as typst math source
+
+use asciimath_parser::tree::{
+    Frac, Func, Group, Intermediate, Matrix, Script, ScriptFunc, Simple, SimpleBinary, SimpleFunc,
+    SimpleScript, SimpleUnary,
+};
+use std::fmt;
+use std::fmt::Write;
+
+use super::Conf;
+use super::ast::{extract_raw_text, extract_rows, extract_text, fused_unary, split_word};
+use super::tokens::{left_bracket_str, right_bracket_str, symbol_str};
+
+/// Characters that typst combines into shorthands like `->` or `<=` when adjacent
+const SHORTHAND: &str = "-+*/<>=!:.|~[]";
+
+/// Separates tokens that typst would otherwise read as one, and escapes argument separators
+///
+/// Adjacent letters form a single identifier in typst, e.g. `xy` instead of `x y`, and inside
+/// a function call a literal `,` or `;` would start a new argument.
+#[derive(Debug)]
+pub struct Typst<'a, W: ?Sized> {
+    inner: &'a mut W,
+    last: Option<char>,
+    calls: usize,
+}
+
+impl<'a, W: Write + ?Sized> Typst<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Typst {
+            inner,
+            last: None,
+            calls: 0,
+        }
+    }
+
+    /// Start a call to `func`, whose arguments are separated with [`Typst::sep`]
+    fn call(&mut self, func: &str) -> fmt::Result {
+        self.write_str(func)?;
+        self.calls += 1;
+        self.raw("(")
+    }
+
+    /// Separate arguments of a call, or the rows of a matrix with `;`
+    fn sep(&mut self, sep: &str) -> fmt::Result {
+        self.raw(sep)?;
+        self.raw(" ")
+    }
+
+    fn end_call(&mut self) -> fmt::Result {
+        self.calls -= 1;
+        self.raw(")")
+    }
+
+    /// Write syntax that should never be escaped
+    fn raw(&mut self, s: &str) -> fmt::Result {
+        self.last = s.chars().last().or(self.last);
+        self.inner.write_str(s)
+    }
+}
+
+impl<W: Write + ?Sized> Typst<'_, W> {
+    /// Write a string literal, which needs no escaping of separators
+    fn quoted(&mut self, text: &str) -> fmt::Result {
+        let mut literal = String::from('"');
+        for chr in text.chars() {
+            if matches!(chr, '"' | '\\') {
+                literal.push('\\');
+            }
+            literal.push(chr);
+        }
+        literal.push('"');
+        self.space(&literal)?;
+        self.raw(&literal)
+    }
+
+    /// Separate `next` from the previous token if typst would join them
+    fn space(&mut self, next: &str) -> fmt::Result {
+        if let (Some(last), Some(first)) = (self.last, next.chars().next()) {
+            let word = last.is_alphanumeric() && (first.is_alphanumeric() || first == '(');
+            let shorthand = SHORTHAND.contains(last) && SHORTHAND.contains(first);
+            if word || shorthand {
+                self.inner.write_char(' ')?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + ?Sized> Write for Typst<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        self.space(s)?;
+        self.last = s.chars().last();
+        if self.calls > 0 {
+            for chr in s.chars() {
+                if matches!(chr, ',' | ';') {
+                    self.inner.write_char('\\')?;
+                }
+                self.inner.write_char(chr)?;
+            }
+            Ok(())
+        } else {
+            self.inner.write_str(s)
+        }
+    }
+}
+
+/// The typst name for a rendered unicode symbol, if it has a common one
+#[allow(clippy::too_many_lines)]
+fn typst_symbol(unicode: &str) -> Option<&'static str> {
+    Some(match unicode {
+        // greek
+        "α" => "alpha",
+        "β" => "beta",
+        "χ" => "chi",
+        "δ" => "delta",
+        "Δ" => "Delta",
+        "ε" => "epsilon",
+        "ϵ" => "epsilon.alt",
+        "η" => "eta",
+        "γ" => "gamma",
+        "Γ" => "Gamma",
+        "ι" => "iota",
+        "κ" => "kappa",
+        "ϰ" => "kappa.alt",
+        "λ" => "lambda",
+        "Λ" => "Lambda",
+        "μ" => "mu",
+        "ν" => "nu",
+        "ω" => "omega",
+        "Ω" => "Omega",
+        "φ" => "phi",
+        "ϕ" => "phi.alt",
+        "Φ" => "Phi",
+        "π" => "pi",
+        "Π" => "Pi",
+        "ϖ" => "pi.alt",
+        "ψ" => "psi",
+        "Ψ" => "Psi",
+        "ρ" => "rho",
+        "ϱ" => "rho.alt",
+        "σ" => "sigma",
+        "Σ" => "Sigma",
+        "τ" => "tau",
+        "θ" => "theta",
+        "ϑ" => "theta.alt",
+        "Θ" => "Theta",
+        "υ" => "upsilon",
+        "Υ" => "Upsilon",
+        "ξ" => "xi",
+        "Ξ" => "Xi",
+        "ζ" => "zeta",
+        // operations
+        "⋅" => "dot.op",
+        "×" => "times",
+        "÷" => "div",
+        "⊕" => "plus.circle",
+        "⊗" => "times.circle",
+        "∑" => "sum",
+        "∏" => "product",
+        "∧" => "and",
+        "∨" => "or",
+        "∪" => "union",
+        // relations
+        "≠" => "!=",
+        "≤" => "<=",
+        "≪" => "<<",
+        "≥" => ">=",
+        "≫" => ">>",
+        "∈" => "in",
+        "∉" => "in.not",
+        "⊂" => "subset",
+        "⊃" => "supset",
+        "⊆" => "subset.eq",
+        "⊇" => "supset.eq",
+        "≡" => "equiv",
+        "≈" => "approx",
+        "∝" => "prop",
+        // logical
+        "¬" => "not",
+        "⇒" => "=>",
+        "⇔" => "<=>",
+        "∀" => "forall",
+        "∃" => "exists",
+        "∄" => "exists.not",
+        " and " => "\" and \"",
+        " or " => "\" or \"",
+        " if " => "\" if \"",
+        // misc
+        "∫" => "integral",
+        "∮" => "integral.cont",
+        "∬" => "integral.double",
+        "∭" => "integral.triple",
+        "∇" => "nabla",
+        "±" => "plus.minus",
+        "∓" => "minus.plus",
+        "∅" => "emptyset",
+        "∞" => "oo",
+        "ℵ" => "aleph",
+        "…" => "...",
+        "⋯" => "dots.c",
+        "⋮" => "dots.v",
+        "⋱" => "dots.down",
+        "ℂ" => "CC",
+        "ℕ" => "NN",
+        "ℚ" => "QQ",
+        "ℝ" => "RR",
+        "ℤ" => "ZZ",
+        "ℓ" => "ell",
+        // arrows
+        "↑" => "arrow.t",
+        "↓" => "arrow.b",
+        "→" => "->",
+        "↦" => "|->",
+        "←" => "<-",
+        "↔" => "<->",
+        // raw tokens
+        "\\" => "backslash",
+        "/" => "\\/",
+        "^" => "\\^",
+        "_" => "\\_",
+        _ => return None,
+    })
+}
+
+/// Whether typst has a built in operator for a function or underover identifier
+fn is_typst_operator(name: &str) -> bool {
+    matches!(
+        name,
+        "sin"
+            | "cos"
+            | "tan"
+            | "sinh"
+            | "cosh"
+            | "tanh"
+            | "cot"
+            | "sec"
+            | "csc"
+            | "arcsin"
+            | "arccos"
+            | "arctan"
+            | "coth"
+            | "exp"
+            | "log"
+            | "ln"
+            | "det"
+            | "gcd"
+            | "lim"
+            | "dim"
+            | "mod"
+            | "min"
+            | "max"
+    )
+}
+
+/// The typst delimiter of a matrix, or `None` when it needs explicit brackets
+fn typst_delim(left: &str, right: &str) -> Option<&'static str> {
+    match (left, right) {
+        ("(", ")") => Some(""),
+        ("[", "]") => Some("delim: \"[\", "),
+        ("{", "}") => Some("delim: \"{\", "),
+        ("|", "|") => Some("delim: \"|\", "),
+        ("", "") => Some("delim: #none, "),
+        _ => None,
+    }
+}
+
+/// The named typst colors, any other color is given as hex
+const TYPST_COLORS: &[&str] = &[
+    "black", "gray", "silver", "white", "navy", "blue", "aqua", "teal", "eastern", "purple",
+    "fuchsia", "maroon", "red", "orange", "yellow", "olive", "green", "lime",
+];
+
+/// Write an identifier, escaping characters that are typst syntax
+fn escaped(text: &str, out: &mut impl Write) -> fmt::Result {
+    let mut escaped = String::with_capacity(text.len());
+    for chr in text.chars() {
+        if matches!(chr, '#' | '$' | '&' | '_' | '^' | '/' | '\\' | '"' | '\'') {
+            escaped.push('\\');
+        }
+        escaped.push(chr);
+    }
+    out.write_str(&escaped)
+}
+
+impl Conf {
+    /// Render a call argument, stripping brackets from a group
+    fn typst_arg(
+        self,
+        simple: &Simple<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match simple {
+            // an empty argument would be a call with too few arguments
+            Simple::Missing => out.write_str("\"\""),
+            Simple::Group(Group { expr, .. }) if self.strip_brackets => {
+                self.typst_expression(expr, out)
+            }
+            simple => self.typst_simple(simple, out),
+        }
+    }
+
+    fn typst_call(
+        self,
+        func: &str,
+        arg: &Simple<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.call(func)?;
+        self.typst_arg(arg, out)?;
+        out.end_call()
+    }
+
+    fn typst_simpleunary(
+        self,
+        unary: &SimpleUnary<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match (unary.op, unary.arg()) {
+            ("sqrt", arg) => self.typst_call("sqrt", arg, out),
+            // fonts
+            ("bb" | "mathbf", arg) => self.typst_call("bold", arg, out),
+            ("bbb" | "mathbb", arg) => self.typst_call("bb", arg, out),
+            ("cc" | "mathcal", arg) => self.typst_call("cal", arg, out),
+            ("tt" | "mathtt", arg) => self.typst_call("mono", arg, out),
+            ("fr" | "mathfrak", arg) => self.typst_call("frak", arg, out),
+            ("sf" | "mathsf", arg) => self.typst_call("sans", arg, out),
+            ("it" | "mathit", arg) => self.typst_call("italic", arg, out),
+            // functions
+            ("abs" | "Abs", arg) => self.typst_call("abs", arg, out),
+            ("ceil", arg) => self.typst_call("ceil", arg, out),
+            ("floor", arg) => self.typst_call("floor", arg, out),
+            ("norm", arg) => self.typst_call("norm", arg, out),
+            ("text" | "mbox", arg) => out.quoted(&extract_text(arg)),
+            // modifiers
+            ("overline", arg) => self.typst_call("overline", arg, out),
+            ("underline" | "ul", arg) => self.typst_call("underline", arg, out),
+            ("cancel", arg) => self.typst_call("cancel", arg, out),
+            ("hat", arg) => self.typst_call("hat", arg, out),
+            ("tilde", arg) => self.typst_call("tilde", arg, out),
+            ("bar", arg) => self.typst_call("macron", arg, out),
+            ("dot", arg) => self.typst_call("dot", arg, out),
+            ("ddot", arg) => self.typst_call("dot.double", arg, out),
+            ("vec", arg) => self.typst_call("arrow", arg, out),
+            ("overarc" | "overparen", arg) => self.typst_call("overparen", arg, out),
+            ("ubrace" | "underbrace", arg) => self.typst_call("underbrace", arg, out),
+            ("obrace" | "overbrace", arg) => self.typst_call("overbrace", arg, out),
+            // generic
+            (op, arg) => {
+                Self::typst_name(op, out)?;
+                self.typst_simple(arg, out)
+            }
+        }
+    }
+
+    fn typst_simplebinary(
+        self,
+        binary: &SimpleBinary<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match (binary.op, binary.first(), binary.second()) {
+            ("frac", numer, denom) => {
+                out.call("frac")?;
+                self.typst_arg(numer, out)?;
+                out.sep(",")?;
+                self.typst_arg(denom, out)?;
+                out.end_call()
+            }
+            ("root", index, arg) => {
+                out.call("root")?;
+                self.typst_arg(index, out)?;
+                out.sep(",")?;
+                self.typst_arg(arg, out)?;
+                out.end_call()
+            }
+            (op @ ("stackrel" | "overset" | "underset"), first, second) => {
+                out.call("attach")?;
+                self.typst_call("limits", second, out)?;
+                out.sep(",")?;
+                out.raw(if op == "underset" { "b: " } else { "t: " })?;
+                self.typst_arg(first, out)?;
+                out.end_call()
+            }
+            ("color", color, arg) => {
+                let color = extract_raw_text(color);
+                out.write_str("#text(fill: ")?;
+                if TYPST_COLORS.contains(&color.as_str()) {
+                    out.raw(&color)?;
+                } else {
+                    out.raw("rgb(")?;
+                    out.quoted(&color)?;
+                    out.raw(")")?;
+                }
+                out.raw(")[$")?;
+                self.typst_arg(arg, out)?;
+                out.raw("$]")
+            }
+            // typst has no element ids or classes, so only the content remains
+            ("id" | "class", _, arg) => self.typst_arg(arg, out),
+            // generic
+            (op, first, second) => {
+                Self::typst_name(op, out)?;
+                self.typst_simple(first, out)?;
+                self.typst_simple(second, out)
+            }
+        }
+    }
+
+    fn typst_name(name: &str, out: &mut Typst<'_, impl Write + ?Sized>) -> fmt::Result {
+        if is_typst_operator(name) {
+            out.write_str(name)
+        } else if name.chars().count() > 1 {
+            out.write_str("op(")?;
+            out.quoted(name)?;
+            out.raw(")")
+        } else {
+            escaped(name, out)
+        }
+    }
+
+    fn typst_simplefunc(
+        self,
+        func: &SimpleFunc<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        Self::typst_name(func.func, out)?;
+        self.typst_simple(func.arg(), out)
+    }
+
+    fn typst_group(
+        self,
+        group: &Group<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        let left = left_bracket_str(group.left_bracket);
+        let right = right_bracket_str(group.right_bracket);
+        if let ("{", "") = (left, right)
+            && let Some(rows) = extract_rows(&group.expr)
+            && rows[0].len() > 1
+        {
+            return self.typst_cases(&rows, out);
+        }
+        // a lone parenthesis would end a call or start an argument list
+        match left {
+            "(" if right != ")" => out.write_str("\\(")?,
+            left => out.write_str(left)?,
+        }
+        self.typst_expression(&group.expr, out)?;
+        match right {
+            ")" if left != "(" => out.write_str("\\)"),
+            right => out.write_str(right),
+        }
+    }
+
+    /// Piecewise cases like `{(x, if x > 0), (-x, otherwise):}`
+    fn typst_cases(
+        self,
+        rows: &[Vec<&[Intermediate<'_>]>],
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        out.call("cases")?;
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                out.sep(",")?;
+            }
+            let (value, conditions) = row
+                .split_first()
+                .unwrap_or_else(|| unreachable!("cases have two or more cells per row"));
+            self.typst_expression(value, out)?;
+            for cond in conditions {
+                // align the conditions of every case
+                out.raw(" & ")?;
+                self.typst_condition(cond, out)?;
+            }
+        }
+        out.end_call()
+    }
+
+    /// A condition of a case, with words like `otherwise` written as text rather than letters
+    fn typst_condition(
+        self,
+        mut cond: &[Intermediate<'_>],
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        while !cond.is_empty() {
+            if let Some((word, rest)) = split_word(cond) {
+                out.quoted(&word)?;
+                cond = rest;
+            } else {
+                self.typst_expression(&cond[..1], out)?;
+                cond = &cond[1..];
+            }
+        }
+        Ok(())
+    }
+
+    fn typst_matrix(
+        self,
+        matrix: &Matrix<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        let left = left_bracket_str(matrix.left_bracket);
+        let right = right_bracket_str(matrix.right_bracket);
+        let delim = typst_delim(left, right);
+        if delim.is_none() {
+            out.write_str(left)?;
+        }
+        out.call("mat")?;
+        out.raw(delim.unwrap_or("delim: #none, "))?;
+        for (i, row) in matrix.rows().enumerate() {
+            if i > 0 {
+                out.sep(";")?;
+            }
+            for (j, expr) in row.iter().enumerate() {
+                if j > 0 {
+                    out.sep(",")?;
+                }
+                self.typst_expression(expr, out)?;
+            }
+        }
+        out.end_call()?;
+        if delim.is_none() {
+            out.write_str(right)?;
+        }
+        Ok(())
+    }
+
+    fn typst_simple(
+        self,
+        simple: &Simple<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match simple {
+            Simple::Missing => Ok(()),
+            &Simple::Number(num) => out.write_str(num),
+            &Simple::Text(text) => out.quoted(text),
+            &Simple::Ident(ident) => Self::typst_name(ident, out),
+            &Simple::Symbol(symbol) => match symbol {
+                "quad" => out.write_str("quad"),
+                "qquad" => out.write_str("wide"),
+                "\\ " => out.write_str("space"),
+                symbol => {
+                    let unicode = symbol_str(symbol, self.skin_tone);
+                    out.write_str(typst_symbol(unicode).unwrap_or(unicode))
+                }
+            },
+            Simple::Func(func) => self.typst_simplefunc(func, out),
+            Simple::Unary(unary) => self.typst_simpleunary(unary, out),
+            Simple::Binary(binary) => self.typst_simplebinary(binary, out),
+            Simple::Group(group) => self.typst_group(group, out),
+            Simple::Matrix(matrix) => self.typst_matrix(matrix, out),
+        }
+    }
+
+    /// A script, in parentheses unless it's a single token
+    fn typst_script_arg(
+        self,
+        simple: &Simple<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match simple {
+            Simple::Number(_) | Simple::Ident(_) | Simple::Symbol(_) | Simple::Text(_) => {
+                self.typst_simple(simple, out)
+            }
+            Simple::Missing => self.typst_arg(simple, out),
+            simple => {
+                out.raw("(")?;
+                self.typst_arg(simple, out)?;
+                out.raw(")")
+            }
+        }
+    }
+
+    fn typst_script(
+        self,
+        script: &Script<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        if let Some(sub) = script.sub() {
+            out.raw("_")?;
+            self.typst_script_arg(sub, out)?;
+        }
+        if let Some(sup) = script.sup() {
+            out.raw("^")?;
+            self.typst_script_arg(sup, out)?;
+        }
+        Ok(())
+    }
+
+    fn typst_func(self, func: &Func<'_>, out: &mut Typst<'_, impl Write + ?Sized>) -> fmt::Result {
+        Self::typst_name(func.func, out)?;
+        self.typst_script(&func.script, out)?;
+        self.typst_scriptfunc(func.arg(), out)
+    }
+
+    fn typst_scriptfunc(
+        self,
+        func: &ScriptFunc<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match func {
+            ScriptFunc::Simple(SimpleScript { simple, script }) => {
+                // a script on nothing still needs a base
+                if matches!(simple, Simple::Missing) && !matches!(script, Script::None) {
+                    out.write_str("\"\"")?;
+                }
+                self.typst_simple(simple, out)?;
+                self.typst_script(script, out)
+            }
+            ScriptFunc::Func(func) => self.typst_func(func, out),
+        }
+    }
+
+    /// A fraction operand, stripping brackets from an unscripted group
+    fn typst_frac_arg(
+        self,
+        func: &ScriptFunc<'_>,
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        match func {
+            ScriptFunc::Simple(SimpleScript {
+                simple,
+                script: Script::None,
+            }) => self.typst_arg(simple, out),
+            func => self.typst_scriptfunc(func, out),
+        }
+    }
+
+    fn typst_frac(self, frac: &Frac<'_>, out: &mut Typst<'_, impl Write + ?Sized>) -> fmt::Result {
+        out.call("frac")?;
+        self.typst_frac_arg(&frac.numer, out)?;
+        out.sep(",")?;
+        self.typst_frac_arg(&frac.denom, out)?;
+        out.end_call()
+    }
+
+    pub(crate) fn typst_expression(
+        self,
+        expr: &[Intermediate<'_>],
+        out: &mut Typst<'_, impl Write + ?Sized>,
+    ) -> fmt::Result {
+        let mut rest = expr;
+        while let Some((inter, tail)) = rest.split_first() {
+            // names like `hbar` or `lceil` are tokenized as a letter directly followed by an
+            // operator
+            let fused = match fused_unary(rest) {
+                Some(("h", unary, script)) if unary.op == "bar" => Some(("ℏ", unary, script)),
+                Some(("l", unary, script)) if unary.op == "ceil" => Some(("⌈", unary, script)),
+                Some(("r", unary, script)) if unary.op == "ceil" => Some(("⌉", unary, script)),
+                Some(("l", unary, script)) if unary.op == "floor" => Some(("⌊", unary, script)),
+                Some(("r", unary, script)) if unary.op == "floor" => Some(("⌋", unary, script)),
+                _ => None,
+            };
+            if let Some((symbol, unary, script)) = fused {
+                out.write_str(symbol)?;
+                self.typst_simple(unary.arg(), out)?;
+                self.typst_script(script, out)?;
+                rest = &rest[2..];
+                continue;
+            }
+            rest = tail;
+            match inter {
+                Intermediate::ScriptFunc(func) => self.typst_scriptfunc(func, out)?,
+                Intermediate::Frac(frac) => self.typst_frac(frac, out)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Conf;
+
+    fn render(inp: &str) -> String {
+        Conf::default().parse(inp).to_typst()
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(render("x + y"), "x+y");
+        assert_eq!(render("x y"), "x y");
+        assert_eq!(render("alpha beta"), "alpha beta");
+        assert_eq!(render("a != b"), "a!=b");
+        assert_eq!(render("x in RR"), "x in RR");
+        assert_eq!(render("a -> b"), "a->b");
+        assert_eq!(render("x - > y"), "x- >y");
+        assert_eq!(render("x quad y"), "x quad y");
+        assert_eq!(render("a and b"), "a\" and \"b");
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(render("1/2"), "frac(1, 2)");
+        assert_eq!(render("(x+1)/y"), "frac(x+1, y)");
+        assert_eq!(render("x^2/y"), "frac(x^2, y)");
+        assert_eq!(render("frac(a)(b)"), "frac(a, b)");
+        assert_eq!(render("(a,b)/c"), "frac(a\\,b, c)");
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(render("sqrt x"), "sqrt(x)");
+        assert_eq!(render("root(3)(x)"), "root(3, x)");
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(render("x^2"), "x^2");
+        assert_eq!(render("x_(i+1)"), "x_(i+1)");
+        assert_eq!(render("sum_(i=1)^n i"), "sum_(i=1)^n i");
+        assert_eq!(render("lim_(x->0)"), "lim_(x->0)");
+        assert_eq!(render("overset(def)(=)"), "attach(limits(=), t: d e f)");
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(render("sin x"), "sin x");
+        assert_eq!(render("sin^2 x"), "sin^2 x");
+        assert_eq!(render("f(x)"), "f (x)");
+        assert_eq!(render("sech x"), "op(\"sech\")x");
+    }
+
+    #[test]
+    fn fonts_and_accents() {
+        assert_eq!(render("bbb R"), "bb(R)");
+        assert_eq!(render("bb(x)"), "bold(x)");
+        assert_eq!(render("vec x"), "arrow(x)");
+        assert_eq!(render("text(if x)"), "\"if x\"");
+        assert_eq!(render("\"a\\b\""), "\"a\\\\b\"");
+        assert_eq!(render("abs(x)"), "abs(x)");
+        assert_eq!(
+            render("color(red)(x) color(#f00)(y)"),
+            "#text(fill: red)[$x$]#text(fill: rgb(\"#f00\"))[$y$]"
+        );
+    }
+
+    #[test]
+    fn brackets_and_matrices() {
+        assert_eq!(render("{x}"), "{x}");
+        assert_eq!(render("(a, b]"), "\\(a,b]");
+        assert_eq!(render("[[1,2],[3,4]]"), "mat(delim: \"[\", 1, 2; 3, 4)");
+        assert_eq!(render("((1, x), (2, y))"), "mat(1, x; 2, y)");
+    }
+
+    #[test]
+    fn cases() {
+        assert_eq!(
+            render("{(x, x>0),(-x, x<=0):}"),
+            "cases(x & x>0, -x & x<=0)"
+        );
+        assert_eq!(
+            render("{(x, if x > 0), (-x, otherwise):}"),
+            "cases(x & \" if \"x>0, -x & \"otherwise\")"
+        );
+        assert_eq!(
+            render("{(1, if x > 0), (0, text(otherwise)):}"),
+            "cases(1 & \" if \"x>0, 0 & \"otherwise\")"
+        );
+        // rows of different lengths aren't cases
+        assert_eq!(render("{(a,b),(c):}"), "{(a,b),(c)");
+    }
+
+    #[test]
+    fn missing_arguments() {
+        assert_eq!(render("sqrt"), "sqrt(\"\")");
+        assert_eq!(render("hat"), "hat(\"\")");
+        assert_eq!(render("bar"), "macron(\"\")");
+        assert_eq!(render("ubrace"), "underbrace(\"\")");
+        assert_eq!(render("frac a"), "frac(a, \"\")");
+        assert_eq!(render("x/"), "frac(x, \"\")");
+        assert_eq!(render("root 3"), "root(3, \"\")");
+        assert_eq!(render("x^"), "x^\"\"");
+    }
+
+    #[test]
+    fn fused_names() {
+        assert_eq!(render("hbar"), "ℏ");
+        assert_eq!(render("hbar omega"), "ℏ omega");
+        assert_eq!(render("h bar x"), "h macron(x)");
+        assert_eq!(render("lceil x rceil"), "⌈x⌉");
+        assert_eq!(render("lceil"), "⌈");
+    }
+}
@@ -1,13 +1,27 @@
 //! Integration tests for the `asciimath-unicode` binary.
 #![cfg(feature = "binary")]
 
+use std::fs;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 /// Run the binary with `args`, feeding `input` on stdin, and return its stdout.
+///
+/// No user config file is visible to the binary.
 fn run(args: &[&str], input: &str) -> String {
+    run_in(
+        &Path::new(env!("CARGO_TARGET_TMPDIR")).join("no-config"),
+        args,
+        input,
+    )
+}
+
+/// Run the binary with `config_home` as the user config directory.
+fn run_in(config_home: &Path, args: &[&str], input: &str) -> String {
     let mut child = Command::new(env!("CARGO_BIN_EXE_asciimath-unicode"))
         .args(args)
+        .env("XDG_CONFIG_HOME", config_home)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
@@ -73,3 +87,328 @@ fn skin_tone() {
     assert_eq!(run(&["--skin-tone", "medium-dark"], ":hand:"), "✋🏾\n");
     assert_eq!(run(&["--skin-tone", "dark"], ":hand:"), "✋🏿\n");
 }
+
+#[test]
+fn block_tall_operators() {
+    assert_eq!(
+        run(&["--block", "--tall-operators"], "int_0^1 x"),
+        "⌠1\n⎮ x\n⌡0\n"
+    );
+}
+
+#[test]
+fn ansi_color() {
+    assert_eq!(
+        run(&["--ansi-color"], "color(red)(x)+1"),
+        "\x1b[31mx\x1b[39m+1\n"
+    );
+    assert_eq!(
+        run(&["--highlight"], "x+1"),
+        "\x1b[34mx\x1b[39m\x1b[35m+\x1b[39m\x1b[36m1\x1b[39m\n"
+    );
+}
+
+#[test]
+fn max_width() {
+    assert_eq!(run(&["--max-width", "6"], "a + b + c + d"), "a+b+c\n  +d\n");
+    assert_eq!(
+        run(&["--block", "--max-width", "6"], "a + b + c"),
+        "a + b\n  + c\n"
+    );
+}
+
+#[test]
+fn ascii() {
+    assert_eq!(run(&["--ascii"], "alpha <= 1/2"), "alpha<=1/2\n");
+    assert_eq!(run(&["--ascii", "--block"], "x/y"), "x\n-\ny\n");
+}
+
+#[test]
+fn block_style() {
+    assert_eq!(
+        run(&["--block", "--block-style", "heavy"], "[[a],[b]]"),
+        "┏a┓\n┃ ┃\n┗b┛\n"
+    );
+}
+
+#[test]
+fn document() {
+    assert_eq!(
+        run(&["--document"], "area `pi r^2`\nhalf $1/2$\n"),
+        "area πr²\nhalf ½\n"
+    );
+    assert_eq!(
+        run(
+            &["--document", "--delimiter", "<m>", "</m>"],
+            "`x` <m>x^2</m>"
+        ),
+        "`x` x²"
+    );
+}
+
+#[test]
+fn lines() {
+    assert_eq!(run(&["--lines"], "1/2\nx^2\r\n\nalpha"), "½\nx²\n\nα\n");
+    assert_eq!(
+        run(
+            &["--lines", "--block", "--record-separator", "\\n\\n"],
+            "x/y\na"
+        ),
+        "x\n─\ny\n\na\n\n"
+    );
+}
+
+#[test]
+fn lines_invalid_utf8() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_asciimath-unicode"))
+        .arg("--lines")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn binary");
+    child
+        .stdin
+        .take()
+        .expect("stdin not piped")
+        .write_all(b"1/2\n\xff\nx^2\n")
+        .expect("failed to write stdin");
+    let output = child.wait_with_output().expect("failed to wait on binary");
+    // the records after the invalid one are still converted
+    assert!(!output.status.success());
+    assert_eq!(output.stdout, "½\n\nx²\n".as_bytes());
+    let stderr = String::from_utf8(output.stderr).expect("stderr was not utf-8");
+    assert!(
+        stderr.starts_with("error: record 2 is not utf-8"),
+        "{stderr}"
+    );
+}
+
+/// Assert that the binary exits quietly when stdout is closed before it writes `input`.
+fn closed_stdout(args: &[&str], input: &str) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_asciimath-unicode"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn binary");
+    drop(child.stdout.take());
+    // the binary may exit before reading all of this
+    let _ = child
+        .stdin
+        .take()
+        .expect("stdin not piped")
+        .write_all(input.repeat(1_000).as_bytes());
+    let output = child.wait_with_output().expect("failed to wait on binary");
+    assert!(output.status.success(), "{args:?}");
+    assert!(output.stderr.is_empty(), "{args:?}: {:?}", output.stderr);
+}
+
+#[test]
+fn closed_stdout_exits_quietly() {
+    closed_stdout(&["--lines"], "x/y\n");
+    closed_stdout(&["--null"], "x/y\0");
+    closed_stdout(&["--document"], "some `x/y` prose\n");
+    closed_stdout(&["--repl"], "x/y\n");
+    closed_stdout(&["--json"], "{\"input\": \"x/y\"}\n");
+    closed_stdout(&["--aligned"], "x = y\n");
+    closed_stdout(&[], "x/y ");
+}
+
+#[test]
+fn aligned() {
+    assert_eq!(
+        run(&["--aligned"], "2x + 3 = 7\n\n= 4 + 3\r\nx = 2\n"),
+        "2x + 3 = 7\n       = 4 + 3\n     x = 2\n"
+    );
+}
+
+#[test]
+fn null_separated() {
+    assert_eq!(run(&["-0"], "1/2\0x\ny\0"), "½\0xy\0");
+    assert_eq!(run(&["-0", "--record-separator", ";"], "a\0b"), "a;b;");
+    // a separator needs records to separate
+    let status = Command::new(env!("CARGO_BIN_EXE_asciimath-unicode"))
+        .args(["--record-separator", ";"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .expect("failed to run binary");
+    assert!(!status.success());
+}
+
+#[test]
+fn json() {
+    let responses = run(
+        &["--json"],
+        concat!(
+            r#"{"id": 1, "input": "1/2"}"#,
+            "\n\n",
+            r#"{"id": "b", "input": "x/y", "conf": {"block": true, "skin_tone": "dark"}}"#,
+            "\n",
+            r#"{"input": "hat(xy)"}"#,
+            "\n",
+            r#"{"id": 4, "input": 3}"#,
+            "\n",
+        ),
+    );
+    let lines: Vec<_> = responses.lines().collect();
+    assert_eq!(lines[0], r#"{"id":1,"output":"½","warnings":[]}"#);
+    assert_eq!(lines[1], r#"{"id":"b","output":"x\n─\ny","warnings":[]}"#);
+    assert_eq!(
+        lines[2],
+        r#"{"id":null,"output":"hat (xy)","warnings":[{"kind":"unsupported_unary","message":"unsupported unary operator","start":0,"end":7}]}"#
+    );
+    assert!(lines[3].starts_with(r#"{"id":4,"error":"#));
+    assert_eq!(lines.len(), 4);
+}
+
+#[test]
+fn json_invalid_utf8() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_asciimath-unicode"))
+        .arg("--json")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn binary");
+    child
+        .stdin
+        .take()
+        .expect("stdin not piped")
+        .write_all(b"{\"id\": 1, \"input\": \"\xff\"}\n{\"id\": 2, \"input\": \"1/2\"}\n")
+        .expect("failed to write stdin");
+    let output = child.wait_with_output().expect("failed to wait on binary");
+    // the requests after the invalid one are still served
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).expect("stdout was not utf-8"),
+        concat!(
+            r#"{"id":null,"error":"request is not utf-8"}"#,
+            "\n",
+            r#"{"id":2,"output":"½","warnings":[]}"#,
+            "\n",
+        )
+    );
+}
+
+/// Write `contents` to `name` in a fresh config directory for `test`.
+fn config_home(test: &str, name: &str, contents: &str) -> PathBuf {
+    let home = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+    let dir = home.join("asciimath-unicode");
+    fs::create_dir_all(&dir).expect("failed to create config dir");
+    fs::write(dir.join(name), contents).expect("failed to write config");
+    home
+}
+
+#[test]
+fn config_file() {
+    let home = config_home(
+        "config_file",
+        "config.toml",
+        "block = true\nvulgar_fracs = false\nblock_style = \"ascii\"\n",
+    );
+    assert_eq!(run_in(&home, &[], "x/y"), "x\n-\ny\n");
+    // flags override the config
+    assert_eq!(
+        run_in(&home, &["--block-style", "heavy"], "x/y"),
+        "x\n━\ny\n"
+    );
+    assert_eq!(run_in(&home, &["--no-config"], "1/2"), "½\n");
+}
+
+#[test]
+fn multi_person_skin_tone() {
+    let home = config_home(
+        "multi_person_skin_tone",
+        "config.toml",
+        "skin_tone = \"light-and-dark\"\n",
+    );
+    assert_eq!(run_in(&home, &[], ":people_holding_hands:"), "🧑🏻‍🤝‍🧑🏿\n");
+    // json requests and the repl serialize the configuration
+    assert_eq!(
+        run_in(&home, &["--json"], r#"{"input": ":people_holding_hands:"}"#),
+        "{\"id\":null,\"output\":\"🧑🏻‍🤝‍🧑🏿\",\"warnings\":[]}\n"
+    );
+    assert!(
+        run_in(&home, &["--repl"], ":conf\n")
+            .lines()
+            .any(|line| line == "skin_tone = \"light-and-dark\"")
+    );
+    // the command line accepts the same names as the config file
+    assert_eq!(
+        run(&["--skin-tone", "light-and-dark"], ":people_holding_hands:"),
+        "🧑🏻‍🤝‍🧑🏿\n"
+    );
+}
+
+#[test]
+fn flags_turn_off_config() {
+    let home = config_home(
+        "flags_turn_off_config",
+        "config.toml",
+        "block = true\nascii = true\nvulgar_fracs = false\nmax_width = 4\n",
+    );
+    assert_eq!(
+        run_in(&home, &[], "1/2 + 1/3"),
+        "1\n-\n2\n    1\n  + -\n    3\n"
+    );
+    assert_eq!(
+        run_in(
+            &home,
+            &["--no-block", "--no-ascii", "--vulgar-fracs"],
+            "1/2"
+        ),
+        "½\n"
+    );
+    assert_eq!(
+        run_in(&home, &["--no-block", "--no-max-width"], "a + b + c"),
+        "a+b+c\n"
+    );
+    // the last of a flag pair wins
+    assert_eq!(
+        run_in(&home, &["--no-block", "--block", "--no-ascii"], "x/y"),
+        "x\n─\ny\n"
+    );
+}
+
+#[test]
+fn explicit_config() {
+    let home = config_home("explicit_config", "config.json", r#"{"skin_tone": "dark"}"#);
+    let path = home.join("asciimath-unicode").join("config.json");
+    assert_eq!(run(&["--config", path.to_str().unwrap()], ":hand:"), "✋🏿\n");
+}
+
+#[test]
+fn repl() {
+    let session = run(
+        &["--repl"],
+        "1/2\n:vulgar off\n1/2\n:block on\nx/y\n:tone dark\n:hand:\n:history\n!1\n:nope\n:quit\nz\n",
+    );
+    assert_eq!(
+        session,
+        "½\n¹⁄₂\nx\n─\ny\n✋🏿\n   1  1/2\n   2  1/2\n   3  x/y\n   4  :hand:\n¹⁄₂\nerror: unknown command :nope, see :help\n"
+    );
+    assert_eq!(
+        run(&["--repl"], ":tone\n:align up\n"),
+        concat!(
+            "error: usage: :tone NAME, one of default, light, medium-light, medium, medium-dark, dark, ",
+            "light-and-medium-light, light-and-medium, light-and-medium-dark, light-and-dark, ",
+            "medium-light-and-light, medium-light-and-medium, medium-light-and-medium-dark, ",
+            "medium-light-and-dark, medium-and-light, medium-and-medium-light, ",
+            "medium-and-medium-dark, medium-and-dark, medium-dark-and-light, ",
+            "medium-dark-and-medium-light, medium-dark-and-medium, medium-dark-and-dark, ",
+            "dark-and-light, dark-and-medium-light, dark-and-medium, dark-and-medium-dark\n",
+            "error: expected one of center, left, right, decimal, got \"up\"\n",
+        )
+    );
+}
+
+#[test]
+fn explain() {
+    assert_eq!(
+        run(&["--explain", "--no-vulgar-fracs"], "1/2"),
+        "fraction => script fraction\n  - not vulgar fraction: vulgar_fracs is off\n  - not one fraction: needs both vulgar_fracs and script_fracs\n  numerator: number 1\n  denominator: number 2\n\n¹⁄₂\n"
+    );
+}